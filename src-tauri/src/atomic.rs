@@ -0,0 +1,281 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// 原子写入单个文件：先写临时文件并同步到磁盘，再重命名覆盖目标文件
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let temp_path = temp_path_for(path);
+    write_synced(&temp_path, contents.as_ref())?;
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e).with_context(|| format!("Failed to replace {}", path.display()));
+    }
+
+    sync_parent(path)
+}
+
+/// 多文件事务中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransactionEntry {
+    temp: PathBuf,
+    target: PathBuf,
+}
+
+/// 事务日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransactionLog {
+    committed: bool,
+    entries: Vec<TransactionEntry>,
+}
+
+/// 多文件写入事务
+///
+/// 提交时先写入并同步所有临时文件，再把日志标记为已提交，最后逐个重命名。
+/// 中途崩溃后由 `recover` 处理：已提交的事务继续完成重命名，未提交的事务丢弃临时文件，
+/// 因此所有文件要么全部更新，要么全部保持原样。
+pub struct Transaction {
+    log_dir: PathBuf,
+    staged: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Transaction {
+    /// 创建事务，日志写入 `log_dir`
+    pub fn new(log_dir: &Path) -> Self {
+        Self {
+            log_dir: log_dir.to_path_buf(),
+            staged: Vec::new(),
+        }
+    }
+
+    /// 暂存一个文件写入
+    pub fn write(&mut self, path: &Path, contents: impl Into<Vec<u8>>) {
+        self.staged.retain(|(staged_path, _)| staged_path != path);
+        self.staged.push((path.to_path_buf(), contents.into()));
+    }
+
+    /// 提交事务
+    pub fn commit(self) -> Result<()> {
+        if self.staged.is_empty() {
+            return Ok(());
+        }
+        if self.staged.len() == 1 {
+            let (path, contents) = &self.staged[0];
+            return write_file(path, contents);
+        }
+
+        fs::create_dir_all(&self.log_dir)
+            .context("Failed to create transaction log directory")?;
+        let log_path = self.log_dir.join(format!("{}.json", Uuid::new_v4()));
+
+        let mut log = TransactionLog {
+            committed: false,
+            entries: self.staged
+                .iter()
+                .map(|(path, _)| TransactionEntry {
+                    temp: temp_path_for(path),
+                    target: path.clone(),
+                })
+                .collect(),
+        };
+
+        // 先记录将要写入的临时文件，崩溃后可以清理
+        write_file(&log_path, serde_json::to_vec(&log)?)?;
+
+        for ((_, contents), entry) in self.staged.iter().zip(&log.entries) {
+            if let Err(e) = write_synced(&entry.temp, contents) {
+                rollback(&log_path, &log);
+                return Err(e);
+            }
+        }
+
+        // 日志标记为已提交后，事务即视为成功
+        log.committed = true;
+        if let Err(e) = write_file(&log_path, serde_json::to_vec(&log)?) {
+            rollback(&log_path, &log);
+            return Err(e);
+        }
+
+        apply(&log)?;
+        fs::remove_file(&log_path)
+            .context("Failed to remove transaction log")?;
+
+        Ok(())
+    }
+}
+
+/// 恢复上次未完成的事务，返回处理的事务数量
+pub fn recover(log_dir: &Path) -> Result<usize> {
+    if !log_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut recovered = 0;
+    for entry in fs::read_dir(log_dir).context("Failed to read transaction log directory")? {
+        let log_path = entry?.path();
+        if log_path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+
+        // 日志本身损坏说明事务尚未开始写入临时文件
+        let log: TransactionLog = match fs::read(&log_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        {
+            Some(log) => log,
+            None => {
+                fs::remove_file(&log_path).context("Failed to remove transaction log")?;
+                continue;
+            }
+        };
+
+        if log.committed {
+            apply(&log)?;
+            fs::remove_file(&log_path).context("Failed to remove transaction log")?;
+        } else {
+            rollback(&log_path, &log);
+        }
+        recovered += 1;
+    }
+
+    Ok(recovered)
+}
+
+/// 把已提交事务的临时文件重命名到目标位置（可重复执行）
+fn apply(log: &TransactionLog) -> Result<()> {
+    for entry in &log.entries {
+        if entry.temp.exists() {
+            fs::rename(&entry.temp, &entry.target)
+                .with_context(|| format!("Failed to replace {}", entry.target.display()))?;
+            sync_parent(&entry.target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 丢弃未提交事务的临时文件和日志
+fn rollback(log_path: &Path, log: &TransactionLog) {
+    for entry in &log.entries {
+        let _ = fs::remove_file(&entry.temp);
+    }
+    let _ = fs::remove_file(log_path);
+}
+
+/// 写入文件并同步到磁盘
+fn write_synced(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.sync_all()
+        .with_context(|| format!("Failed to sync {}", path.display()))
+}
+
+/// 同步父目录，确保重命名已落盘（Windows 上目录无法单独同步）
+fn sync_parent(path: &Path) -> Result<()> {
+    if cfg!(unix) {
+        if let Some(parent) = path.parent() {
+            File::open(parent)
+                .and_then(|dir| dir.sync_all())
+                .with_context(|| format!("Failed to sync {}", parent.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 同目录下的临时文件路径
+fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    path.with_file_name(format!(".{}.{}.tmp", file_name, Uuid::new_v4()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn write_file_replaces_contents() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("content.md");
+
+        write_file(&path, "第一版").unwrap();
+        write_file(&path, "第二版").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "第二版");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn committed_transaction_is_rolled_forward_on_recovery() {
+        let dir = TempDir::new().unwrap();
+        let log_dir = dir.path().join("transactions");
+        let content = dir.path().join("content.md");
+        let metadata = dir.path().join("metadata.json");
+        fs::write(&content, "old").unwrap();
+        fs::write(&metadata, "old").unwrap();
+
+        // 模拟已提交但只完成了一半重命名时崩溃
+        fs::create_dir_all(&log_dir).unwrap();
+        let log = TransactionLog {
+            committed: true,
+            entries: vec![
+                TransactionEntry { temp: temp_path_for(&content), target: content.clone() },
+                TransactionEntry { temp: temp_path_for(&metadata), target: metadata.clone() },
+            ],
+        };
+        fs::write(&log.entries[1].temp, "new").unwrap();
+        fs::write(&content, "new").unwrap();
+        fs::write(log_dir.join("crash.json"), serde_json::to_vec(&log).unwrap()).unwrap();
+
+        assert_eq!(recover(&log_dir).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&content).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&metadata).unwrap(), "new");
+        assert_eq!(fs::read_dir(&log_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn uncommitted_transaction_is_rolled_back_on_recovery() {
+        let dir = TempDir::new().unwrap();
+        let log_dir = dir.path().join("transactions");
+        let content = dir.path().join("content.md");
+        fs::write(&content, "old").unwrap();
+
+        fs::create_dir_all(&log_dir).unwrap();
+        let log = TransactionLog {
+            committed: false,
+            entries: vec![TransactionEntry { temp: temp_path_for(&content), target: content.clone() }],
+        };
+        fs::write(&log.entries[0].temp, "half written").unwrap();
+        fs::write(log_dir.join("crash.json"), serde_json::to_vec(&log).unwrap()).unwrap();
+
+        recover(&log_dir).unwrap();
+        assert_eq!(fs::read_to_string(&content).unwrap(), "old");
+        assert!(!log.entries[0].temp.exists());
+    }
+
+    #[test]
+    fn transaction_writes_all_files() {
+        let dir = TempDir::new().unwrap();
+        let mut transaction = Transaction::new(&dir.path().join("transactions"));
+        transaction.write(&dir.path().join("a.json"), "a");
+        transaction.write(&dir.path().join("b.json"), "b");
+        transaction.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(dir.path().join("a.json")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.path().join("b.json")).unwrap(), "b");
+        assert_eq!(fs::read_dir(dir.path().join("transactions")).unwrap().count(), 0);
+    }
+}
@@ -1,20 +1,43 @@
-use crate::file_system::{FileSystemManager, ProjectConfig, ProjectData, BookConfig, BookData, DocumentConfig};
+use crate::file_system::{
+    FileSystemManager, ProjectConfig, ProjectData, BookConfig, BookData, DocumentConfig, CommitInfo,
+    BranchInfo, DocumentRefs, DocumentMergeOutcome, RecoverableDocument, ProjectMigrationReport,
+    DocumentType, StatusWorkflow, StatusTransition,
+};
+use crate::diff::{self, DiffGranularity, DiffResult};
+use crate::docx::ManuscriptOptions;
+use crate::import::ImportOptions;
+use crate::merge::{self, MergeResult};
+use crate::object_store::RepackStats;
+use crate::outline::DocumentNode;
+use crate::pdf::PdfOptions;
+use crate::replace::{ReplaceOptions, ReplaceResult, RevertResult};
+use crate::sandbox::{PathSandbox, SandboxError};
+use crate::search::{HistoryMatch, SearchOptions, SearchResults};
+use crate::site::SiteOptions;
+use crate::stats::{self, TextStats};
+use crate::trash::{TrashEntry, TrashSettings};
 use anyhow::Result;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use tauri::State;
+use tauri::{Manager, State};
 
 /// 应用状态
 pub struct AppState {
     pub file_manager: Mutex<FileSystemManager>,
+    pub sandbox: PathSandbox, // 通用文件命令允许访问的位置
 }
 
 impl AppState {
     pub fn new() -> Result<Self> {
+        let file_manager = FileSystemManager::new()?;
+        let sandbox = PathSandbox::new();
+        sandbox.allow(file_manager.data_dir())?;
+
         Ok(Self {
-            file_manager: Mutex::new(FileSystemManager::new()?),
+            file_manager: Mutex::new(file_manager),
+            sandbox,
         })
     }
 }
@@ -93,7 +116,7 @@ pub async fn export_project(
     export_path: String,
 ) -> Result<(), String> {
     let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
-    let path = PathBuf::from(export_path);
+    let path = state.sandbox.resolve(&export_path).map_err(|e| e.to_string())?;
     
     file_manager
         .export_project(&project_id, &path)
@@ -113,6 +136,44 @@ pub async fn get_project_stats(
         .map_err(|e| e.to_string())
 }
 
+/// 重建项目历史的增量链
+#[tauri::command]
+pub async fn repack_project(
+    state: State<'_, AppState>,
+    project_id: String,
+) -> Result<RepackStats, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .repack_project(&project_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 把旧项目迁移为书籍
+#[tauri::command]
+pub async fn migrate_project(
+    state: State<'_, AppState>,
+    project_id: String,
+) -> Result<ProjectMigrationReport, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .migrate_project(&project_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 迁移所有旧项目
+#[tauri::command]
+pub async fn migrate_all_projects(
+    state: State<'_, AppState>,
+) -> Result<Vec<ProjectMigrationReport>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .migrate_all_projects()
+        .map_err(|e| e.to_string())
+}
+
 /// 选择文件夹对话框
 #[tauri::command]
 pub async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
@@ -121,12 +182,15 @@ pub async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, Stri
 
     let result = Arc::new(Mutex::new(None));
     let result_clone = result.clone();
+    let app_handle = app.clone();
 
     app.dialog()
         .file()
         .pick_folder(move |path| {
+            let path = path.map(|p| p.to_string());
+            allow_picked_path(&app_handle, path.as_deref());
             let mut result = result_clone.lock().unwrap();
-            *result = path.map(|p| p.to_string());
+            *result = path;
         });
 
     // 等待一小段时间让对话框完成
@@ -153,9 +217,12 @@ pub async fn select_file(app: tauri::AppHandle, filters: Vec<(String, Vec<String
         file_dialog = file_dialog.add_filter(&name, &ext_refs);
     }
 
+    let app_handle = app.clone();
     file_dialog.pick_file(move |path| {
+        let path = path.map(|p| p.to_string());
+        allow_picked_path(&app_handle, path.as_deref());
         let mut result = result_clone.lock().unwrap();
-        *result = path.map(|p| p.to_string());
+        *result = path;
     });
 
     // 等待一小段时间让对话框完成
@@ -165,6 +232,51 @@ pub async fn select_file(app: tauri::AppHandle, filters: Vec<(String, Vec<String
     Ok(final_result)
 }
 
+/// 选择保存位置对话框
+#[tauri::command]
+pub async fn select_save_file(
+    app: tauri::AppHandle,
+    default_path: Option<String>,
+    filters: Vec<(String, Vec<String>)>,
+) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let mut file_dialog = app.dialog().file();
+    if let Some(default_path) = default_path {
+        let default_path = PathBuf::from(default_path);
+        if let Some(directory) = default_path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            file_dialog = file_dialog.set_directory(directory);
+        }
+        if let Some(file_name) = default_path.file_name() {
+            file_dialog = file_dialog.set_file_name(file_name.to_string_lossy());
+        }
+    }
+
+    for (name, extensions) in filters {
+        let ext_refs: Vec<&str> = extensions.iter().map(|s| s.as_str()).collect();
+        file_dialog = file_dialog.add_filter(&name, &ext_refs);
+    }
+
+    let path = file_dialog.blocking_save_file().map(|p| p.to_string());
+    allow_picked_path(&app, path.as_deref());
+    Ok(path)
+}
+
+/// 用户在对话框中选择的位置加入文件访问的允许范围
+///
+/// 选择的文件夹连同其下的内容都允许访问；选择的文件（包括保存对话框中还不存在的文件）
+/// 只允许它本身，不会因此放开所在的文件夹。
+fn allow_picked_path(app: &tauri::AppHandle, path: Option<&str>) {
+    let Some(path) = path else { return };
+    let path = Path::new(path);
+    let sandbox = &app.state::<AppState>().sandbox;
+    let allowed = if path.is_dir() { sandbox.allow(path) } else { sandbox.allow_file(path) };
+
+    if let Err(e) = allowed {
+        log::warn!("Failed to allow picked path {}: {}", path.display(), e);
+    }
+}
+
 /// 显示消息对话框
 #[tauri::command]
 pub async fn show_message(
@@ -193,40 +305,42 @@ pub async fn show_message(
 
 /// 检查文件是否存在
 #[tauri::command]
-pub async fn file_exists(path: String) -> Result<bool, String> {
-    use std::path::Path;
-    Ok(Path::new(&path).exists())
+pub async fn file_exists(state: State<'_, AppState>, path: String) -> Result<bool, SandboxError> {
+    let path = state.sandbox.resolve(&path)?;
+    Ok(path.exists())
 }
 
 /// 创建目录
 #[tauri::command]
-pub async fn create_directory(path: String) -> Result<(), String> {
+pub async fn create_directory(state: State<'_, AppState>, path: String) -> Result<(), SandboxError> {
     use std::fs;
-    fs::create_dir_all(&path).map_err(|e| e.to_string())
+    let path = state.sandbox.resolve(&path)?;
+    fs::create_dir_all(&path).map_err(|e| SandboxError::io(&path, e))
 }
 
 /// 读取文件内容
 #[tauri::command]
-pub async fn read_file(path: String) -> Result<String, String> {
+pub async fn read_file(state: State<'_, AppState>, path: String) -> Result<String, SandboxError> {
     use std::fs;
-    fs::read_to_string(&path).map_err(|e| e.to_string())
+    let path = state.sandbox.resolve(&path)?;
+    fs::read_to_string(&path).map_err(|e| SandboxError::io(&path, e))
 }
 
 /// 写入文件内容
 #[tauri::command]
-pub async fn write_file(path: String, content: String) -> Result<(), String> {
+pub async fn write_file(state: State<'_, AppState>, path: String, content: String) -> Result<(), SandboxError> {
     use std::fs;
-    fs::write(&path, content).map_err(|e| e.to_string())
+    let path = state.sandbox.resolve(&path)?;
+    fs::write(&path, content).map_err(|e| SandboxError::io(&path, e))
 }
 
 /// 获取文件信息
 #[tauri::command]
-pub async fn get_file_info(path: String) -> Result<HashMap<String, Value>, String> {
+pub async fn get_file_info(state: State<'_, AppState>, path: String) -> Result<HashMap<String, Value>, SandboxError> {
     use std::fs;
-    use std::path::Path;
     
-    let path = Path::new(&path);
-    let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&path)?;
+    let metadata = fs::metadata(&path).map_err(|e| SandboxError::io(&path, e))?;
     
     let mut info = HashMap::new();
     info.insert("exists".to_string(), Value::Bool(path.exists()));
@@ -245,19 +359,18 @@ pub async fn get_file_info(path: String) -> Result<HashMap<String, Value>, Strin
 
 /// 列出目录内容
 #[tauri::command]
-pub async fn list_directory(path: String) -> Result<Vec<HashMap<String, Value>>, String> {
+pub async fn list_directory(state: State<'_, AppState>, path: String) -> Result<Vec<HashMap<String, Value>>, SandboxError> {
     use std::fs;
-    use std::path::Path;
     
-    let path = Path::new(&path);
-    let entries = fs::read_dir(path).map_err(|e| e.to_string())?;
+    let directory = state.sandbox.resolve(&path)?;
+    let entries = fs::read_dir(&directory).map_err(|e| SandboxError::io(&directory, e))?;
     
     let mut items = Vec::new();
     
     for entry in entries {
-        let entry = entry.map_err(|e| e.to_string())?;
+        let entry = entry.map_err(|e| SandboxError::io(&directory, e))?;
         let path = entry.path();
-        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        let metadata = entry.metadata().map_err(|e| SandboxError::io(&path, e))?;
         
         let mut item = HashMap::new();
         item.insert("name".to_string(), Value::String(
@@ -334,6 +447,21 @@ pub async fn create_book(
         .map_err(|e| e.to_string())
 }
 
+/// 从稿件文件或文件夹导入新书籍
+#[tauri::command]
+pub async fn import_book(
+    state: State<'_, AppState>,
+    source_path: String,
+    options: Option<ImportOptions>,
+) -> Result<BookData, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&source_path).map_err(|e| e.to_string())?;
+
+    file_manager
+        .import_book(&path, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
 /// 列出所有书籍
 #[tauri::command]
 pub async fn list_books(
@@ -385,20 +513,239 @@ pub async fn delete_book(
         .map_err(|e| e.to_string())
 }
 
+// ===== 回收站命令 =====
+
+/// 列出回收站中的书籍和文档
+#[tauri::command]
+pub async fn list_trash(
+    state: State<'_, AppState>,
+) -> Result<Vec<TrashEntry>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .list_trash()
+        .map_err(|e| e.to_string())
+}
+
+/// 从回收站恢复书籍或文档
+#[tauri::command]
+pub async fn restore_from_trash(
+    state: State<'_, AppState>,
+    entry_id: String,
+) -> Result<TrashEntry, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .restore_from_trash(&entry_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 清空回收站
+#[tauri::command]
+pub async fn empty_trash(
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .empty_trash()
+        .map_err(|e| e.to_string())
+}
+
+/// 获取回收站设置
+#[tauri::command]
+pub async fn get_trash_settings(
+    state: State<'_, AppState>,
+) -> Result<TrashSettings, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .load_trash_settings()
+        .map_err(|e| e.to_string())
+}
+
+/// 保存回收站设置
+#[tauri::command]
+pub async fn save_trash_settings(
+    state: State<'_, AppState>,
+    settings: TrashSettings,
+) -> Result<TrashSettings, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .save_trash_settings(&settings)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 全文搜索命令 =====
+
+/// 在所有书籍中全文搜索
+#[tauri::command]
+pub async fn search(
+    state: State<'_, AppState>,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<SearchResults, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .search(&query, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// 在版本历史中查找内容出现和消失的提交
+#[tauri::command]
+pub async fn search_history(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: Option<String>,
+    query: String,
+) -> Result<Vec<HistoryMatch>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .search_history(&book_id, document_id.as_deref(), &query)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 查找替换命令 =====
+
+/// 在整本书中查找替换，dry_run 时只返回匹配
+#[tauri::command]
+pub async fn replace_in_book(
+    state: State<'_, AppState>,
+    book_id: String,
+    options: ReplaceOptions,
+) -> Result<ReplaceResult, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .replace_in_book(&book_id, &options)
+        .map_err(|e| e.to_string())
+}
+
+/// 撤销一组提交，例如一次查找替换
+#[tauri::command]
+pub async fn revert_commit_group(
+    state: State<'_, AppState>,
+    book_id: String,
+    group_id: String,
+) -> Result<RevertResult, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .revert_commit_group(&book_id, &group_id)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 书籍导出命令 =====
+
+/// 导出书籍为 EPUB
+#[tauri::command]
+pub async fn export_book(
+    state: State<'_, AppState>,
+    book_id: String,
+    export_path: String,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&export_path).map_err(|e| e.to_string())?;
+
+    file_manager
+        .export_book(&book_id, &path)
+        .map_err(|e| e.to_string())
+}
+
+/// 导出书籍为投稿格式的 DOCX
+#[tauri::command]
+pub async fn export_book_docx(
+    state: State<'_, AppState>,
+    book_id: String,
+    export_path: String,
+    options: Option<ManuscriptOptions>,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&export_path).map_err(|e| e.to_string())?;
+
+    file_manager
+        .export_book_docx(&book_id, &path, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// PDF 选项中的字体文件同样只能来自数据目录或用户选择过的位置
+fn resolve_pdf_options(sandbox: &PathSandbox, options: Option<PdfOptions>) -> Result<PdfOptions, SandboxError> {
+    let mut options = options.unwrap_or_default();
+    if let Some(font_path) = options.font_path.as_deref().filter(|path| !path.trim().is_empty()) {
+        options.font_path = Some(sandbox.resolve(font_path)?.to_string_lossy().into_owned());
+    }
+    Ok(options)
+}
+
+/// 导出书籍为 PDF
+#[tauri::command]
+pub async fn export_book_pdf(
+    state: State<'_, AppState>,
+    book_id: String,
+    export_path: String,
+    options: Option<PdfOptions>,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&export_path).map_err(|e| e.to_string())?;
+    let options = resolve_pdf_options(&state.sandbox, options).map_err(|e| e.to_string())?;
+
+    file_manager
+        .export_book_pdf(&book_id, &path, &options)
+        .map_err(|e| e.to_string())
+}
+
+/// 导出单个文档为 PDF
+#[tauri::command]
+pub async fn export_document_pdf(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    export_path: String,
+    options: Option<PdfOptions>,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&export_path).map_err(|e| e.to_string())?;
+    let options = resolve_pdf_options(&state.sandbox, options).map_err(|e| e.to_string())?;
+
+    file_manager
+        .export_document_pdf(&book_id, &document_id, &path, &options)
+        .map_err(|e| e.to_string())
+}
+
+/// 导出书籍为静态网站
+#[tauri::command]
+pub async fn export_book_site(
+    state: State<'_, AppState>,
+    book_id: String,
+    export_dir: String,
+    options: Option<SiteOptions>,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+    let path = state.sandbox.resolve(&export_dir).map_err(|e| e.to_string())?;
+
+    file_manager
+        .export_book_site(&book_id, &path, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
 // ===== 文档管理命令 =====
 
-/// 创建新文档
+/// 创建新文档，可指定上级文档
 #[tauri::command]
 pub async fn create_document(
     state: State<'_, AppState>,
     book_id: String,
     title: String,
-    doc_type: String,
+    doc_type: DocumentType,
+    parent_id: Option<String>,
 ) -> Result<DocumentConfig, String> {
     let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
 
     file_manager
-        .create_document(&book_id, &title, &doc_type)
+        .create_document(&book_id, &title, doc_type, parent_id.as_deref())
         .map_err(|e| e.to_string())
 }
 
@@ -457,3 +804,430 @@ pub async fn delete_document(
         .delete_document(&book_id, &document_id)
         .map_err(|e| e.to_string())
 }
+
+/// 按当前设置重新统计书籍中所有文档
+#[tauri::command]
+pub async fn refresh_book_stats(
+    state: State<'_, AppState>,
+    book_id: String,
+) -> Result<BookData, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .refresh_book_stats(&book_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 统计文本（供编辑器实时显示）
+#[tauri::command]
+pub async fn get_text_stats(content: String, count_markdown_syntax: bool) -> Result<TextStats, String> {
+    Ok(stats::compute(&content, count_markdown_syntax))
+}
+
+// ===== 状态流程命令 =====
+
+/// 获取书籍的状态流程
+#[tauri::command]
+pub async fn get_status_workflow(
+    state: State<'_, AppState>,
+    book_id: String,
+) -> Result<StatusWorkflow, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .load_status_workflow(&book_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 保存书籍的自定义状态流程
+#[tauri::command]
+pub async fn save_status_workflow(
+    state: State<'_, AppState>,
+    book_id: String,
+    workflow: StatusWorkflow,
+) -> Result<StatusWorkflow, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .save_status_workflow(&book_id, &workflow)
+        .map_err(|e| e.to_string())
+}
+
+/// 按状态流程改变文档状态
+#[tauri::command]
+pub async fn set_document_status(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    status: String,
+) -> Result<DocumentConfig, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .set_document_status(&book_id, &document_id, &status)
+        .map_err(|e| e.to_string())
+}
+
+/// 获取文档的状态变化历史
+#[tauri::command]
+pub async fn list_status_history(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+) -> Result<Vec<StatusTransition>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .list_status_history(&book_id, &document_id)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 目录结构命令 =====
+
+/// 获取书籍的目录树
+#[tauri::command]
+pub async fn get_document_tree(
+    state: State<'_, AppState>,
+    book_id: String,
+) -> Result<Vec<DocumentNode>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .get_document_tree(&book_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 把文档移到另一个上级文档之下
+#[tauri::command]
+pub async fn move_document(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    parent_id: Option<String>,
+    index: Option<usize>,
+) -> Result<Vec<DocumentNode>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .move_document(&book_id, &document_id, parent_id.as_deref(), index)
+        .map_err(|e| e.to_string())
+}
+
+/// 重排同一上级文档下的子文档
+#[tauri::command]
+pub async fn reorder_documents(
+    state: State<'_, AppState>,
+    book_id: String,
+    parent_id: Option<String>,
+    document_ids: Vec<String>,
+) -> Result<Vec<DocumentNode>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .reorder_documents(&book_id, parent_id.as_deref(), &document_ids)
+        .map_err(|e| e.to_string())
+}
+
+/// 重新连续编号所有文档
+#[tauri::command]
+pub async fn renumber_documents(
+    state: State<'_, AppState>,
+    book_id: String,
+) -> Result<Vec<DocumentNode>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .renumber_documents(&book_id)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 编辑日志命令 =====
+
+/// 把编辑器的最新内容写入编辑日志
+#[tauri::command]
+pub async fn append_document_journal(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    content: String,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .append_document_journal(&book_id, &document_id, &content)
+        .map_err(|e| e.to_string())
+}
+
+/// 列出异常退出后有未保存修改的文档
+#[tauri::command]
+pub async fn list_recoverable_documents(
+    state: State<'_, AppState>,
+) -> Result<Vec<RecoverableDocument>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .list_recoverable_documents()
+        .map_err(|e| e.to_string())
+}
+
+/// 恢复文档的未保存修改
+#[tauri::command]
+pub async fn recover_document_journal(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+) -> Result<String, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .recover_document_journal(&book_id, &document_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 放弃文档的未保存修改
+#[tauri::command]
+pub async fn discard_document_journal(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .discard_document_journal(&book_id, &document_id)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 文档版本管理命令 =====
+
+/// 为文档当前内容创建提交
+#[tauri::command]
+pub async fn create_document_commit(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    message: String,
+    is_auto_commit: bool,
+) -> Result<CommitInfo, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .create_document_commit(&book_id, &document_id, &message, is_auto_commit)
+        .map_err(|e| e.to_string())
+}
+
+/// 列出文档的所有提交
+#[tauri::command]
+pub async fn list_document_commits(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+) -> Result<Vec<CommitInfo>, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .list_document_commits(&book_id, &document_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 加载某次提交的文档内容
+#[tauri::command]
+pub async fn load_document_commit(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    commit_id: String,
+) -> Result<String, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .load_document_commit(&book_id, &document_id, &commit_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 删除文档的某次提交
+#[tauri::command]
+pub async fn delete_document_commit(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    commit_id: String,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .delete_document_commit(&book_id, &document_id, &commit_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 重建文档历史的增量链
+#[tauri::command]
+pub async fn repack_document(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+) -> Result<RepackStats, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .repack_document(&book_id, &document_id)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 文档分支管理命令 =====
+
+/// 列出文档的分支
+#[tauri::command]
+pub async fn list_document_branches(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+) -> Result<DocumentRefs, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .load_document_refs(&book_id, &document_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 创建文档分支
+#[tauri::command]
+pub async fn create_document_branch(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    name: String,
+    from_commit_id: Option<String>,
+) -> Result<BranchInfo, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .create_document_branch(&book_id, &document_id, &name, from_commit_id.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// 切换文档分支，返回该分支的最新内容
+#[tauri::command]
+pub async fn switch_document_branch(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    branch_id: String,
+) -> Result<String, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .switch_document_branch(&book_id, &document_id, &branch_id)
+        .map_err(|e| e.to_string())
+}
+
+/// 重命名文档分支
+#[tauri::command]
+pub async fn rename_document_branch(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    branch_id: String,
+    new_name: String,
+) -> Result<BranchInfo, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .rename_document_branch(&book_id, &document_id, &branch_id, &new_name)
+        .map_err(|e| e.to_string())
+}
+
+/// 删除文档分支
+#[tauri::command]
+pub async fn delete_document_branch(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    branch_id: String,
+) -> Result<(), String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .delete_document_branch(&book_id, &document_id, &branch_id)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 文档合并命令 =====
+
+/// 三方合并文本
+#[tauri::command]
+pub async fn merge_text(base: String, ours: String, theirs: String) -> Result<MergeResult, String> {
+    Ok(merge::merge_texts(&base, &ours, &theirs))
+}
+
+/// 把另一个分支合并到当前分支
+#[tauri::command]
+pub async fn merge_document_branch(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    branch_id: String,
+    message: String,
+) -> Result<DocumentMergeOutcome, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .merge_document_branch(&book_id, &document_id, &branch_id, &message)
+        .map_err(|e| e.to_string())
+}
+
+/// 用解决冲突后的内容完成合并
+#[tauri::command]
+pub async fn resolve_document_merge(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    theirs_commit_id: String,
+    content: String,
+    message: String,
+) -> Result<CommitInfo, String> {
+    let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+    file_manager
+        .resolve_document_merge(&book_id, &document_id, &theirs_commit_id, &content, &message)
+        .map_err(|e| e.to_string())
+}
+
+// ===== 差异比较命令 =====
+
+/// 比较两段文本
+#[tauri::command]
+pub async fn diff_text(
+    old_text: String,
+    new_text: String,
+    granularity: Option<DiffGranularity>,
+) -> Result<DiffResult, String> {
+    Ok(diff::diff_texts(&old_text, &new_text, granularity.unwrap_or_default()))
+}
+
+/// 比较文档的两个版本，不指定提交时使用工作副本
+#[tauri::command]
+pub async fn diff_versions(
+    state: State<'_, AppState>,
+    book_id: String,
+    document_id: String,
+    old_commit_id: Option<String>,
+    new_commit_id: Option<String>,
+    granularity: Option<DiffGranularity>,
+) -> Result<DiffResult, String> {
+    // 只在读取版本时持有锁，差异计算不阻塞其他命令
+    let (old_text, new_text) = {
+        let file_manager = state.file_manager.lock().map_err(|e| e.to_string())?;
+
+        let old_text = file_manager
+            .load_document_version(&book_id, &document_id, old_commit_id.as_deref())
+            .map_err(|e| e.to_string())?;
+        let new_text = file_manager
+            .load_document_version(&book_id, &document_id, new_commit_id.as_deref())
+            .map_err(|e| e.to_string())?;
+
+        (old_text, new_text)
+    };
+
+    Ok(diff::diff_texts(&old_text, &new_text, granularity.unwrap_or_default()))
+}
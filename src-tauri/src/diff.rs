@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+use similar::{Algorithm, DiffTag};
+use std::time::{Duration, Instant};
+
+use crate::text;
+
+/// 单次差异计算的最长耗时，超时后返回近似结果
+const DIFF_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 差异粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffGranularity {
+    #[default]
+    Line,
+    Word,
+    Char,
+}
+
+/// 变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeType {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+/// 单个变更，与前端 `DiffChange` 结构一致
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffChange {
+    #[serde(rename = "type")]
+    pub change_type: ChangeType,
+    pub value: String,
+    pub line_number: u32, // 新增的变更为新版本行号，其余为旧版本行号
+}
+
+/// 差异块，行号从 1 开始
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub changes: Vec<DiffChange>,
+}
+
+/// 增删统计（按所选粒度计数，空白不计入）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffStats {
+    pub additions: u32,
+    pub deletions: u32,
+    pub added_characters: u32,
+    pub removed_characters: u32,
+}
+
+/// 差异结果，与前端 `DiffResult` 结构兼容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffResult {
+    pub old_text: String,
+    pub new_text: String,
+    pub granularity: DiffGranularity,
+    pub changes: Vec<DiffChange>,
+    pub hunks: Vec<DiffHunk>,
+    pub stats: DiffStats,
+}
+
+/// 词元及其所在行号
+struct Token<'a> {
+    text: &'a str,
+    line: u32,
+}
+
+/// 计算两段文本的差异
+pub fn diff_texts(old_text: &str, new_text: &str, granularity: DiffGranularity) -> DiffResult {
+    let old_tokens = tokenize(old_text, granularity);
+    let new_tokens = tokenize(new_text, granularity);
+
+    let ops = diff_slices(
+        &old_tokens.iter().map(|token| token.text).collect::<Vec<_>>(),
+        &new_tokens.iter().map(|token| token.text).collect::<Vec<_>>(),
+    );
+
+    // 展开为逐词元的变更序列
+    let mut items = Vec::new();
+    let mut stats = DiffStats::default();
+    for (tag, old_range, new_range) in ops {
+        match tag {
+            DiffTag::Equal => {
+                items.extend(old_range.map(|i| (ChangeType::Unchanged, &old_tokens[i])));
+            }
+            DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => {
+                for token in &old_tokens[old_range] {
+                    count(&mut stats.deletions, &mut stats.removed_characters, token.text);
+                    items.push((ChangeType::Removed, token));
+                }
+                for token in &new_tokens[new_range] {
+                    count(&mut stats.additions, &mut stats.added_characters, token.text);
+                    items.push((ChangeType::Added, token));
+                }
+            }
+        }
+    }
+
+    let changes = build_changes(&items, granularity);
+    let hunks = build_hunks(&items, granularity);
+
+    DiffResult {
+        old_text: old_text.to_string(),
+        new_text: new_text.to_string(),
+        granularity,
+        changes,
+        hunks,
+        stats,
+    }
+}
+
+/// 计算两个序列的差异操作（Myers 算法）
+pub fn diff_slices<'a>(
+    old: &[&'a str],
+    new: &[&'a str],
+) -> Vec<(DiffTag, std::ops::Range<usize>, std::ops::Range<usize>)> {
+    similar::capture_diff_slices_deadline(
+        Algorithm::Myers,
+        old,
+        new,
+        Some(Instant::now() + DIFF_TIMEOUT),
+    )
+    .iter()
+    .map(|op| op.as_tag_tuple())
+    .collect()
+}
+
+/// 按粒度切分文本并记录行号
+fn tokenize(text: &str, granularity: DiffGranularity) -> Vec<Token<'_>> {
+    if granularity == DiffGranularity::Line {
+        return text
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| Token { text: line, line: i as u32 + 1 })
+            .collect();
+    }
+
+    let pieces = match granularity {
+        DiffGranularity::Word => text::split_words(text),
+        _ => text::split_chars(text),
+    };
+
+    let mut line = 1;
+    pieces
+        .into_iter()
+        .map(|piece| {
+            let token = Token { text: piece, line };
+            line += piece.matches('\n').count() as u32;
+            token
+        })
+        .collect()
+}
+
+/// 统计一个变更词元
+fn count(units: &mut u32, characters: &mut u32, token: &str) {
+    let visible = token.chars().filter(|c| !c.is_whitespace()).count() as u32;
+    if visible > 0 {
+        *units += 1;
+        *characters += visible;
+    }
+}
+
+/// 生成变更列表：按行比较时每行一项，按词或字比较时合并相邻的同类变更
+fn build_changes(items: &[(ChangeType, &Token)], granularity: DiffGranularity) -> Vec<DiffChange> {
+    let mut changes: Vec<DiffChange> = Vec::new();
+
+    for (change_type, token) in items {
+        if granularity != DiffGranularity::Line {
+            if let Some(last) = changes.last_mut() {
+                if last.change_type == *change_type {
+                    last.value.push_str(token.text);
+                    continue;
+                }
+            }
+        }
+
+        changes.push(DiffChange {
+            change_type: *change_type,
+            value: token.text.to_string(),
+            line_number: token.line,
+        });
+    }
+
+    changes
+}
+
+/// 按上下文把变更分组为差异块
+fn build_hunks(items: &[(ChangeType, &Token)], granularity: DiffGranularity) -> Vec<DiffHunk> {
+    let context = match granularity {
+        DiffGranularity::Line => 3,
+        DiffGranularity::Word => 8,
+        DiffGranularity::Char => 16,
+    };
+
+    // 每个位置之前的新旧版本行号
+    let mut positions = Vec::with_capacity(items.len() + 1);
+    let (mut old_line, mut new_line) = (1u32, 1u32);
+    for (change_type, token) in items {
+        positions.push((old_line, new_line));
+        let lines = if granularity == DiffGranularity::Line { 1 } else { token.text.matches('\n').count() as u32 };
+        if *change_type != ChangeType::Added {
+            old_line += lines;
+        }
+        if *change_type != ChangeType::Removed {
+            new_line += lines;
+        }
+    }
+    positions.push((old_line, new_line));
+
+    // 找出需要展示的区间并合并相近的区间
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, (change_type, _)) in items.iter().enumerate() {
+        if *change_type == ChangeType::Unchanged {
+            continue;
+        }
+        let start = i.saturating_sub(context);
+        let end = (i + 1 + context).min(items.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let (old_start, new_start) = positions[start];
+            let (old_end, new_end) = positions[end];
+            let span = |from: u32, to: u32| {
+                if granularity == DiffGranularity::Line { to - from } else { to - from + 1 }
+            };
+
+            DiffHunk {
+                old_start,
+                old_lines: span(old_start, old_end),
+                new_start,
+                new_lines: span(new_start, new_end),
+                changes: build_changes(&items[start..end], granularity),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_diff_matches_frontend_shape() {
+        let result = diff_texts("a\nb\nc", "a\nB\nc\nd", DiffGranularity::Line);
+
+        let summary: Vec<(ChangeType, &str, u32)> = result
+            .changes
+            .iter()
+            .map(|change| (change.change_type, change.value.as_str(), change.line_number))
+            .collect();
+        assert_eq!(summary, vec![
+            (ChangeType::Unchanged, "a", 1),
+            (ChangeType::Removed, "b", 2),
+            (ChangeType::Added, "B", 2),
+            (ChangeType::Unchanged, "c", 3),
+            (ChangeType::Added, "d", 4),
+        ]);
+        assert_eq!((result.stats.additions, result.stats.deletions), (2, 1));
+
+        let json = serde_json::to_value(&result.changes[1]).unwrap();
+        assert_eq!(json["type"], "removed");
+        assert_eq!(json["lineNumber"], 2);
+
+        assert_eq!(result.hunks.len(), 1);
+        let hunk = &result.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 3, 1, 4));
+    }
+
+    #[test]
+    fn word_diff_splits_cjk_characters() {
+        let result = diff_texts("她推开了门。\nHe didn't wait.", "她轻轻推开了窗。\nHe didn't wait long.", DiffGranularity::Word);
+
+        let added: Vec<&str> = result
+            .changes
+            .iter()
+            .filter(|change| change.change_type == ChangeType::Added)
+            .map(|change| change.value.as_str())
+            .collect();
+        assert_eq!(added, vec!["轻轻", "窗", " long"]);
+        assert_eq!(result.stats.additions, 4);
+        assert_eq!(result.stats.deletions, 1);
+
+        let long = result.changes.iter().find(|change| change.value == " long").unwrap();
+        assert_eq!(long.line_number, 2);
+    }
+
+    #[test]
+    fn char_diff_handles_multibyte_text() {
+        let result = diff_texts("春眠不觉晓", "春眠不知晓", DiffGranularity::Char);
+        let summary: Vec<(ChangeType, &str)> = result
+            .changes
+            .iter()
+            .map(|change| (change.change_type, change.value.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            (ChangeType::Unchanged, "春眠不"),
+            (ChangeType::Removed, "觉"),
+            (ChangeType::Added, "知"),
+            (ChangeType::Unchanged, "晓"),
+        ]);
+        assert_eq!(result.hunks[0].old_lines, 1);
+    }
+
+    #[test]
+    fn identical_texts_have_no_hunks() {
+        let result = diff_texts("same\ntext", "same\ntext", DiffGranularity::Line);
+        assert!(result.hunks.is_empty());
+        assert_eq!(result.stats.additions + result.stats.deletions, 0);
+    }
+}
@@ -0,0 +1,597 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::export::{self, Chapter};
+use crate::file_system::BookConfig;
+use crate::stats;
+use crate::text;
+
+/// 首行缩进（半英寸，单位为 twip）
+const FIRST_LINE_INDENT: u32 = 720;
+/// 正文宽度（Letter 纸宽减去两侧一英寸页边距）
+const TEXT_WIDTH: u32 = 9360;
+
+/// 投稿格式选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManuscriptOptions {
+    pub font_family: String,
+    pub font_size: u32, // 磅
+    pub double_spacing: bool,
+    pub page_number_header: bool, // 页眉显示“作者 / 书名 / 页码”
+    pub title_page: bool,
+    pub chapter_page_break: bool, // 每章另起一页
+}
+
+impl Default for ManuscriptOptions {
+    fn default() -> Self {
+        Self {
+            font_family: "Times New Roman".to_string(),
+            font_size: 12,
+            double_spacing: true,
+            page_number_header: true,
+            title_page: true,
+            chapter_page_break: true,
+        }
+    }
+}
+
+/// 生成投稿格式的 DOCX 文件内容
+pub fn build(config: &BookConfig, chapters: &[Chapter], options: &ManuscriptOptions) -> Result<Vec<u8>> {
+    let samples: Vec<&str> = std::iter::once(config.name.as_str())
+        .chain(chapters.iter().map(|chapter| chapter.content.as_str()))
+        .collect();
+    let language = export::detect_language(&samples);
+
+    let mut body = String::new();
+    if options.title_page {
+        let word_count: u32 = chapters
+            .iter()
+            .map(|chapter| stats::compute(&chapter.content, false).word_count)
+            .sum();
+        body.push_str(&title_page(config, word_count, language));
+    }
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        let page_break = if index == 0 { options.title_page } else { options.chapter_page_break };
+        let mut writer = BodyWriter::new(page_break);
+        if !chapter.starts_with_heading() {
+            writer.paragraph("Heading1", &[Run::plain(&chapter.title)]);
+        }
+        writer.markdown(&chapter.content);
+        body.push_str(&writer.finish());
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    add_file(&mut zip, "[Content_Types].xml", CONTENT_TYPES, deflated)?;
+    add_file(&mut zip, "_rels/.rels", PACKAGE_RELS, deflated)?;
+    add_file(&mut zip, "docProps/core.xml", &core_properties(config), deflated)?;
+    add_file(&mut zip, "word/_rels/document.xml.rels", DOCUMENT_RELS, deflated)?;
+    add_file(&mut zip, "word/styles.xml", &styles_xml(options, language), deflated)?;
+    add_file(&mut zip, "word/header1.xml", &running_header(config, options), deflated)?;
+    add_file(&mut zip, "word/header2.xml", &header_xml(""), deflated)?;
+    add_file(&mut zip, "word/document.xml", &document_xml(&body, options), deflated)?;
+
+    let cursor = zip.finish().context("Failed to finish DOCX archive")?;
+    Ok(cursor.into_inner())
+}
+
+/// 写入压缩包中的一个文件
+fn add_file(zip: &mut ZipWriter<Cursor<Vec<u8>>>, name: &str, content: &str, options: SimpleFileOptions) -> Result<()> {
+    zip.start_file(name, options)
+        .with_context(|| format!("Failed to add {} to DOCX", name))?;
+    zip.write_all(content.as_bytes())
+        .with_context(|| format!("Failed to write {} to DOCX", name))
+}
+
+/// 一段带格式的文字
+#[derive(Debug, Clone, Default)]
+struct Run {
+    text: String,
+    italic: bool,
+    bold: bool,
+    strike: bool,
+    code: bool,
+    line_break: bool, // 换行而不是文字
+}
+
+impl Run {
+    fn plain(text: &str) -> Self {
+        Self { text: text.to_string(), ..Self::default() }
+    }
+
+    fn to_xml(&self) -> String {
+        if self.line_break {
+            return "<w:r><w:br/></w:r>".to_string();
+        }
+
+        let mut properties = String::new();
+        if self.code {
+            properties.push_str("<w:rFonts w:ascii=\"Courier New\" w:hAnsi=\"Courier New\"/>");
+        }
+        if self.bold {
+            properties.push_str("<w:b/>");
+        }
+        if self.italic {
+            properties.push_str("<w:i/>");
+        }
+        if self.strike {
+            properties.push_str("<w:strike/>");
+        }
+
+        let properties = if properties.is_empty() { properties } else { format!("<w:rPr>{}</w:rPr>", properties) };
+
+        // 制表符需要单独的元素
+        let content: Vec<String> = self.text
+            .split('\t')
+            .map(|part| if part.is_empty() {
+                String::new()
+            } else {
+                format!("<w:t xml:space=\"preserve\">{}</w:t>", export::escape_xml(part))
+            })
+            .collect();
+        format!("<w:r>{}{}</w:r>", properties, content.join("<w:tab/>"))
+    }
+}
+
+/// 把 Markdown 转为 WordprocessingML 段落
+struct BodyWriter {
+    xml: String,
+    runs: Vec<Run>,
+    style: Option<&'static str>, // 当前打开的段落样式
+    page_break: bool,            // 下一个段落前分页
+    italic: usize,
+    bold: usize,
+    strike: usize,
+    quote_depth: usize,
+    lists: Vec<Option<u64>>, // 有序列表的下一个编号
+    in_code_block: bool,
+    soft_break: bool,        // 上一个事件是软换行，按两侧文字决定是否补空格
+}
+
+impl BodyWriter {
+    fn new(page_break: bool) -> Self {
+        Self {
+            xml: String::new(),
+            runs: Vec::new(),
+            style: None,
+            page_break,
+            italic: 0,
+            bold: 0,
+            strike: 0,
+            quote_depth: 0,
+            lists: Vec::new(),
+            in_code_block: false,
+            soft_break: false,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.close();
+        self.xml
+    }
+
+    /// 直接写入一个完整段落
+    fn paragraph(&mut self, style: &'static str, runs: &[Run]) {
+        self.close();
+        self.style = Some(style);
+        self.runs.extend_from_slice(runs);
+        self.close();
+    }
+
+    /// 当前块级结构对应的正文样式
+    fn block_style(&self) -> &'static str {
+        if self.in_code_block {
+            "Code"
+        } else if !self.lists.is_empty() {
+            "ListParagraph"
+        } else if self.quote_depth > 0 {
+            "Quote"
+        } else {
+            "BodyText"
+        }
+    }
+
+    fn open(&mut self, style: &'static str) {
+        if self.style.is_none() {
+            self.style = Some(style);
+        }
+    }
+
+    fn close(&mut self) {
+        self.soft_break = false;
+        let Some(style) = self.style.take() else {
+            return;
+        };
+
+        let page_break = if std::mem::take(&mut self.page_break) { "<w:pageBreakBefore/>" } else { "" };
+        self.xml.push_str(&format!("<w:p><w:pPr><w:pStyle w:val=\"{}\"/>{}</w:pPr>", style, page_break));
+        for run in self.runs.drain(..) {
+            self.xml.push_str(&run.to_xml());
+        }
+        self.xml.push_str("</w:p>");
+    }
+
+    fn text(&mut self, text: &str) {
+        // 中文按行折断的段落合并时不能在字之间插入空格，只有两侧都是西文时才补上
+        if std::mem::take(&mut self.soft_break) {
+            let previous = self.runs.last().and_then(|run| run.text.chars().last());
+            let next = text.chars().next();
+            if previous.is_some_and(|c| !is_cjk_text(c)) && next.is_some_and(|c| !is_cjk_text(c)) {
+                self.push_text(" ");
+            }
+        }
+        self.push_text(text);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let style = self.block_style();
+        self.open(style);
+        self.runs.push(Run {
+            text: text.to_string(),
+            italic: self.italic > 0,
+            bold: self.bold > 0,
+            strike: self.strike > 0,
+            code: self.in_code_block,
+            ..Run::default()
+        });
+    }
+
+    fn markdown(&mut self, markdown: &str) {
+        for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH) {
+            match event {
+                Event::Start(Tag::Heading { level, .. }) => {
+                    self.close();
+                    self.open(heading_style(level));
+                }
+                Event::End(TagEnd::Heading(_)) | Event::End(TagEnd::Paragraph) => self.close(),
+                Event::Start(Tag::Paragraph) => {
+                    let style = self.block_style();
+                    self.open(style);
+                }
+                Event::Start(Tag::BlockQuote(_)) => {
+                    self.close();
+                    self.quote_depth += 1;
+                }
+                Event::End(TagEnd::BlockQuote(_)) => {
+                    self.close();
+                    self.quote_depth = self.quote_depth.saturating_sub(1);
+                }
+                Event::Start(Tag::List(start)) => {
+                    self.close();
+                    self.lists.push(start);
+                }
+                Event::End(TagEnd::List(_)) => {
+                    self.close();
+                    self.lists.pop();
+                }
+                Event::Start(Tag::Item) => {
+                    self.close();
+                    let marker = match self.lists.last_mut() {
+                        Some(Some(number)) => {
+                            *number += 1;
+                            format!("{}.\t", *number - 1)
+                        }
+                        _ => "•\t".to_string(),
+                    };
+                    self.open("ListParagraph");
+                    self.runs.push(Run::plain(&marker));
+                }
+                Event::End(TagEnd::Item) => self.close(),
+                Event::Start(Tag::CodeBlock(_)) => {
+                    self.close();
+                    self.in_code_block = true;
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    // 代码块末尾的换行不需要保留
+                    if self.runs.last().is_some_and(|run| run.line_break) {
+                        self.runs.pop();
+                    }
+                    self.close();
+                    self.in_code_block = false;
+                }
+                Event::Start(Tag::Emphasis) => self.italic += 1,
+                Event::End(TagEnd::Emphasis) => self.italic = self.italic.saturating_sub(1),
+                Event::Start(Tag::Strong) => self.bold += 1,
+                Event::End(TagEnd::Strong) => self.bold = self.bold.saturating_sub(1),
+                Event::Start(Tag::Strikethrough) => self.strike += 1,
+                Event::End(TagEnd::Strikethrough) => self.strike = self.strike.saturating_sub(1),
+                Event::Text(text) if self.in_code_block => {
+                    for (i, line) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            self.runs.push(Run { line_break: true, ..Run::default() });
+                        }
+                        if !line.is_empty() {
+                            self.text(line);
+                        }
+                    }
+                }
+                Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => self.text(&text),
+                Event::Code(code) => {
+                    self.text(&code);
+                    if let Some(run) = self.runs.last_mut() {
+                        run.code = true;
+                    }
+                }
+                Event::SoftBreak => self.soft_break = true,
+                Event::HardBreak => {
+                    self.soft_break = false;
+                    let style = self.block_style();
+                    self.open(style);
+                    self.runs.push(Run { line_break: true, ..Run::default() });
+                }
+                // 分隔线作为场景分隔符
+                Event::Rule => self.paragraph("SceneBreak", &[Run::plain("#")]),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// 中日韩文字和全角标点，两侧的软换行不转为空格
+fn is_cjk_text(c: char) -> bool {
+    text::is_cjk(c) || matches!(c as u32, 0x3000..=0x303F | 0xFF00..=0xFFEF)
+}
+
+/// 标题级别对应的样式
+fn heading_style(level: HeadingLevel) -> &'static str {
+    match level {
+        HeadingLevel::H1 => "Heading1",
+        HeadingLevel::H2 => "Heading2",
+        _ => "Heading3",
+    }
+}
+
+/// 标题页：左上角作者，右上角约略字数，书名居中
+fn title_page(config: &BookConfig, word_count: u32, language: &str) -> String {
+    let rounded = if word_count >= 10_000 {
+        (word_count + 500) / 1000 * 1000
+    } else {
+        (word_count + 50) / 100 * 100
+    };
+    let count_label = if language == "en" {
+        format!("about {} words", rounded)
+    } else {
+        format!("约 {} 字", rounded)
+    };
+    let byline = if language == "en" { format!("by {}", config.author) } else { format!("{} 著", config.author) };
+
+    let mut writer = BodyWriter::new(false);
+    writer.paragraph("TitlePageInfo", &[Run::plain(&format!("{}\t{}", config.author, count_label))]);
+    writer.paragraph("Title", &[Run::plain(&config.name)]);
+    if !config.author.trim().is_empty() {
+        writer.paragraph("Byline", &[Run::plain(&byline)]);
+    }
+    writer.finish()
+}
+
+fn document_xml(body: &str, options: &ManuscriptOptions) -> String {
+    let headers = if options.page_number_header {
+        "<w:headerReference w:type=\"default\" r:id=\"rIdHeader1\"/><w:headerReference w:type=\"first\" r:id=\"rIdHeader2\"/>"
+    } else {
+        ""
+    };
+    let title_pg = if options.title_page { "<w:titlePg/>" } else { "" };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<w:body>
+{body}
+<w:sectPr>{headers}<w:pgSz w:w="12240" w:h="15840"/><w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="1440" w:header="720" w:footer="720" w:gutter="0"/>{title_pg}</w:sectPr>
+</w:body>
+</w:document>
+"#,
+        body = body,
+        headers = headers,
+        title_pg = title_pg,
+    )
+}
+
+fn header_xml(paragraphs: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:hdr xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+{}<w:p><w:pPr><w:pStyle w:val="Header"/></w:pPr></w:p>
+</w:hdr>
+"#,
+        paragraphs,
+    )
+}
+
+/// 页眉：作者 / 书名 / 页码，右对齐
+fn running_header(config: &BookConfig, options: &ManuscriptOptions) -> String {
+    if !options.page_number_header {
+        return header_xml("");
+    }
+
+    let label = if config.author.trim().is_empty() {
+        format!("{} / ", config.name)
+    } else {
+        format!("{} / {} / ", config.author, config.name)
+    };
+    header_xml(&format!(
+        "<w:p><w:pPr><w:pStyle w:val=\"Header\"/><w:jc w:val=\"right\"/></w:pPr>{}<w:fldSimple w:instr=\" PAGE \"><w:r><w:t>1</w:t></w:r></w:fldSimple></w:p>",
+        Run::plain(&label).to_xml(),
+    ))
+}
+
+fn styles_xml(options: &ManuscriptOptions, language: &str) -> String {
+    let font = export::escape_xml(&options.font_family);
+    let size = options.font_size * 2; // 半磅
+    let line = if options.double_spacing { 480 } else { 240 };
+    let east_asia = match language {
+        "zh" => "zh-CN",
+        "ja" => "ja-JP",
+        "ko" => "ko-KR",
+        _ => "en-US",
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:docDefaults>
+<w:rPrDefault><w:rPr><w:rFonts w:ascii="{font}" w:hAnsi="{font}" w:eastAsia="{font}" w:cs="{font}"/><w:sz w:val="{size}"/><w:szCs w:val="{size}"/><w:lang w:val="en-US" w:eastAsia="{east_asia}"/></w:rPr></w:rPrDefault>
+<w:pPrDefault><w:pPr><w:spacing w:before="0" w:after="0" w:line="{line}" w:lineRule="auto"/></w:pPr></w:pPrDefault>
+</w:docDefaults>
+<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>
+<w:style w:type="paragraph" w:styleId="BodyText"><w:name w:val="Body Text"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:ind w:firstLine="{indent}"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="BodyText"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="2880" w:after="480"/><w:jc w:val="center"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="BodyText"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="480" w:after="240"/><w:jc w:val="center"/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading3"><w:name w:val="heading 3"/><w:basedOn w:val="Normal"/><w:next w:val="BodyText"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240"/><w:outlineLvl w:val="2"/></w:pPr><w:rPr><w:b/><w:i/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Quote"><w:name w:val="Quote"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:ind w:left="{indent}" w:right="{indent}"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="SceneBreak"><w:name w:val="Scene Break"/><w:basedOn w:val="Normal"/><w:next w:val="BodyText"/><w:qFormat/><w:pPr><w:jc w:val="center"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:pPr><w:tabs><w:tab w:val="left" w:pos="{indent}"/></w:tabs><w:ind w:left="{indent}" w:hanging="360"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="Code"><w:name w:val="Code"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:line="240" w:lineRule="auto"/><w:ind w:left="{indent}"/></w:pPr><w:rPr><w:rFonts w:ascii="Courier New" w:hAnsi="Courier New"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:before="4320" w:after="240"/><w:jc w:val="center"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Byline"><w:name w:val="Byline"/><w:basedOn w:val="Normal"/><w:pPr><w:jc w:val="center"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="TitlePageInfo"><w:name w:val="Title Page Info"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:line="240" w:lineRule="auto"/><w:tabs><w:tab w:val="right" w:pos="{width}"/></w:tabs></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="Header"><w:name w:val="header"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:line="240" w:lineRule="auto"/></w:pPr></w:style>
+</w:styles>
+"#,
+        font = font,
+        size = size,
+        east_asia = east_asia,
+        line = line,
+        indent = FIRST_LINE_INDENT,
+        width = TEXT_WIDTH,
+    )
+}
+
+fn core_properties(config: &BookConfig) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+<dc:title>{title}</dc:title>
+<dc:creator>{author}</dc:creator>
+<dc:description>{description}</dc:description>
+<dcterms:created xsi:type="dcterms:W3CDTF">{created}</dcterms:created>
+<dcterms:modified xsi:type="dcterms:W3CDTF">{modified}</dcterms:modified>
+</cp:coreProperties>
+"#,
+        title = export::escape_xml(&config.name),
+        author = export::escape_xml(&config.author),
+        description = export::escape_xml(&config.description),
+        created = config.created_at.format("%Y-%m-%dT%H:%M:%SZ"),
+        modified = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+    )
+}
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+<Override PartName="/word/header1.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml"/>
+<Override PartName="/word/header2.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml"/>
+<Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>
+</Types>
+"#;
+
+const PACKAGE_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>
+</Relationships>
+"#;
+
+const DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rIdStyles" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+<Relationship Id="rIdHeader1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header1.xml"/>
+<Relationship Id="rIdHeader2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header2.xml"/>
+</Relationships>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn config() -> BookConfig {
+        BookConfig { genre: "Fiction".to_string(), ..BookConfig::sample("The Long Night", "Jane Roe") }
+    }
+
+    fn read(bytes: Vec<u8>, name: &str) -> String {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut content = String::new();
+        archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn maps_markdown_to_manuscript_styles() {
+        let chapters = vec![
+            Chapter {
+                title: "One".into(),
+                content: "It was *dark* & **cold**.\n\n> A quote.\n\n***\n\nLater.".into(),
+            },
+            Chapter { title: "Two".into(), content: "## Part\n\nText.".into() },
+        ];
+
+        let bytes = build(&config(), &chapters, &ManuscriptOptions::default()).unwrap();
+        let document = read(bytes.clone(), "word/document.xml");
+
+        assert!(document.contains("<w:pStyle w:val=\"Title\"/></w:pPr><w:r><w:t xml:space=\"preserve\">The Long Night</w:t>"));
+        assert!(document.contains("<w:pStyle w:val=\"Heading1\"/><w:pageBreakBefore/></w:pPr><w:r><w:t xml:space=\"preserve\">One</w:t>"));
+        assert!(document.contains("<w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\">dark</w:t></w:r>"));
+        assert!(document.contains("<w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">cold</w:t>"));
+        assert!(document.contains("&amp;"));
+        assert!(document.contains("<w:pStyle w:val=\"Quote\"/>"));
+        assert!(document.contains("<w:pStyle w:val=\"SceneBreak\"/></w:pPr><w:r><w:t xml:space=\"preserve\">#</w:t>"));
+        assert!(document.contains("<w:pStyle w:val=\"Heading2\"/><w:pageBreakBefore/>"));
+        assert!(document.contains("<w:titlePg/>"));
+
+        let header = read(bytes.clone(), "word/header1.xml");
+        assert!(header.contains("Jane Roe / The Long Night / "));
+        assert!(header.contains("w:instr=\" PAGE \""));
+
+        let styles = read(bytes, "word/styles.xml");
+        assert!(styles.contains("w:ascii=\"Times New Roman\""));
+        assert!(styles.contains("w:line=\"480\""));
+    }
+
+    #[test]
+    fn options_control_font_and_spacing() {
+        let options = ManuscriptOptions {
+            font_family: "Courier New".to_string(),
+            double_spacing: false,
+            title_page: false,
+            page_number_header: false,
+            ..ManuscriptOptions::default()
+        };
+        let chapters = vec![Chapter { title: "一".into(), content: "正文。".into() }];
+
+        let bytes = build(&config(), &chapters, &options).unwrap();
+        let styles = read(bytes.clone(), "word/styles.xml");
+        assert!(styles.contains("w:ascii=\"Courier New\""));
+        assert!(styles.contains("w:line=\"240\""));
+
+        let document = read(bytes, "word/document.xml");
+        assert!(!document.contains("headerReference"));
+        assert!(!document.contains("pageBreakBefore"));
+    }
+
+    #[test]
+    fn soft_breaks_between_cjk_characters_add_no_space() {
+        let options = ManuscriptOptions { title_page: false, ..ManuscriptOptions::default() };
+        let chapters = vec![Chapter {
+            title: "一".into(),
+            content: "雨停了，\n她推开门。\n\nThe rain\nstopped.\n\n他说\nOK。".into(),
+        }];
+
+        let document = read(build(&config(), &chapters, &options).unwrap(), "word/document.xml");
+        let space = "<w:r><w:t xml:space=\"preserve\"> </w:t></w:r>";
+        assert_eq!(document.matches(space).count(), 1);
+        assert!(document.contains(&format!("The rain</w:t></w:r>{}", space)));
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::export::{self, Chapter, Cover};
+use crate::file_system::BookConfig;
+
+/// 默认样式表
+const STYLESHEET: &str = r#"body { margin: 0 5%; line-height: 1.6; text-align: justify; }
+h1, h2, h3 { text-align: center; line-height: 1.3; page-break-after: avoid; }
+h1 { margin: 2em 0 1.5em; }
+p { margin: 0; text-indent: 2em; }
+blockquote { margin: 1em 2em; font-style: italic; }
+hr { border: none; text-align: center; margin: 1.5em 0; }
+hr::after { content: "* * *"; }
+img { max-width: 100%; }
+table { border-collapse: collapse; margin: 1em auto; }
+th, td { border: 1px solid #999; padding: 0.2em 0.5em; }
+.cover { margin: 0; padding: 0; text-align: center; }
+.cover img { height: 100%; max-height: 100vh; }
+"#;
+
+/// 生成 EPUB 3 文件内容
+pub fn build(config: &BookConfig, chapters: &[Chapter], cover: Option<&Cover>) -> Result<Vec<u8>> {
+    let samples: Vec<&str> = std::iter::once(config.name.as_str())
+        .chain(chapters.iter().map(|chapter| chapter.content.as_str()))
+        .collect();
+    let language = export::detect_language(&samples);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // mimetype 必须是第一个文件且不压缩
+    add_file(&mut zip, "mimetype", b"application/epub+zip", stored)?;
+    add_file(&mut zip, "META-INF/container.xml", container_xml().as_bytes(), deflated)?;
+    add_file(&mut zip, "OEBPS/styles/book.css", STYLESHEET.as_bytes(), deflated)?;
+
+    if let Some(cover) = cover {
+        let image_name = format!("OEBPS/images/cover.{}", cover.extension);
+        add_file(&mut zip, &image_name, &cover.data, stored)?;
+        add_file(&mut zip, "OEBPS/cover.xhtml", cover_xhtml(config, cover, language).as_bytes(), deflated)?;
+    }
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        let name = format!("OEBPS/{}", chapter_file(index));
+        add_file(&mut zip, &name, chapter_xhtml(chapter, language).as_bytes(), deflated)?;
+    }
+
+    add_file(&mut zip, "OEBPS/nav.xhtml", nav_xhtml(config, chapters, language).as_bytes(), deflated)?;
+    add_file(&mut zip, "OEBPS/toc.ncx", toc_ncx(config, chapters).as_bytes(), deflated)?;
+    add_file(&mut zip, "OEBPS/content.opf", package_opf(config, chapters, cover, language).as_bytes(), deflated)?;
+
+    let cursor = zip.finish().context("Failed to finish EPUB archive")?;
+    Ok(cursor.into_inner())
+}
+
+/// 写入压缩包中的一个文件
+fn add_file(zip: &mut ZipWriter<Cursor<Vec<u8>>>, name: &str, data: &[u8], options: SimpleFileOptions) -> Result<()> {
+    zip.start_file(name, options)
+        .with_context(|| format!("Failed to add {} to EPUB", name))?;
+    zip.write_all(data)
+        .with_context(|| format!("Failed to write {} to EPUB", name))
+}
+
+/// 章节文件名（相对 OEBPS 目录）
+fn chapter_file(index: usize) -> String {
+    format!("text/chapter-{:03}.xhtml", index + 1)
+}
+
+/// XHTML 文档外壳
+fn xhtml_page(title: &str, language: &str, stylesheet: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}" lang="{language}">
+<head>
+<meta charset="UTF-8"/>
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="{stylesheet}"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        language = language,
+        title = export::escape_xml(title),
+        stylesheet = stylesheet,
+        body = body,
+    )
+}
+
+fn container_xml() -> String {
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#
+    .to_string()
+}
+
+fn chapter_xhtml(chapter: &Chapter, language: &str) -> String {
+    let mut body = String::from("<section epub:type=\"chapter\">\n");
+    if !chapter.starts_with_heading() {
+        body.push_str(&format!("<h1>{}</h1>\n", export::escape_xml(&chapter.title)));
+    }
+    body.push_str(&export::markdown_to_xhtml(&chapter.content));
+    body.push_str("</section>");
+
+    xhtml_page(&chapter.title, language, "../styles/book.css", &body)
+}
+
+fn cover_xhtml(config: &BookConfig, cover: &Cover, language: &str) -> String {
+    let body = format!(
+        "<section class=\"cover\" epub:type=\"cover\">\n<img src=\"images/cover.{}\" alt=\"{}\"/>\n</section>",
+        cover.extension,
+        export::escape_xml(&config.name),
+    );
+    xhtml_page(&config.name, language, "styles/book.css", &body)
+}
+
+fn nav_xhtml(config: &BookConfig, chapters: &[Chapter], language: &str) -> String {
+    let items: String = chapters
+        .iter()
+        .enumerate()
+        .map(|(index, chapter)| {
+            format!(
+                "      <li><a href=\"{}\">{}</a></li>\n",
+                chapter_file(index),
+                export::escape_xml(&chapter.title),
+            )
+        })
+        .collect();
+
+    let body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n  <h1>{}</h1>\n  <ol>\n{}  </ol>\n</nav>",
+        if language == "en" { "Contents" } else { "目录" },
+        items,
+    );
+    xhtml_page(&config.name, language, "styles/book.css", &body)
+}
+
+/// EPUB 2 目录，供不支持导航文档的旧阅读器使用
+fn toc_ncx(config: &BookConfig, chapters: &[Chapter]) -> String {
+    let nav_points: String = chapters
+        .iter()
+        .enumerate()
+        .map(|(index, chapter)| {
+            format!(
+                "    <navPoint id=\"nav-{n}\" playOrder=\"{n}\">\n      <navLabel><text>{title}</text></navLabel>\n      <content src=\"{src}\"/>\n    </navPoint>\n",
+                n = index + 1,
+                title = export::escape_xml(&chapter.title),
+                src = chapter_file(index),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="urn:uuid:{id}"/>
+  </head>
+  <docTitle><text>{title}</text></docTitle>
+  <navMap>
+{nav_points}  </navMap>
+</ncx>
+"#,
+        id = export::escape_xml(&config.id),
+        title = export::escape_xml(&config.name),
+        nav_points = nav_points,
+    )
+}
+
+fn package_opf(config: &BookConfig, chapters: &[Chapter], cover: Option<&Cover>, language: &str) -> String {
+    let mut metadata = vec![
+        format!("<dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>", export::escape_xml(&config.id)),
+        format!("<dc:title>{}</dc:title>", export::escape_xml(&config.name)),
+        format!("<dc:language>{}</dc:language>", language),
+        format!("<meta property=\"dcterms:modified\">{}</meta>", Utc::now().format("%Y-%m-%dT%H:%M:%SZ")),
+    ];
+    if !config.author.trim().is_empty() {
+        metadata.push(format!("<dc:creator>{}</dc:creator>", export::escape_xml(&config.author)));
+    }
+    if !config.description.trim().is_empty() {
+        metadata.push(format!("<dc:description>{}</dc:description>", export::escape_xml(&config.description)));
+    }
+    if !config.genre.trim().is_empty() {
+        metadata.push(format!("<dc:subject>{}</dc:subject>", export::escape_xml(&config.genre)));
+    }
+    for tag in &config.tags {
+        metadata.push(format!("<dc:subject>{}</dc:subject>", export::escape_xml(tag)));
+    }
+
+    let mut manifest = vec![
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>".to_string(),
+        "<item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>".to_string(),
+        "<item id=\"css\" href=\"styles/book.css\" media-type=\"text/css\"/>".to_string(),
+    ];
+    let mut spine = Vec::new();
+
+    if let Some(cover) = cover {
+        // 旧版阅读器通过 meta 识别封面
+        metadata.push("<meta name=\"cover\" content=\"cover-image\"/>".to_string());
+        manifest.push(format!(
+            "<item id=\"cover-image\" href=\"images/cover.{}\" media-type=\"{}\" properties=\"cover-image\"/>",
+            cover.extension, cover.media_type,
+        ));
+        manifest.push("<item id=\"cover\" href=\"cover.xhtml\" media-type=\"application/xhtml+xml\"/>".to_string());
+        spine.push("<itemref idref=\"cover\"/>".to_string());
+    }
+
+    for index in 0..chapters.len() {
+        let id = format!("chapter-{:03}", index + 1);
+        manifest.push(format!(
+            "<item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+            id,
+            chapter_file(index),
+        ));
+        spine.push(format!("<itemref idref=\"{}\"/>", id));
+    }
+
+    let indent = |lines: Vec<String>| -> String {
+        lines.into_iter().map(|line| format!("    {}\n", line)).collect()
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{language}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{metadata}  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine toc="ncx">
+{spine}  </spine>
+</package>
+"#,
+        language = language,
+        metadata = indent(metadata),
+        manifest = indent(manifest),
+        spine = indent(spine),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn config() -> BookConfig {
+        BookConfig {
+            description: "一本<短篇>集".to_string(),
+            genre: "小说".to_string(),
+            ..BookConfig::sample("雨夜 & 其他", "作者")
+        }
+    }
+
+    fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut content = String::new();
+        archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn builds_epub_package() {
+        let chapters = vec![
+            Chapter { title: "第一章".into(), content: "雨停了。\n\n她推开门<br>。".into() },
+            Chapter { title: "第二章".into(), content: "# 第二章 夜\n\n**风**起了。\n\n![窗外](images/wind.png)".into() },
+        ];
+        let cover = Cover { data: vec![0x89, b'P', b'N', b'G'], media_type: "image/png", extension: "png" };
+
+        let bytes = build(&config(), &chapters, Some(&cover)).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        // mimetype 是第一个未压缩的文件
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        drop(mimetype);
+
+        let opf = read(&mut archive, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>雨夜 &amp; 其他</dc:title>"));
+        assert!(opf.contains("<dc:description>一本&lt;短篇&gt;集</dc:description>"));
+        assert!(opf.contains("<dc:language>zh</dc:language>"));
+        assert!(opf.contains("properties=\"cover-image\""));
+        let spine_order: Vec<usize> = ["idref=\"cover\"", "idref=\"chapter-001\"", "idref=\"chapter-002\""]
+            .iter()
+            .map(|item| opf.find(item).unwrap())
+            .collect();
+        assert!(spine_order.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains("<a href=\"text/chapter-002.xhtml\">第二章</a>"));
+
+        // 原始 HTML 被转义，已有标题的章节不再重复标题
+        let first = read(&mut archive, "OEBPS/text/chapter-001.xhtml");
+        assert!(first.contains("<h1>第一章</h1>"));
+        assert!(first.contains("&lt;br&gt;"));
+        let second = read(&mut archive, "OEBPS/text/chapter-002.xhtml");
+        assert!(!second.contains("<h1>第二章</h1>"));
+        assert!(second.contains("<strong>风</strong>"));
+
+        // 图片文件没有打包，只保留替代文字，避免阅读器里出现失效的引用
+        assert!(!second.contains("<img"));
+        assert!(second.contains("窗外"));
+    }
+}
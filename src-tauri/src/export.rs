@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::text;
+
+/// 导出用的章节
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub content: String, // Markdown 正文
+}
+
+impl Chapter {
+    /// 正文是否已经以标题开头（此时不再额外插入章节标题）
+    pub fn starts_with_heading(&self) -> bool {
+        self.content
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| line.trim_start().starts_with('#'))
+    }
+}
+
+/// 封面图片
+#[derive(Debug, Clone)]
+pub struct Cover {
+    pub data: Vec<u8>,
+    pub media_type: &'static str,
+    pub extension: &'static str,
+}
+
+/// 加载封面图片，支持 data URL 和书籍目录内的相对路径
+pub fn load_cover(cover_image: &str, book_dir: &Path) -> Result<Option<Cover>> {
+    let cover_image = cover_image.trim();
+    if cover_image.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(data_url) = cover_image.strip_prefix("data:") {
+        let (header, payload) = data_url
+            .split_once(',')
+            .ok_or_else(|| anyhow::anyhow!("Invalid cover image data URL"))?;
+        let mime = header.trim_end_matches(";base64");
+        let Some((media_type, extension)) = image_type_for_mime(mime) else {
+            return Err(anyhow::anyhow!("Unsupported cover image type: {}", mime));
+        };
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .context("Failed to decode cover image")?;
+        return Ok(Some(Cover { data, media_type, extension }));
+    }
+
+    if cover_image.starts_with("http://") || cover_image.starts_with("https://") {
+        log::warn!("Remote cover images are not embedded: {}", cover_image);
+        return Ok(None);
+    }
+
+    // 封面路径来自书籍配置，只能指向书籍目录内的文件
+    let relative = Path::new(cover_image);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(anyhow::anyhow!("Cover image must be inside the book folder: {}", cover_image));
+    }
+    let path = book_dir.join(relative);
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    let Some((media_type, extension)) = image_type_for_extension(&extension) else {
+        return Err(anyhow::anyhow!("Unsupported cover image type: {}", path.display()));
+    };
+    let canonical = fs::canonicalize(&path)
+        .with_context(|| format!("Failed to read cover image {}", path.display()))?;
+    let canonical_book_dir = fs::canonicalize(book_dir)
+        .with_context(|| format!("Failed to read book folder {}", book_dir.display()))?;
+    if !canonical.starts_with(&canonical_book_dir) {
+        return Err(anyhow::anyhow!("Cover image must be inside the book folder: {}", cover_image));
+    }
+    let data = fs::read(&canonical)
+        .with_context(|| format!("Failed to read cover image {}", path.display()))?;
+
+    Ok(Some(Cover { data, media_type, extension }))
+}
+
+/// 按 MIME 类型识别图片
+fn image_type_for_mime(mime: &str) -> Option<(&'static str, &'static str)> {
+    match mime {
+        "image/jpeg" | "image/jpg" => Some(("image/jpeg", "jpg")),
+        "image/png" => Some(("image/png", "png")),
+        "image/gif" => Some(("image/gif", "gif")),
+        "image/webp" => Some(("image/webp", "webp")),
+        "image/svg+xml" => Some(("image/svg+xml", "svg")),
+        _ => None,
+    }
+}
+
+/// 按扩展名识别图片
+fn image_type_for_extension(extension: &str) -> Option<(&'static str, &'static str)> {
+    match extension {
+        "jpg" | "jpeg" => image_type_for_mime("image/jpeg"),
+        "png" | "gif" | "webp" => image_type_for_mime(&format!("image/{}", extension)),
+        "svg" => image_type_for_mime("image/svg+xml"),
+        _ => None,
+    }
+}
+
+/// 把 Markdown 渲染为 XHTML 片段
+///
+/// 原样嵌入的 HTML 会被转义成文本，保证输出始终是格式良好的 XML；
+/// 图片引用的文件不会被打包，因此只保留替代文字。
+pub fn markdown_to_xhtml(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let events = Parser::new_ext(markdown, options).filter_map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Some(Event::Text(raw)),
+        Event::Start(Tag::Image { .. }) | Event::End(TagEnd::Image) => None,
+        event => Some(event),
+    });
+
+    let mut xhtml = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut xhtml, events);
+    xhtml
+}
+
+/// 转义 XML 文本和属性值
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 根据书名和正文推断语言代码
+///
+/// 中文里也会出现个别假名（如人名间的「・」、引用的「の」），因此按假名、谚文与汉字的数量判断，
+/// 而不是见到一个就认定为日文或韩文。
+pub fn detect_language(samples: &[&str]) -> &'static str {
+    let (mut kana, mut hangul, mut han) = (0usize, 0usize, 0usize);
+    for c in samples.iter().flat_map(|sample| sample.chars()) {
+        match c as u32 {
+            0x30FB => {} // 中点，中文译名也用
+            0x3040..=0x30FF | 0x31F0..=0x31FF => kana += 1,
+            0xAC00..=0xD7AF => hangul += 1,
+            _ if text::is_cjk(c) => han += 1,
+            _ => {}
+        }
+    }
+
+    // 日文正文里假名通常占汉字的一半以上，这里放宽到十分之一
+    if kana > 0 && kana >= hangul && kana * 10 >= han {
+        "ja"
+    } else if hangul > 0 && hangul * 10 >= han {
+        "ko"
+    } else if kana + hangul + han > 0 {
+        "zh"
+    } else {
+        "en"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn cover_paths_must_stay_inside_the_book_folder() {
+        let dir = TempDir::new().unwrap();
+        let book_dir = dir.path().join("book");
+        fs::create_dir_all(book_dir.join("images")).unwrap();
+        fs::write(book_dir.join("images").join("cover.png"), b"png").unwrap();
+        fs::write(dir.path().join("outside.png"), b"secret").unwrap();
+
+        let cover = load_cover("images/cover.png", &book_dir).unwrap().unwrap();
+        assert_eq!(cover.data, b"png");
+        assert_eq!(cover.media_type, "image/png");
+
+        let outside = dir.path().join("outside.png").to_string_lossy().to_string();
+        for cover_image in [outside.as_str(), "../outside.png", "images/../../outside.png"] {
+            assert!(load_cover(cover_image, &book_dir).is_err(), "{}", cover_image);
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("outside.png"), book_dir.join("link.png")).unwrap();
+            assert!(load_cover("link.png", &book_dir).is_err());
+        }
+    }
+
+    #[test]
+    fn detects_language_from_script_counts() {
+        assert_eq!(detect_language(&["韩立走进了七玄门。"]), "zh");
+        assert_eq!(detect_language(&["哈利・波特与魔法石"]), "zh");
+        assert_eq!(detect_language(&["他在书里写下了「の」这个字，然后合上了书本离开房间。"]), "zh");
+        assert_eq!(detect_language(&["吾輩は猫である。名前はまだ無い。"]), "ja");
+        assert_eq!(detect_language(&["나는 고양이로소이다"]), "ko");
+        assert_eq!(detect_language(&["The Hobbit"]), "en");
+    }
+}
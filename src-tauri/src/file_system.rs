@@ -1,11 +1,29 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 use uuid::Uuid;
 
+use crate::atomic::{self, Transaction};
+use crate::docx::{self, ManuscriptOptions};
+use crate::epub;
+use crate::export::{self, Chapter};
+use crate::import::{self, ImportOptions, ImportedDocument};
+use crate::journal::{self, JournalHead};
+use crate::merge::{self, MergeResult};
+use crate::object_store::{ObjectStore, RepackStats};
+use crate::outline::{self, DocumentNode};
+use crate::pdf::{self, PdfOptions};
+use crate::replace::{self, ReplaceOptions, ReplaceResult, Replacer, RevertResult};
+use crate::schema;
+use crate::search::{self, HistoryChange, HistoryMatch, LineMatch, SearchHit, SearchIndex, SearchOptions, SearchResults};
+use crate::site::{self, SiteChapter, SiteOptions};
+use crate::stats::{self, TextStats};
+use crate::trash::{Trash, TrashEntry, TrashKind, TrashSettings};
+
 /// 项目配置结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectConfig {
@@ -30,6 +48,8 @@ pub struct ProjectSettings {
     pub font_size: u32,
     pub line_height: u32,
     pub font_family: String,
+    #[serde(default)]
+    pub count_markdown_syntax: bool, // 统计字数时是否计入 Markdown 标记
 }
 
 impl Default for ProjectSettings {
@@ -43,6 +63,7 @@ impl Default for ProjectSettings {
             font_size: 14,
             line_height: 24,
             font_family: "'JetBrains Mono', 'Fira Code', 'Monaco', 'Consolas', monospace".to_string(),
+            count_markdown_syntax: false,
         }
     }
 }
@@ -70,6 +91,121 @@ pub struct CommitInfo {
     pub document_hash: String,
     pub word_count: u32,
     pub character_count: u32,
+    #[serde(default)]
+    pub parent_ids: Vec<String>, // 合并提交有两个父提交
+    #[serde(default)]
+    pub branch_id: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<String>, // 同一次批量操作产生的提交共用一个分组，可一起撤销
+}
+
+/// 新提交的附加信息
+#[derive(Debug, Clone, Copy, Default)]
+struct CommitOptions<'a> {
+    is_auto_commit: bool,
+    extra_parents: &'a [String], // 合并提交的其他父提交
+    group_id: Option<&'a str>,
+}
+
+/// 默认分支名称
+pub const DEFAULT_BRANCH_NAME: &str = "main";
+
+/// 文档分支信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchInfo {
+    pub id: String,
+    pub name: String,
+    pub head_commit_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// 文档分支引用（HEAD 指向当前分支 ID）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentRefs {
+    pub head: String,
+    pub branches: Vec<BranchInfo>,
+}
+
+impl DocumentRefs {
+    /// 获取当前分支
+    pub fn head_branch(&self) -> Result<&BranchInfo> {
+        self.branch(&self.head)
+    }
+
+    /// 按 ID 查找分支
+    pub fn branch(&self, branch_id: &str) -> Result<&BranchInfo> {
+        self.branches
+            .iter()
+            .find(|branch| branch.id == branch_id)
+            .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", branch_id))
+    }
+
+    /// 按 ID 查找分支（可变）
+    fn branch_mut(&mut self, branch_id: &str) -> Result<&mut BranchInfo> {
+        self.branches
+            .iter_mut()
+            .find(|branch| branch.id == branch_id)
+            .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", branch_id))
+    }
+}
+
+/// 编辑日志文件名
+const JOURNAL_FILE: &str = "journal.log";
+
+/// 书籍自定义状态流程的文件名，没有此文件时使用默认流程
+const WORKFLOW_FILE: &str = "workflow.json";
+
+/// 文档状态历史的文件名
+const STATUS_HISTORY_FILE: &str = "status_history.json";
+
+/// 校验调用方传入的 ID
+///
+/// 项目、书籍和文档的 ID 会拼进数据目录下的路径，只接受标准格式的 UUID，
+/// 这样 `..`、路径分隔符、盘符之类的输入无法指向数据目录之外。
+fn parse_id<'a>(kind: &str, id: &'a str) -> Result<&'a str> {
+    // 限定长度，排除花括号、urn: 前缀和不带连字符的写法
+    if id.len() == 36 && Uuid::parse_str(id).is_ok() {
+        Ok(id)
+    } else {
+        Err(anyhow::anyhow!("Invalid {} ID: {:?}", kind, id))
+    }
+}
+
+/// 校验项目提交的 ID
+///
+/// 项目提交由前端生成，不是 UUID，但同样会拼成 `<id>.md` 文件名，
+/// 因此只接受字母、数字、`-` 和 `_`。
+fn parse_commit_id(id: &str) -> Result<&str> {
+    let is_file_name = !id.is_empty()
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if is_file_name {
+        Ok(id)
+    } else {
+        Err(anyhow::anyhow!("Invalid commit ID: {:?}", id))
+    }
+}
+
+/// 异常退出后可以恢复的文档
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoverableDocument {
+    pub book_id: String,
+    pub book_name: String,
+    pub document_id: String,
+    pub document_title: String,
+    pub saved_at: Option<DateTime<Utc>>, // content.md 的最后写入时间
+    pub journaled_at: DateTime<Utc>,     // 日志中最后一次编辑的时间
+    pub content: String,                 // 重放日志得到的内容
+}
+
+/// 分支合并结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentMergeOutcome {
+    pub base_commit_id: Option<String>,
+    pub ours_commit_id: Option<String>,
+    pub theirs_commit_id: String,
+    pub result: MergeResult,
+    pub commit: Option<CommitInfo>, // 无冲突时自动生成的合并提交
 }
 
 /// 书籍配置结构
@@ -87,6 +223,25 @@ pub struct BookConfig {
     pub settings: BookSettings,
 }
 
+#[cfg(test)]
+impl BookConfig {
+    /// 测试用的书籍配置，其余字段为空或默认值
+    pub fn sample(name: &str, author: &str) -> Self {
+        Self {
+            id: "4f1c1f6e-2a7b-4bb0-9c55-7d6e3d0f0a11".to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            author: author.to_string(),
+            genre: String::new(),
+            created_at: Utc::now(),
+            last_modified: Utc::now(),
+            cover_image: None,
+            tags: vec![],
+            settings: BookSettings::default(),
+        }
+    }
+}
+
 /// 书籍设置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookSettings {
@@ -99,6 +254,8 @@ pub struct BookSettings {
     pub font_size: u32,
     pub line_height: u32,
     pub font_family: String,
+    #[serde(default)]
+    pub count_markdown_syntax: bool, // 统计字数时是否计入 Markdown 标记
 }
 
 impl Default for BookSettings {
@@ -113,6 +270,7 @@ impl Default for BookSettings {
             font_size: 14,
             line_height: 24,
             font_family: "'JetBrains Mono', 'Fira Code', 'Monaco', 'Consolas', monospace".to_string(),
+            count_markdown_syntax: false,
         }
     }
 }
@@ -122,14 +280,108 @@ impl Default for BookSettings {
 pub struct DocumentConfig {
     pub id: String,
     pub book_id: String,
+    #[serde(default)]
+    pub parent_id: Option<String>, // 上级文档（卷或章），顶层文档为空
     pub title: String,
-    pub order: u32,
-    pub doc_type: String, // 'chapter' | 'section' | 'note'
+    pub order: u32, // 同一上级文档下的顺序，从 1 开始
+    pub doc_type: DocumentType,
     pub created_at: DateTime<Utc>,
     pub last_modified: DateTime<Utc>,
     pub word_count: u32,
     pub character_count: u32,
-    pub status: String, // 'draft' | 'review' | 'final'
+    pub status: String, // 书籍状态流程中的状态
+}
+
+/// 文档类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    Part,
+    #[default]
+    Chapter,
+    Scene,
+    Section,
+    Note, // 不导出，也不计入合计字数
+}
+
+/// 书籍的状态流程
+///
+/// 新文档使用第一个状态，状态只能沿 `transitions` 中列出的方向改变。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusWorkflow {
+    pub statuses: Vec<String>,
+    pub transitions: Vec<StatusRule>,
+}
+
+/// 允许的一种状态变化
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusRule {
+    pub from: String,
+    pub to: String,
+}
+
+impl Default for StatusWorkflow {
+    fn default() -> Self {
+        let rule = |from: &str, to: &str| StatusRule { from: from.to_string(), to: to.to_string() };
+        Self {
+            statuses: vec!["draft".to_string(), "review".to_string(), "final".to_string()],
+            transitions: vec![
+                rule("draft", "review"),
+                rule("review", "draft"),
+                rule("review", "final"),
+                rule("final", "review"),
+            ],
+        }
+    }
+}
+
+impl StatusWorkflow {
+    /// 新文档的初始状态
+    pub fn initial_status(&self) -> &str {
+        self.statuses.first().map_or("", String::as_str)
+    }
+
+    pub fn allows(&self, from: &str, to: &str) -> bool {
+        self.transitions.iter().any(|rule| rule.from == from && rule.to == to)
+    }
+
+    /// 检查状态不为空、不重复，且状态变化只涉及已定义的状态
+    pub fn validate(&self) -> Result<()> {
+        if self.statuses.is_empty() {
+            return Err(anyhow::anyhow!("A workflow needs at least one status"));
+        }
+
+        let mut seen = HashSet::new();
+        for status in &self.statuses {
+            if status.trim().is_empty() {
+                return Err(anyhow::anyhow!("Status names cannot be empty"));
+            }
+            if !seen.insert(status.as_str()) {
+                return Err(anyhow::anyhow!("Duplicate status: {}", status));
+            }
+        }
+
+        for rule in &self.transitions {
+            for status in [&rule.from, &rule.to] {
+                if !seen.contains(status.as_str()) {
+                    return Err(anyhow::anyhow!("Transition refers to unknown status: {}", status));
+                }
+            }
+            if rule.from == rule.to {
+                return Err(anyhow::anyhow!("Transition from {} to itself is not a status change", rule.from));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 一次状态变化记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub from: String,
+    pub to: String,
+    pub timestamp: DateTime<Utc>,
 }
 
 /// 书籍数据结构
@@ -150,17 +402,52 @@ pub struct ProjectData {
     pub commit_data: HashMap<String, String>, // commit_id -> document_content
 }
 
+/// 项目迁移结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStatus {
+    Migrated,
+    AlreadyMigrated,
+    Failed,
+}
+
+/// 单个项目的迁移报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectMigrationReport {
+    pub project_id: String,
+    pub project_name: String,
+    pub status: MigrationStatus,
+    pub book_id: String, // 沿用项目 ID
+    pub document_id: Option<String>,
+    pub commits_migrated: usize,
+    pub missing_commits: Vec<String>, // 找不到内容、未能迁移的提交
+    pub dropped_settings: Vec<String>, // 书籍设置中没有对应项的项目设置
+    pub error: Option<String>,
+}
+
 /// 文件系统管理器
 pub struct FileSystemManager {
+    data_dir: PathBuf,
     projects_dir: PathBuf,
     books_dir: PathBuf,
+    transactions_dir: PathBuf,
+    backups_dir: PathBuf,
+    objects: ObjectStore,
+    search_index: Mutex<SearchIndex>,
+    journals: Mutex<HashMap<PathBuf, JournalHead>>, // 本次运行中各编辑日志最后写入的内容
+    trash: Trash,
 }
 
 impl FileSystemManager {
     /// 创建新的文件系统管理器
     pub fn new() -> Result<Self> {
-        let projects_dir = Self::get_projects_directory()?;
-        let books_dir = Self::get_books_directory()?;
+        Self::with_data_dir(&Self::get_data_directory()?)
+    }
+
+    /// 在指定的数据目录下创建文件系统管理器
+    pub fn with_data_dir(data_dir: &Path) -> Result<Self> {
+        let projects_dir = data_dir.join("projects");
+        let books_dir = data_dir.join("books");
 
         // 确保项目目录存在
         if !projects_dir.exists() {
@@ -174,23 +461,59 @@ impl FileSystemManager {
                 .context("Failed to create books directory")?;
         }
 
-        Ok(Self { projects_dir, books_dir })
-    }
+        // 完成或回滚上次崩溃时未完成的多文件写入
+        let transactions_dir = data_dir.join("transactions");
+        let recovered = atomic::recover(&transactions_dir)?;
+        if recovered > 0 {
+            log::warn!("Recovered {} interrupted write transaction(s)", recovered);
+        }
 
-    /// 获取项目存储目录
-    fn get_projects_directory() -> Result<PathBuf> {
-        let home_dir = dirs::home_dir()
-            .context("Failed to get home directory")?;
+        // 快照对象存储
+        let objects = ObjectStore::new(data_dir.join("objects"))?;
+
+        // 升级书籍格式前的备份
+        let backups_dir = data_dir.join("backups");
+
+        // 全文索引，首次搜索时加载
+        let search_index = Mutex::new(SearchIndex::new(data_dir.join("index")));
+
+        // 回收站，启动时清除超过保留天数的内容
+        let trash = Trash::new(data_dir.join("trash"));
+        match trash.purge_expired(Utc::now()) {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {} expired trash entries", purged),
+            Err(e) => log::warn!("Failed to purge expired trash entries: {}", e),
+        }
+
+        let manager = Self {
+            data_dir: data_dir.to_path_buf(),
+            projects_dir,
+            books_dir,
+            transactions_dir,
+            backups_dir,
+            objects,
+            search_index,
+            journals: Mutex::new(HashMap::new()),
+            trash,
+        };
 
-        Ok(home_dir.join(".branchwrite").join("projects"))
+        if let Err(e) = manager.upgrade_all_books() {
+            log::warn!("Failed to upgrade books: {}", e);
+        }
+        Ok(manager)
+    }
+
+    /// 应用数据目录
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
     }
 
-    /// 获取书籍存储目录
-    fn get_books_directory() -> Result<PathBuf> {
+    /// 获取应用数据目录
+    fn get_data_directory() -> Result<PathBuf> {
         let home_dir = dirs::home_dir()
             .context("Failed to get home directory")?;
 
-        Ok(home_dir.join(".branchwrite").join("books"))
+        Ok(home_dir.join(".branchwrite"))
     }
 
     /// 创建新项目
@@ -229,7 +552,7 @@ impl FileSystemManager {
         };
 
         // 创建项目目录
-        let project_dir = self.projects_dir.join(&project_id);
+        let project_dir = self.project_dir(&project_id)?;
         fs::create_dir_all(&project_dir)
             .context("Failed to create project directory")?;
 
@@ -266,7 +589,7 @@ impl FileSystemManager {
         };
 
         // 创建书籍目录
-        let book_dir = self.books_dir.join(&book_id);
+        let book_dir = self.book_dir(&book_id)?;
         fs::create_dir_all(&book_dir)
             .context("Failed to create book directory")?;
 
@@ -275,35 +598,143 @@ impl FileSystemManager {
         fs::create_dir_all(&documents_dir)
             .context("Failed to create documents directory")?;
 
-        // 保存书籍数据
-        self.save_book(&book_data)?;
+        // 保存书籍数据，新书直接使用当前格式版本
+        let mut transaction = self.transaction();
+        self.stage_book(&mut transaction, &book_data)?;
+        schema::stage_current_version(&mut transaction, &book_dir)?;
+        transaction.commit()
+            .context("Failed to write book data")?;
+
+        Ok(book_data)
+    }
+
+    /// 从 Markdown/纯文本文件或文件夹导入新书
+    pub fn import_book(&self, source: &Path, options: &ImportOptions) -> Result<BookData> {
+        // 先读取并拆分稿件，出错时不会留下空书
+        let imported = import::read_source(source, options)?;
+        let name = options
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&imported.title);
+        // 未填写的简介和作者取自 DOCX/EPUB 的元数据
+        fn or_imported<'a>(value: &'a str, imported: &'a Option<String>) -> &'a str {
+            if value.trim().is_empty() { imported.as_deref().unwrap_or(value) } else { value }
+        }
+        let description = or_imported(&options.description, &imported.description);
+        let author = or_imported(&options.author, &imported.author);
+
+        let mut book_data = self.create_book(name, description, author, &options.genre)?;
+        if let Err(e) = self.write_imported_documents(&mut book_data, &imported.documents) {
+            // 用户从未见过这本书，直接删除而不是移到回收站
+            if let Err(cleanup) = self.book_dir(&book_data.config.id).and_then(|book_dir| {
+                fs::remove_dir_all(book_dir).context("Failed to remove book directory")
+            }) {
+                log::warn!("Failed to remove partially imported book {}: {}", book_data.config.id, cleanup);
+            }
+            return Err(e);
+        }
 
         Ok(book_data)
     }
 
+    /// 在一个事务中写入导入的文档和更新后的书籍数据
+    fn write_imported_documents(&self, book_data: &mut BookData, documents: &[ImportedDocument]) -> Result<()> {
+        let book_id = book_data.config.id.clone();
+        let count_markdown_syntax = book_data.config.settings.count_markdown_syntax;
+        let now = Utc::now();
+        let mut transaction = self.transaction();
+
+        for (index, document) in documents.iter().enumerate() {
+            let text_stats = stats::compute(&document.content, count_markdown_syntax);
+            let document_config = DocumentConfig {
+                id: Uuid::new_v4().to_string(),
+                book_id: book_id.clone(),
+                parent_id: None,
+                title: document.title.clone(),
+                order: index as u32 + 1,
+                doc_type: document.doc_type,
+                created_at: now,
+                last_modified: now,
+                word_count: text_stats.word_count,
+                character_count: text_stats.character_count,
+                status: "draft".to_string(),
+            };
+
+            let doc_dir = self.document_dir(&book_id, &document_config.id)?;
+            fs::create_dir_all(doc_dir.join("commits"))
+                .context("Failed to create document directory")?;
+            transaction.write(&doc_dir.join("content.md"), document.content.as_str());
+            let metadata_json = serde_json::to_string_pretty(&document_config)
+                .context("Failed to serialize document metadata")?;
+            transaction.write(&doc_dir.join("metadata.json"), metadata_json);
+
+            book_data.documents.push(document_config);
+        }
+
+        book_data.current_document_id = book_data.documents.first().map(|document| document.id.clone());
+        self.stage_book(&mut transaction, book_data)?;
+        transaction.commit()
+            .context("Failed to write imported documents")
+    }
+
+    /// 开始一个多文件写入事务
+    fn transaction(&self) -> Transaction {
+        Transaction::new(&self.transactions_dir)
+    }
+
     /// 保存书籍数据
+    ///
+    /// 文档状态的变化同样要符合状态流程，并记入状态历史。
     pub fn save_book(&self, book_data: &BookData) -> Result<()> {
-        let book_dir = self.books_dir.join(&book_data.config.id);
+        let book_id = &book_data.config.id;
+        let workflow = self.load_status_workflow(book_id)?;
+        let previous: HashMap<String, String> = self
+            .load_book(book_id)?
+            .documents
+            .into_iter()
+            .map(|document| (document.id, document.status))
+            .collect();
+
+        let mut transaction = self.transaction();
+        for document in &book_data.documents {
+            // 文档列表中的 ID 之后会用来拼路径，不合法的不能写进去
+            parse_id("document", &document.id)?;
+            match previous.get(&document.id) {
+                Some(from) if from != &document.status => {
+                    self.stage_status_change(&mut transaction, &workflow, document, from)?;
+                    self.stage_document_metadata(&mut transaction, document)?;
+                }
+                Some(_) => {}
+                None if !workflow.statuses.contains(&document.status) => {
+                    return Err(anyhow::anyhow!("Unknown status: {}", document.status));
+                }
+                None => {}
+            }
+        }
+        self.stage_book(&mut transaction, book_data)?;
+        transaction.commit()
+            .context("Failed to write book data")
+    }
+
+    /// 把书籍数据加入写入事务
+    fn stage_book(&self, transaction: &mut Transaction, book_data: &BookData) -> Result<()> {
+        let book_dir = self.book_dir(&book_data.config.id)?;
 
         // 保存书籍配置
-        let config_path = book_dir.join("config.json");
         let config_json = serde_json::to_string_pretty(&book_data.config)
             .context("Failed to serialize book config")?;
-        fs::write(&config_path, config_json)
-            .context("Failed to write book config")?;
+        transaction.write(&book_dir.join("config.json"), config_json);
 
         // 保存文档列表
-        let documents_path = book_dir.join("documents.json");
         let documents_json = serde_json::to_string_pretty(&book_data.documents)
             .context("Failed to serialize documents list")?;
-        fs::write(&documents_path, documents_json)
-            .context("Failed to write documents list")?;
+        transaction.write(&book_dir.join("documents.json"), documents_json);
 
         // 保存当前文档ID
         if let Some(current_doc_id) = &book_data.current_document_id {
-            let current_doc_path = book_dir.join("current_document.txt");
-            fs::write(&current_doc_path, current_doc_id)
-                .context("Failed to write current document ID")?;
+            transaction.write(&book_dir.join("current_document.txt"), current_doc_id.as_str());
         }
 
         Ok(())
@@ -311,12 +742,15 @@ impl FileSystemManager {
 
     /// 加载书籍数据
     pub fn load_book(&self, book_id: &str) -> Result<BookData> {
-        let book_dir = self.books_dir.join(book_id);
+        let book_dir = self.book_dir(book_id)?;
 
         if !book_dir.exists() {
             return Err(anyhow::anyhow!("Book not found: {}", book_id));
         }
 
+        // 旧格式的书籍先升级
+        self.upgrade_book(&book_dir)?;
+
         // 加载书籍配置
         let config_path = book_dir.join("config.json");
         let config_json = fs::read_to_string(&config_path)
@@ -369,97 +803,149 @@ impl FileSystemManager {
             if path.is_dir() {
                 let config_path = path.join("config.json");
                 if config_path.exists() {
+                    // 启动时已升级过所有书籍，这里只检查版本；仍未升级的说明升级失败，不列出
+                    match schema::read_version(&path) {
+                        Ok(schema::CURRENT_VERSION) => {}
+                        Ok(version) => {
+                            log::warn!("Skipping book {} with format version {}", path.display(), version);
+                            continue;
+                        }
+                        Err(e) => {
+                            log::warn!("Skipping book {}: {}", path.display(), e);
+                            continue;
+                        }
+                    }
                     match fs::read_to_string(&config_path) {
                         Ok(config_json) => {
                             match serde_json::from_str::<BookConfig>(&config_json) {
                                 Ok(config) => books.push(config),
-                                Err(e) => eprintln!("Failed to parse book config: {}", e),
+                                Err(e) => log::warn!("Failed to parse book config: {}", e),
                             }
                         }
-                        Err(e) => eprintln!("Failed to read book config: {}", e),
+                        Err(e) => log::warn!("Failed to read book config: {}", e),
                     }
                 }
             }
         }
 
         // 按最后修改时间排序
-        books.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+        books.sort_by_key(|book| std::cmp::Reverse(book.last_modified));
 
         Ok(books)
     }
 
+    /// 把书籍目录升级到当前格式版本
+    fn upgrade_book(&self, book_dir: &Path) -> Result<()> {
+        schema::upgrade(book_dir, &self.backups_dir, &self.transactions_dir)?;
+        Ok(())
+    }
+
+    /// 启动时把所有旧格式的书籍升级一次，失败的书籍在打开时会再试
+    fn upgrade_all_books(&self) -> Result<()> {
+        let entries = fs::read_dir(&self.books_dir)
+            .context("Failed to read books directory")?;
+        for entry in entries {
+            let path = entry.context("Failed to read directory entry")?.path();
+            if !path.join("config.json").exists() {
+                continue;
+            }
+            if let Err(e) = self.upgrade_book(&path) {
+                log::warn!("Failed to upgrade book {}: {:#}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
     /// 删除书籍
+    ///
+    /// 书籍目录连同版本历史移进回收站，可以恢复。
     pub fn delete_book(&self, book_id: &str) -> Result<()> {
-        let book_dir = self.books_dir.join(book_id);
-
-        if book_dir.exists() {
-            fs::remove_dir_all(&book_dir)
-                .context("Failed to delete book directory")?;
+        let book_dir = self.book_dir(book_id)?;
+        if !book_dir.exists() {
+            return Ok(());
         }
 
-        Ok(())
+        let title = fs::read_to_string(book_dir.join("config.json"))
+            .ok()
+            .and_then(|config_json| serde_json::from_str::<BookConfig>(&config_json).ok())
+            .map_or_else(|| book_id.to_string(), |config| config.name);
+        let entry = TrashEntry {
+            id: Uuid::new_v4().to_string(),
+            kind: TrashKind::Book,
+            book_id: book_id.to_string(),
+            title,
+            deleted_at: Utc::now(),
+            document: None,
+            children: vec![],
+        };
+        self.trash.put(&book_dir, &entry)
+            .context("Failed to move book to trash")
     }
 
     // ===== 文档管理方法 =====
 
-    /// 创建新文档
-    pub fn create_document(&self, book_id: &str, title: &str, doc_type: &str) -> Result<DocumentConfig> {
+    /// 创建新文档，放在上级文档（不指定时为顶层）的末尾
+    pub fn create_document(&self, book_id: &str, title: &str, doc_type: DocumentType, parent_id: Option<&str>) -> Result<DocumentConfig> {
         let document_id = Uuid::new_v4().to_string();
         let now = Utc::now();
 
         // 加载书籍数据以获取下一个顺序号
         let mut book_data = self.load_book(book_id)?;
-        let next_order = book_data.documents.len() as u32 + 1;
+        if let Some(parent_id) = parent_id {
+            if !book_data.documents.iter().any(|document| document.id == parent_id) {
+                return Err(anyhow::anyhow!("Document not found: {}", parent_id));
+            }
+        }
+        let next_order = outline::next_order(&book_data.documents, parent_id);
+        let status = self.load_status_workflow(book_id)?.initial_status().to_string();
 
         let document_config = DocumentConfig {
             id: document_id.clone(),
             book_id: book_id.to_string(),
+            parent_id: parent_id.map(str::to_string),
             title: title.to_string(),
             order: next_order,
-            doc_type: doc_type.to_string(),
+            doc_type,
             created_at: now,
             last_modified: now,
             word_count: 0,
             character_count: 0,
-            status: "draft".to_string(),
+            status,
         };
 
         // 创建文档目录
-        let doc_dir = self.books_dir.join(book_id).join("documents").join(&document_id);
+        let doc_dir = self.document_dir(book_id, &document_id)?;
         fs::create_dir_all(&doc_dir)
             .context("Failed to create document directory")?;
 
+        // 创建提交目录
+        let commits_dir = doc_dir.join("commits");
+        fs::create_dir_all(&commits_dir)
+            .context("Failed to create commits directory")?;
+
+        let mut transaction = self.transaction();
+
         // 创建空的文档内容文件
-        let content_path = doc_dir.join("content.md");
-        fs::write(&content_path, "")
-            .context("Failed to create document content file")?;
+        transaction.write(&doc_dir.join("content.md"), "");
 
         // 保存文档元数据
-        let metadata_path = doc_dir.join("metadata.json");
         let metadata_json = serde_json::to_string_pretty(&document_config)
             .context("Failed to serialize document metadata")?;
-        fs::write(&metadata_path, metadata_json)
-            .context("Failed to write document metadata")?;
-
-        // 创建提交目录
-        let commits_dir = doc_dir.join("commits");
-        fs::create_dir_all(&commits_dir)
-            .context("Failed to create commits directory")?;
+        transaction.write(&doc_dir.join("metadata.json"), metadata_json);
 
         // 更新书籍的文档列表
         book_data.documents.push(document_config.clone());
-        self.save_book(&book_data)?;
+        self.stage_book(&mut transaction, &book_data)?;
+
+        transaction.commit()
+            .context("Failed to write new document")?;
 
         Ok(document_config)
     }
 
     /// 加载文档内容
     pub fn load_document(&self, book_id: &str, document_id: &str) -> Result<String> {
-        let content_path = self.books_dir
-            .join(book_id)
-            .join("documents")
-            .join(document_id)
-            .join("content.md");
+        let content_path = self.document_dir(book_id, document_id)?.join("content.md");
 
         if !content_path.exists() {
             return Ok(String::new());
@@ -471,14 +957,10 @@ impl FileSystemManager {
 
     /// 保存文档内容
     pub fn save_document(&self, book_id: &str, document_id: &str, content: &str) -> Result<()> {
-        let doc_dir = self.books_dir
-            .join(book_id)
-            .join("documents")
-            .join(document_id);
+        let doc_dir = self.document_dir(book_id, document_id)?;
 
-        let content_path = doc_dir.join("content.md");
-        fs::write(&content_path, content)
-            .context("Failed to write document content")?;
+        let mut transaction = self.transaction();
+        transaction.write(&doc_dir.join("content.md"), content);
 
         // 更新文档元数据
         let metadata_path = doc_dir.join("metadata.json");
@@ -489,132 +971,1565 @@ impl FileSystemManager {
                 .context("Failed to parse document metadata")?;
 
             // 更新统计信息
+            let text_stats = self.book_text_stats(book_id, content);
             document_config.last_modified = Utc::now();
-            document_config.character_count = content.len() as u32;
-            document_config.word_count = content
-                .split_whitespace()
-                .filter(|word| !word.is_empty())
-                .count() as u32;
+            document_config.character_count = text_stats.character_count;
+            document_config.word_count = text_stats.word_count;
 
             // 保存更新的元数据
             let updated_metadata_json = serde_json::to_string_pretty(&document_config)
                 .context("Failed to serialize updated document metadata")?;
-            fs::write(&metadata_path, updated_metadata_json)
-                .context("Failed to write updated document metadata")?;
+            transaction.write(&metadata_path, updated_metadata_json);
+
+            // 同步书籍文档列表中的统计信息
+            let mut book_data = self.load_book(book_id)?;
+            if let Some(entry) = book_data.documents.iter_mut().find(|doc| doc.id == document_id) {
+                entry.last_modified = document_config.last_modified;
+                entry.character_count = document_config.character_count;
+                entry.word_count = document_config.word_count;
+                self.stage_book(&mut transaction, &book_data)?;
+            }
         }
 
-        Ok(())
+        // 正文、元数据和文档列表一起落盘
+        transaction.commit()
+            .context("Failed to write document content")?;
+
+        // 索引失败不影响保存，下次搜索时会按文件指纹补上
+        if let Err(e) = self.index_document(book_id, document_id, content) {
+            log::warn!("Failed to update search index for {}: {}", document_id, e);
+        }
+
+        // 保存的内容包含了日志中最后的修改时，日志才不再需要；
+        // 日志晚于这次保存写入时里面还有更新的修改，要留到下一次保存
+        self.remove_journal_if_saved(&doc_dir.join(JOURNAL_FILE), content)
     }
 
-    /// 列出书籍的所有文档
-    pub fn list_documents(&self, book_id: &str) -> Result<Vec<DocumentConfig>> {
-        let book_data = self.load_book(book_id)?;
-        Ok(book_data.documents)
+    /// 更新单篇文档的全文索引
+    fn index_document(&self, book_id: &str, document_id: &str, content: &str) -> Result<()> {
+        let content_path = self.document_dir(book_id, document_id)?.join("content.md");
+        self.search_index
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Search index lock poisoned"))?
+            .update_document(&content_path, book_id, document_id, content)
     }
 
-    /// 删除文档
-    pub fn delete_document(&self, book_id: &str, document_id: &str) -> Result<()> {
-        // 删除文档目录
-        let doc_dir = self.books_dir
-            .join(book_id)
-            .join("documents")
-            .join(document_id);
+    /// 在所有书籍中全文搜索，结果按书籍列表和文档顺序排列
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<SearchResults> {
+        let clauses = search::parse_query(query);
+        if clauses.is_empty() {
+            return Ok(SearchResults::default());
+        }
 
-        if doc_dir.exists() {
-            fs::remove_dir_all(&doc_dir)
-                .context("Failed to delete document directory")?;
+        let candidates = {
+            let mut index = self.search_index
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Search index lock poisoned"))?;
+            index.refresh(&self.books_dir)?;
+            index.candidates(&clauses, &options.book_ids)
+        };
+        let mut documents_by_book: HashMap<String, HashSet<String>> = HashMap::new();
+        for (book_id, document_id) in candidates {
+            documents_by_book.entry(book_id).or_default().insert(document_id);
         }
 
-        // 从书籍的文档列表中移除
-        let mut book_data = self.load_book(book_id)?;
-        book_data.documents.retain(|doc| doc.id != document_id);
+        let mut results = SearchResults::default();
+        for book in self.list_books()? {
+            let Some(document_ids) = documents_by_book.get(&book.id) else { continue };
+            let book_data = self.load_book(&book.id)?;
+            let documents: Vec<&DocumentConfig> = outline::reading_order(&book_data.documents)
+                .into_iter()
+                .filter(|document| document_ids.contains(&document.id))
+                .collect();
+
+            for document in documents {
+                let content = self.load_document(&book.id, &document.id)?;
+                for line_match in search::find_matches(&content, &clauses) {
+                    results.total += 1;
+                    if results.hits.len() < options.limit() {
+                        results.hits.push(SearchHit {
+                            book_id: book.id.clone(),
+                            book_name: book.name.clone(),
+                            document_id: document.id.clone(),
+                            document_title: document.title.clone(),
+                            line: line_match.line,
+                            snippet: line_match.snippet,
+                            highlights: line_match.highlights,
+                        });
+                    }
+                }
+            }
+        }
 
-        // 如果删除的是当前文档，清除当前文档ID
-        if book_data.current_document_id.as_ref() == Some(&document_id.to_string()) {
-            book_data.current_document_id = None;
+        Ok(results)
+    }
+
+    /// 在版本历史中查找内容出现和消失的提交，类似 `git log -S`
+    ///
+    /// 不指定文档时搜索书中所有文档。只有与所有父版本都不同的提交才会列出，
+    /// 因此合并提交不会重复报告已在分支上出现过的变化。结果按时间排列。
+    pub fn search_history(&self, book_id: &str, document_id: Option<&str>, query: &str) -> Result<Vec<HistoryMatch>> {
+        let clauses = search::parse_query(query);
+        if clauses.is_empty() {
+            return Ok(vec![]);
         }
 
-        self.save_book(&book_data)?;
+        let book_data = self.load_book(book_id)?;
+        let documents: Vec<&DocumentConfig> = book_data
+            .documents
+            .iter()
+            .filter(|document| document_id.is_none() || document_id == Some(document.id.as_str()))
+            .collect();
+        if let (Some(id), true) = (document_id, documents.is_empty()) {
+            return Err(anyhow::anyhow!("Document not found: {}", id));
+        }
 
-        Ok(())
+        let mut matches = Vec::new();
+        for document in documents {
+            // 提交历史按从新到旧保存，父提交总在子提交之后
+            let mut commits = self.list_document_commits(book_id, &document.id)?;
+            commits.reverse();
+            let hashes: HashMap<String, String> = commits
+                .iter()
+                .map(|commit| (commit.id.clone(), commit.document_hash.clone()))
+                .collect();
+
+            // 相同内容的版本只读取一次
+            let mut first_matches: HashMap<String, Option<LineMatch>> = HashMap::new();
+            let mut first_match = |hash: &str| -> Result<Option<LineMatch>> {
+                if let Some(found) = first_matches.get(hash) {
+                    return Ok(found.clone());
+                }
+                let content = self.objects.get(hash)?;
+                let found = search::find_matches(&content, &clauses).into_iter().next();
+                first_matches.insert(hash.to_string(), found.clone());
+                Ok(found)
+            };
+
+            for commit in commits {
+                let current = first_match(&commit.document_hash)?;
+                let mut parents = Vec::new();
+                for parent_id in &commit.parent_ids {
+                    if let Some(hash) = hashes.get(parent_id) {
+                        parents.push(first_match(hash)?);
+                    }
+                }
+
+                let change = match current {
+                    Some(found) if parents.iter().all(Option::is_none) => Some((HistoryChange::Appeared, found)),
+                    None if parents.iter().all(Option::is_some) => {
+                        parents.into_iter().flatten().next().map(|found| (HistoryChange::Disappeared, found))
+                    }
+                    _ => None,
+                };
+                let Some((change, line_match)) = change else { continue };
+                matches.push(HistoryMatch {
+                    book_id: book_id.to_string(),
+                    document_id: document.id.clone(),
+                    document_title: document.title.clone(),
+                    change,
+                    commit,
+                    line: line_match.line,
+                    snippet: line_match.snippet,
+                    highlights: line_match.highlights,
+                });
+            }
+        }
+
+        matches.sort_by_key(|found| found.commit.timestamp);
+        Ok(matches)
     }
 
-    /// 保存项目数据
-    pub fn save_project(&self, project_data: &ProjectData) -> Result<()> {
-        let project_dir = self.projects_dir.join(&project_data.config.id);
-        
-        // 保存项目配置
-        let config_path = project_dir.join("config.json");
-        let config_json = serde_json::to_string_pretty(&project_data.config)
-            .context("Failed to serialize project config")?;
-        fs::write(&config_path, config_json)
-            .context("Failed to write project config")?;
+    /// 在整本书中查找替换
+    ///
+    /// 预览时只返回匹配。正式替换时每篇受影响的文档各记录一次提交，
+    /// 这些提交共用一个分组 ID，可以用 `revert_commit_group` 一起撤销。
+    pub fn replace_in_book(&self, book_id: &str, options: &ReplaceOptions) -> Result<ReplaceResult> {
+        let replacer = Replacer::new(options)?;
 
-        // 保存文档内容
-        let content_path = project_dir.join("document.md");
-        fs::write(&content_path, &project_data.document_content)
-            .context("Failed to write document content")?;
+        let book_data = self.load_book(book_id)?;
+        let documents: Vec<&DocumentConfig> = outline::reading_order(&book_data.documents)
+            .into_iter()
+            .filter(|document| {
+                (options.document_ids.is_empty() || options.document_ids.contains(&document.id))
+                    && (options.statuses.is_empty() || options.statuses.contains(&document.status))
+            })
+            .collect();
+
+        let group_id = Uuid::new_v4().to_string();
+        let message = format!("替换“{}”为“{}”", options.find, options.replace);
+        let mut result = ReplaceResult::default();
+
+        // 某篇文档失败时继续处理其余文档，已替换的文档仍属于同一分组，可以一起撤销
+        for document in documents {
+            let content = match self.load_document(book_id, &document.id) {
+                Ok(content) => content,
+                Err(e) => {
+                    log::warn!("Failed to load {} for replacement: {}", document.title, e);
+                    result.failed.push(document.id.clone());
+                    continue;
+                }
+            };
+            let (replaced, replacements) = replacer.replace(&content);
+            if replacements.is_empty() {
+                continue;
+            }
 
-        // 保存文档元数据
-        let metadata_path = project_dir.join("metadata.json");
-        let metadata_json = serde_json::to_string_pretty(&project_data.document_metadata)
-            .context("Failed to serialize document metadata")?;
-        fs::write(&metadata_path, metadata_json)
-            .context("Failed to write document metadata")?;
+            if !options.dry_run {
+                if let Err(e) = self.replace_document(book_id, &document.id, &content, &replaced, &message, &group_id) {
+                    log::warn!("Failed to replace in {}: {}", document.title, e);
+                    result.failed.push(document.id.clone());
+                    continue;
+                }
+            }
 
-        // 保存提交历史
-        let commits_path = project_dir.join("commits.json");
-        let commits_json = serde_json::to_string_pretty(&project_data.commits)
-            .context("Failed to serialize commits")?;
-        fs::write(&commits_path, commits_json)
-            .context("Failed to write commits")?;
+            result.matches.extend(replace::preview(&document.id, &document.title, &content, &replacements));
+            result.replacement_count += replacements.len();
+            result.documents.push(document.id.clone());
+        }
 
-        // 保存提交数据
-        let commits_dir = project_dir.join("commit_data");
-        if !commits_dir.exists() {
-            fs::create_dir_all(&commits_dir)
-                .context("Failed to create commit data directory")?;
+        if !options.dry_run && !result.documents.is_empty() {
+            result.group_id = Some(group_id);
         }
+        Ok(result)
+    }
 
-        for (commit_id, content) in &project_data.commit_data {
-            let commit_file = commits_dir.join(format!("{}.md", commit_id));
-            fs::write(&commit_file, content)
-                .context("Failed to write commit data")?;
+    /// 把一篇文档的替换结果记为分组提交并写入工作副本
+    ///
+    /// 先提交再写入：写入失败时历史中虽有这次替换，工作副本仍是原文，撤销分组时三方合并会保留原文。
+    fn replace_document(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        content: &str,
+        replaced: &str,
+        message: &str,
+        group_id: &str,
+    ) -> Result<()> {
+        // 未提交的修改先保存为一个版本，撤销替换时才能回到替换前的内容
+        let refs = self.load_document_refs(book_id, document_id)?;
+        if content != self.branch_head_content(book_id, document_id, &refs)? {
+            let options = CommitOptions { is_auto_commit: true, ..Default::default() };
+            self.record_document_commit(book_id, document_id, content, "替换前自动保存", options)?;
         }
 
-        Ok(())
+        let options = CommitOptions { group_id: Some(group_id), ..Default::default() };
+        self.record_document_commit(book_id, document_id, replaced, message, options)?;
+        self.save_document(book_id, document_id, replaced)
     }
 
-    /// 加载项目数据
-    pub fn load_project(&self, project_id: &str) -> Result<ProjectData> {
-        let project_dir = self.projects_dir.join(project_id);
-        
-        if !project_dir.exists() {
-            return Err(anyhow::anyhow!("Project not found: {}", project_id));
+    /// 撤销一组提交，例如一次查找替换
+    ///
+    /// 每篇文档把分组提交带来的改动反向合并到工作副本，之后的编辑得以保留；
+    /// 与之后的编辑冲突的文档保持不变，列在结果的 `conflicts` 中。
+    /// 撤销本身也记录为一组提交，可以再次撤销。
+    pub fn revert_commit_group(&self, book_id: &str, group_id: &str) -> Result<RevertResult> {
+        let book_data = self.load_book(book_id)?;
+        let documents = outline::reading_order(&book_data.documents);
+
+        let revert_group_id = Uuid::new_v4().to_string();
+        let mut result = RevertResult::default();
+        let mut found = false;
+
+        for document in documents {
+            let commits = self.list_document_commits(book_id, &document.id)?;
+            let Some(commit) = commits.iter().find(|commit| commit.group_id.as_deref() == Some(group_id)) else { continue };
+            found = true;
+
+            // 分组提交不在当前分支上时无法安全地反向合并
+            let refs = self.load_document_refs(book_id, &document.id)?;
+            if commit.branch_id.as_deref() != Some(refs.head.as_str()) {
+                result.conflicts.push(document.id.clone());
+                continue;
+            }
+
+            let grouped = self.objects.get(&commit.document_hash)?;
+            let previous = match commit.parent_ids.first() {
+                Some(parent_id) => self.load_document_commit(book_id, &document.id, parent_id)?,
+                None => String::new(),
+            };
+            let current = self.load_document(book_id, &document.id)?;
+
+            let reverted = if current == grouped {
+                previous
+            } else {
+                match merge::merge_texts(&grouped, &current, &previous).content {
+                    Some(content) => content,
+                    None => {
+                        result.conflicts.push(document.id.clone());
+                        continue;
+                    }
+                }
+            };
+            if reverted == current {
+                continue;
+            }
+
+            self.save_document(book_id, &document.id, &reverted)?;
+            let message = format!("撤销：{}", commit.message);
+            let options = CommitOptions { group_id: Some(&revert_group_id), ..Default::default() };
+            self.record_document_commit(book_id, &document.id, &reverted, &message, options)?;
+            result.documents.push(document.id.clone());
         }
 
-        // 加载项目配置
-        let config_path = project_dir.join("config.json");
-        let config_json = fs::read_to_string(&config_path)
-            .context("Failed to read project config")?;
-        let config: ProjectConfig = serde_json::from_str(&config_json)
-            .context("Failed to parse project config")?;
+        if !found {
+            return Err(anyhow::anyhow!("Commit group not found: {}", group_id));
+        }
+        if !result.documents.is_empty() {
+            result.group_id = Some(revert_group_id);
+        }
+        Ok(result)
+    }
 
-        // 加载文档内容
-        let content_path = project_dir.join("document.md");
-        let document_content = fs::read_to_string(&content_path)
+    /// 按书籍设置统计文本
+    fn book_text_stats(&self, book_id: &str, content: &str) -> TextStats {
+        let count_markdown_syntax = self.book_dir(book_id)
+            .ok()
+            .and_then(|book_dir| fs::read_to_string(book_dir.join("config.json")).ok())
+            .and_then(|config_json| serde_json::from_str::<BookConfig>(&config_json).ok())
+            .map(|config| config.settings.count_markdown_syntax)
             .unwrap_or_default();
 
-        // 加载文档元数据
-        let metadata_path = project_dir.join("metadata.json");
-        let metadata_json = fs::read_to_string(&metadata_path)
-            .context("Failed to read document metadata")?;
-        let document_metadata: DocumentMetadata = serde_json::from_str(&metadata_json)
+        stats::compute(content, count_markdown_syntax)
+    }
+
+    /// 按当前设置重新统计书籍中所有文档
+    pub fn refresh_book_stats(&self, book_id: &str) -> Result<BookData> {
+        let mut book_data = self.load_book(book_id)?;
+        let count_markdown_syntax = book_data.config.settings.count_markdown_syntax;
+        let mut transaction = self.transaction();
+
+        for document in &mut book_data.documents {
+            let content = self.load_document(book_id, &document.id)?;
+            let text_stats = stats::compute(&content, count_markdown_syntax);
+            document.word_count = text_stats.word_count;
+            document.character_count = text_stats.character_count;
+
+            self.stage_document_metadata(&mut transaction, document)?;
+        }
+
+        self.stage_book(&mut transaction, &book_data)?;
+        transaction.commit()
+            .context("Failed to write refreshed statistics")?;
+        Ok(book_data)
+    }
+
+    /// 列出书籍的所有文档
+    pub fn list_documents(&self, book_id: &str) -> Result<Vec<DocumentConfig>> {
+        let book_data = self.load_book(book_id)?;
+        Ok(book_data.documents)
+    }
+
+    /// 把文档元数据加入写入事务，文档目录不存在时跳过
+    fn stage_document_metadata(&self, transaction: &mut Transaction, document: &DocumentConfig) -> Result<()> {
+        let metadata_path = self.document_dir(&document.book_id, &document.id)?.join("metadata.json");
+        if metadata_path.exists() {
+            let metadata_json = serde_json::to_string_pretty(document)
+                .context("Failed to serialize document metadata")?;
+            transaction.write(&metadata_path, metadata_json);
+        }
+        Ok(())
+    }
+
+    // ===== 状态流程方法 =====
+
+    /// 读取书籍的状态流程，没有自定义时使用默认流程
+    pub fn load_status_workflow(&self, book_id: &str) -> Result<StatusWorkflow> {
+        let workflow_path = self.book_dir(book_id)?.join(WORKFLOW_FILE);
+        if !workflow_path.exists() {
+            return Ok(StatusWorkflow::default());
+        }
+
+        let workflow_json = fs::read_to_string(&workflow_path)
+            .context("Failed to read status workflow")?;
+        serde_json::from_str(&workflow_json)
+            .context("Failed to parse status workflow")
+    }
+
+    /// 保存书籍的状态流程，仍有文档处于的状态不能删除
+    pub fn save_status_workflow(&self, book_id: &str, workflow: &StatusWorkflow) -> Result<StatusWorkflow> {
+        workflow.validate()?;
+
+        let book_data = self.load_book(book_id)?;
+        if let Some(document) = book_data
+            .documents
+            .iter()
+            .find(|document| !workflow.statuses.contains(&document.status))
+        {
+            return Err(anyhow::anyhow!(
+                "Status {} is still used by document {}",
+                document.status,
+                document.title
+            ));
+        }
+
+        let workflow_json = serde_json::to_string_pretty(workflow)
+            .context("Failed to serialize status workflow")?;
+        atomic::write_file(&self.book_dir(book_id)?.join(WORKFLOW_FILE), workflow_json)
+            .context("Failed to write status workflow")?;
+        Ok(workflow.clone())
+    }
+
+    /// 按状态流程改变文档状态，并记入状态历史
+    pub fn set_document_status(&self, book_id: &str, document_id: &str, status: &str) -> Result<DocumentConfig> {
+        let workflow = self.load_status_workflow(book_id)?;
+        let mut book_data = self.load_book(book_id)?;
+        let document = book_data
+            .documents
+            .iter_mut()
+            .find(|document| document.id == document_id)
+            .ok_or_else(|| anyhow::anyhow!("Document not found: {}", document_id))?;
+
+        let from = std::mem::replace(&mut document.status, status.to_string());
+        let document = document.clone();
+
+        let mut transaction = self.transaction();
+        self.stage_status_change(&mut transaction, &workflow, &document, &from)?;
+        self.stage_document_metadata(&mut transaction, &document)?;
+        self.stage_book(&mut transaction, &book_data)?;
+        transaction.commit()
+            .context("Failed to write document status")?;
+
+        Ok(document)
+    }
+
+    /// 文档的状态变化历史，按时间从早到晚排列
+    pub fn list_status_history(&self, book_id: &str, document_id: &str) -> Result<Vec<StatusTransition>> {
+        let doc_dir = self.document_dir(book_id, document_id)?;
+        if !doc_dir.exists() {
+            return Err(anyhow::anyhow!("Document not found: {}", document_id));
+        }
+
+        let history_path = doc_dir.join(STATUS_HISTORY_FILE);
+        if !history_path.exists() {
+            return Ok(vec![]);
+        }
+
+        let history_json = fs::read_to_string(&history_path)
+            .context("Failed to read status history")?;
+        serde_json::from_str(&history_json)
+            .context("Failed to parse status history")
+    }
+
+    /// 检查状态变化是否被流程允许，并把变化追加到状态历史
+    fn stage_status_change(
+        &self,
+        transaction: &mut Transaction,
+        workflow: &StatusWorkflow,
+        document: &DocumentConfig,
+        from: &str,
+    ) -> Result<()> {
+        if !workflow.statuses.contains(&document.status) {
+            return Err(anyhow::anyhow!("Unknown status: {}", document.status));
+        }
+        if !workflow.allows(from, &document.status) {
+            return Err(anyhow::anyhow!(
+                "Cannot change status of {} from {} to {}",
+                document.title,
+                from,
+                document.status
+            ));
+        }
+
+        let mut history = self.list_status_history(&document.book_id, &document.id)?;
+        history.push(StatusTransition {
+            from: from.to_string(),
+            to: document.status.clone(),
+            timestamp: Utc::now(),
+        });
+        let history_json = serde_json::to_string_pretty(&history)
+            .context("Failed to serialize status history")?;
+        transaction.write(&self.document_dir(&document.book_id, &document.id)?.join(STATUS_HISTORY_FILE), history_json);
+        Ok(())
+    }
+
+    // ===== 目录结构方法 =====
+
+    /// 书籍的目录树，每个节点带有下级文档的合计字数
+    pub fn get_document_tree(&self, book_id: &str) -> Result<Vec<DocumentNode>> {
+        let book_data = self.load_book(book_id)?;
+        Ok(outline::tree(&book_data.documents))
+    }
+
+    /// 把文档连同下级文档移到另一个上级文档之下，`index` 为在新的同级文档中的位置
+    pub fn move_document(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        parent_id: Option<&str>,
+        index: Option<usize>,
+    ) -> Result<Vec<DocumentNode>> {
+        let mut book_data = self.load_book(book_id)?;
+        outline::move_document(&mut book_data.documents, document_id, parent_id, index)?;
+        self.write_document_structure(book_id, book_data)
+    }
+
+    /// 按给出的顺序重排同一上级文档下的子文档
+    pub fn reorder_documents(&self, book_id: &str, parent_id: Option<&str>, document_ids: &[String]) -> Result<Vec<DocumentNode>> {
+        let mut book_data = self.load_book(book_id)?;
+        outline::reorder(&mut book_data.documents, parent_id, document_ids)?;
+        self.write_document_structure(book_id, book_data)
+    }
+
+    /// 重新连续编号所有文档，并修复指向不存在文档的上级引用
+    pub fn renumber_documents(&self, book_id: &str) -> Result<Vec<DocumentNode>> {
+        let mut book_data = self.load_book(book_id)?;
+        outline::renumber(&mut book_data.documents);
+        self.write_document_structure(book_id, book_data)
+    }
+
+    /// 按阅读顺序写回 documents.json，上级或顺序有变化的文档同步更新元数据
+    fn write_document_structure(&self, book_id: &str, mut book_data: BookData) -> Result<Vec<DocumentNode>> {
+        let previous: HashMap<String, (Option<String>, u32)> = self
+            .load_book(book_id)?
+            .documents
+            .into_iter()
+            .map(|document| (document.id, (document.parent_id, document.order)))
+            .collect();
+
+        book_data.documents = outline::reading_order(&book_data.documents)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut transaction = self.transaction();
+        for document in &book_data.documents {
+            let position = (document.parent_id.clone(), document.order);
+            if previous.get(&document.id) != Some(&position) {
+                self.stage_document_metadata(&mut transaction, document)?;
+            }
+        }
+        self.stage_book(&mut transaction, &book_data)?;
+        transaction.commit()
+            .context("Failed to write document structure")?;
+
+        Ok(outline::tree(&book_data.documents))
+    }
+
+    /// 删除文档
+    ///
+    /// 文档目录移进回收站，记下它在目录中的位置，恢复时放回原处。
+    pub fn delete_document(&self, book_id: &str, document_id: &str) -> Result<()> {
+        let mut book_data = self.load_book(book_id)?;
+        let document = book_data
+            .documents
+            .iter()
+            .find(|document| document.id == document_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Document not found: {}", document_id))?;
+
+        // 从书籍的文档列表中移除，子文档上移一级
+        let children: Vec<String> = outline::reading_order(&book_data.documents)
+            .into_iter()
+            .filter(|child| child.parent_id.as_deref() == Some(document_id))
+            .map(|child| child.id.clone())
+            .collect();
+        outline::remove_document(&mut book_data.documents, document_id);
+
+        // 如果删除的是当前文档，清除当前文档ID
+        if book_data.current_document_id.as_ref() == Some(&document_id.to_string()) {
+            book_data.current_document_id = None;
+        }
+
+        let doc_dir = self.document_dir(book_id, document_id)?;
+        let entry = TrashEntry {
+            id: Uuid::new_v4().to_string(),
+            kind: TrashKind::Document,
+            book_id: book_id.to_string(),
+            title: document.title.clone(),
+            deleted_at: Utc::now(),
+            document: Some(document),
+            children,
+        };
+        let moved = doc_dir.exists();
+        if moved {
+            self.trash.put(&doc_dir, &entry)
+                .context("Failed to move document to trash")?;
+        }
+
+        // 文档列表写入失败时把文档移回原处
+        if let Err(e) = self.write_document_structure(book_id, book_data) {
+            if moved {
+                if let Err(restore_error) = self.trash.take(&entry.id, &doc_dir) {
+                    log::error!("Document {} left in trash entry {}: {}", document_id, entry.id, restore_error);
+                }
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    // ===== 回收站方法 =====
+
+    /// 列出回收站中的书籍和文档，最近删除的在前
+    pub fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        self.trash.list()
+    }
+
+    /// 从回收站恢复书籍或文档
+    ///
+    /// 文档放回原来的上级文档和位置，删除时上移的子文档若仍在原处，重新归到它下面。
+    /// 文档所属的书籍也在回收站中时，需要先恢复书籍。
+    pub fn restore_from_trash(&self, entry_id: &str) -> Result<TrashEntry> {
+        let entry = self.trash.get(parse_id("trash entry", entry_id)?)?;
+        match entry.kind {
+            TrashKind::Book => {
+                self.trash.take(entry_id, &self.book_dir(&entry.book_id)?)?;
+            }
+            TrashKind::Document => {
+                let document = entry.document.clone().context("Trash entry has no document")?;
+                let mut book_data = self.load_book(&entry.book_id)?;
+                if book_data.documents.iter().any(|existing| existing.id == document.id) {
+                    return Err(anyhow::anyhow!("Document already exists: {}", document.title));
+                }
+                let parent_id = document.parent_id.clone().filter(|parent_id| {
+                    book_data.documents.iter().any(|existing| &existing.id == parent_id)
+                });
+                let index = document.order.saturating_sub(1) as usize;
+                book_data.documents.push(document.clone());
+                outline::move_document(&mut book_data.documents, &document.id, parent_id.as_deref(), Some(index))?;
+
+                for child_id in &entry.children {
+                    let still_promoted = book_data
+                        .documents
+                        .iter()
+                        .any(|child| &child.id == child_id && child.parent_id == parent_id);
+                    if still_promoted {
+                        outline::move_document(&mut book_data.documents, child_id, Some(&document.id), None)?;
+                    }
+                }
+
+                let doc_dir = self.document_dir(&entry.book_id, &document.id)?;
+                self.trash.take(entry_id, &doc_dir)?;
+
+                // 文档列表写入失败时把文档放回回收站
+                if let Err(e) = self.write_document_structure(&entry.book_id, book_data) {
+                    if let Err(trash_error) = self.trash.put(&doc_dir, &entry) {
+                        log::error!("Restored document {} left outside the trash: {}", document.id, trash_error);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(entry)
+    }
+
+    /// 永久删除回收站中的所有内容，返回删除的数量
+    pub fn empty_trash(&self) -> Result<usize> {
+        self.trash.empty()
+    }
+
+    /// 读取回收站设置
+    pub fn load_trash_settings(&self) -> Result<TrashSettings> {
+        self.trash.load_settings()
+    }
+
+    /// 保存回收站设置，并立即按新的保留天数清理
+    pub fn save_trash_settings(&self, settings: &TrashSettings) -> Result<TrashSettings> {
+        self.trash.save_settings(settings)?;
+        self.trash.purge_expired(Utc::now())?;
+        Ok(settings.clone())
+    }
+
+    // ===== 编辑日志方法 =====
+
+    /// 把编辑器的最新内容写入文档的编辑日志
+    pub fn append_document_journal(&self, book_id: &str, document_id: &str, content: &str) -> Result<()> {
+        let doc_dir = self.document_dir(book_id, document_id)?;
+        if !doc_dir.exists() {
+            return Err(anyhow::anyhow!("Document not found: {}", document_id));
+        }
+
+        let journal_path = doc_dir.join(JOURNAL_FILE);
+        let mut journals = self.lock_journals()?;
+        // 写入失败时日志末尾可能不完整，丢掉内存中的状态，下次从完整快照开始
+        let head = journals.remove(&journal_path);
+        let head = journal::append(&journal_path, head, content)?;
+        journals.insert(journal_path, head);
+        Ok(())
+    }
+
+    fn lock_journals(&self) -> Result<MutexGuard<'_, HashMap<PathBuf, JournalHead>>> {
+        self.journals
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Journal lock poisoned"))
+    }
+
+    /// 已保存的内容与日志最后写入的内容相同（或本次运行没有写过日志）时删除日志
+    fn remove_journal_if_saved(&self, journal_path: &Path, saved_content: &str) -> Result<()> {
+        let mut journals = self.lock_journals()?;
+        if journals.get(journal_path).is_some_and(|head| head.content() != saved_content) {
+            return Ok(());
+        }
+        journals.remove(journal_path);
+        journal::remove(journal_path)
+    }
+
+    /// 查找异常退出后留下未保存修改的文档，已过期的日志会被清理
+    pub fn list_recoverable_documents(&self) -> Result<Vec<RecoverableDocument>> {
+        let mut recoverable = Vec::new();
+
+        for book in self.list_books()? {
+            let documents_dir = self.book_dir(&book.id)?.join("documents");
+            if !documents_dir.exists() {
+                continue;
+            }
+
+            let entries = fs::read_dir(&documents_dir)
+                .context("Failed to read documents directory")?;
+            for entry in entries {
+                let doc_dir = entry.context("Failed to read directory entry")?.path();
+                let Some(document_id) = doc_dir.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+
+                match self.recoverable_document(&book, document_id) {
+                    Ok(Some(document)) => recoverable.push(document),
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Failed to replay journal for {}: {}", document_id, e),
+                }
+            }
+        }
+
+        recoverable.sort_by_key(|document| std::cmp::Reverse(document.journaled_at));
+        Ok(recoverable)
+    }
+
+    /// 检查单个文档的日志是否比已保存的内容更新
+    fn recoverable_document(&self, book: &BookConfig, document_id: &str) -> Result<Option<RecoverableDocument>> {
+        let doc_dir = self.document_dir(&book.id, document_id)?;
+        let journal_path = doc_dir.join(JOURNAL_FILE);
+        let Some(replayed) = journal::replay(&journal_path)? else {
+            return Ok(None);
+        };
+
+        let content_path = doc_dir.join("content.md");
+        let saved_at = fs::metadata(&content_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(DateTime::<Utc>::from);
+        let saved_content = fs::read_to_string(&content_path).unwrap_or_default();
+
+        // 日志与已保存的内容相同，说明修改已经落盘。不按时间判断：保存只在包含日志中
+        // 最后的修改时才删除日志，保存时间晚于日志并不代表日志里的修改已经保存
+        if replayed.content == saved_content {
+            journal::remove(&journal_path)?;
+            return Ok(None);
+        }
+
+        let metadata_json = fs::read_to_string(doc_dir.join("metadata.json"))
+            .context("Failed to read document metadata")?;
+        let document: DocumentConfig = serde_json::from_str(&metadata_json)
+            .context("Failed to parse document metadata")?;
+
+        Ok(Some(RecoverableDocument {
+            book_id: book.id.clone(),
+            book_name: book.name.clone(),
+            document_id: document.id,
+            document_title: document.title,
+            saved_at,
+            journaled_at: replayed.timestamp,
+            content: replayed.content,
+        }))
+    }
+
+    /// 恢复编辑日志中的未保存修改，返回恢复后的内容
+    pub fn recover_document_journal(&self, book_id: &str, document_id: &str) -> Result<String> {
+        let journal_path = self.document_dir(book_id, document_id)?.join(JOURNAL_FILE);
+        let replayed = journal::replay(&journal_path)?
+            .ok_or_else(|| anyhow::anyhow!("No unsaved changes for document: {}", document_id))?;
+
+        // 正常保存会同时清理日志
+        self.save_document(book_id, document_id, &replayed.content)?;
+        Ok(replayed.content)
+    }
+
+    /// 放弃编辑日志中的未保存修改
+    pub fn discard_document_journal(&self, book_id: &str, document_id: &str) -> Result<()> {
+        let journal_path = self.document_dir(book_id, document_id)?.join(JOURNAL_FILE);
+        self.lock_journals()?.remove(&journal_path);
+        journal::remove(&journal_path)
+    }
+
+    // ===== 路径解析 =====
+
+    /// 获取项目目录，数据目录下的项目路径都由这里生成
+    fn project_dir(&self, project_id: &str) -> Result<PathBuf> {
+        Ok(self.projects_dir.join(parse_id("project", project_id)?))
+    }
+
+    /// 获取书籍目录，数据目录下的书籍路径都由这里生成
+    fn book_dir(&self, book_id: &str) -> Result<PathBuf> {
+        Ok(self.books_dir.join(parse_id("book", book_id)?))
+    }
+
+    /// 获取文档目录
+    fn document_dir(&self, book_id: &str, document_id: &str) -> Result<PathBuf> {
+        Ok(self.book_dir(book_id)?
+            .join("documents")
+            .join(parse_id("document", document_id)?))
+    }
+
+    // ===== 文档版本管理方法 =====
+
+    /// 读取文档的提交历史（最新的在前）
+    fn read_document_commits(&self, commits_dir: &Path) -> Result<Vec<CommitInfo>> {
+        let index_path = commits_dir.join("commits.json");
+        if !index_path.exists() {
+            return Ok(vec![]);
+        }
+
+        let commits_json = fs::read_to_string(&index_path)
+            .context("Failed to read document commits")?;
+        serde_json::from_str(&commits_json)
+            .context("Failed to parse document commits")
+    }
+
+    /// 把文档的提交历史加入写入事务
+    fn stage_document_commits(&self, transaction: &mut Transaction, commits_dir: &Path, commits: &[CommitInfo]) -> Result<()> {
+        let commits_json = serde_json::to_string_pretty(commits)
+            .context("Failed to serialize document commits")?;
+        transaction.write(&commits_dir.join("commits.json"), commits_json);
+        Ok(())
+    }
+
+    /// 为文档当前内容创建提交
+    pub fn create_document_commit(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        message: &str,
+        is_auto_commit: bool,
+    ) -> Result<CommitInfo> {
+        let content = self.load_document(book_id, document_id)?;
+        let options = CommitOptions { is_auto_commit, ..Default::default() };
+        self.record_document_commit(book_id, document_id, &content, message, options)
+    }
+
+    /// 在当前分支上记录一次提交
+    fn record_document_commit(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        content: &str,
+        message: &str,
+        options: CommitOptions,
+    ) -> Result<CommitInfo> {
+        let doc_dir = self.document_dir(book_id, document_id)?;
+        if !doc_dir.exists() {
+            return Err(anyhow::anyhow!("Document not found: {}", document_id));
+        }
+
+        let text_stats = self.book_text_stats(book_id, content);
+
+        let commits_dir = doc_dir.join("commits");
+        if !commits_dir.exists() {
+            fs::create_dir_all(&commits_dir)
+                .context("Failed to create commits directory")?;
+        }
+
+        let mut refs = self.load_document_refs(book_id, document_id)?;
+        let mut commits = self.read_document_commits(&commits_dir)?;
+
+        // 以当前分支的最新提交为父版本保存增量快照
+        let head_branch = refs.head_branch()?.clone();
+        let parent_ids: Vec<String> = head_branch.head_commit_id
+            .iter()
+            .chain(options.extra_parents)
+            .cloned()
+            .collect();
+        let parent_hash = head_branch.head_commit_id
+            .as_ref()
+            .and_then(|parent_id| commits.iter().find(|commit| &commit.id == parent_id))
+            .map(|parent| parent.document_hash.clone());
+
+        let commit = CommitInfo {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            message: message.to_string(),
+            is_auto_commit: options.is_auto_commit,
+            document_hash: self.objects.put_with_base(content, parent_hash.as_deref())?,
+            word_count: text_stats.word_count,
+            character_count: text_stats.character_count,
+            parent_ids,
+            branch_id: Some(head_branch.id.clone()),
+            group_id: options.group_id.map(str::to_string),
+        };
+
+        commits.insert(0, commit.clone());
+
+        let branch = refs.branch_mut(&head_branch.id)?;
+        branch.head_commit_id = Some(commit.id.clone());
+        branch.last_modified = commit.timestamp;
+
+        // 提交历史和分支引用必须同时更新
+        let mut transaction = self.transaction();
+        self.stage_document_commits(&mut transaction, &commits_dir, &commits)?;
+        self.stage_document_refs(&mut transaction, book_id, document_id, &refs)?;
+        transaction.commit()
+            .context("Failed to write document commit")?;
+
+        Ok(commit)
+    }
+
+    /// 列出文档的所有提交
+    pub fn list_document_commits(&self, book_id: &str, document_id: &str) -> Result<Vec<CommitInfo>> {
+        let commits_dir = self.document_dir(book_id, document_id)?.join("commits");
+        self.read_document_commits(&commits_dir)
+    }
+
+    /// 加载某次提交的文档内容
+    pub fn load_document_commit(&self, book_id: &str, document_id: &str, commit_id: &str) -> Result<String> {
+        let commits_dir = self.document_dir(book_id, document_id)?.join("commits");
+        let commits = self.read_document_commits(&commits_dir)?;
+
+        let commit = commits
+            .iter()
+            .find(|commit| commit.id == commit_id)
+            .ok_or_else(|| anyhow::anyhow!("Commit not found: {}", commit_id))?;
+
+        self.objects.get(&commit.document_hash)
+    }
+
+    /// 加载文档的某个版本，不指定提交时返回工作副本
+    pub fn load_document_version(&self, book_id: &str, document_id: &str, commit_id: Option<&str>) -> Result<String> {
+        match commit_id {
+            Some(commit_id) => self.load_document_commit(book_id, document_id, commit_id),
+            None => self.load_document(book_id, document_id),
+        }
+    }
+
+    /// 删除文档的某次提交
+    ///
+    /// 快照对象可能被其他提交共享，因此只从索引中移除。
+    /// 子提交改为指向被删除提交的父提交，指向它的分支回退到其第一个父提交。
+    pub fn delete_document_commit(&self, book_id: &str, document_id: &str, commit_id: &str) -> Result<()> {
+        let commits_dir = self.document_dir(book_id, document_id)?.join("commits");
+        let mut refs = self.load_document_refs(book_id, document_id)?;
+        let mut commits = self.read_document_commits(&commits_dir)?;
+
+        let position = commits
+            .iter()
+            .position(|commit| commit.id == commit_id)
+            .ok_or_else(|| anyhow::anyhow!("Commit not found: {}", commit_id))?;
+        let removed = commits.remove(position);
+
+        for commit in &mut commits {
+            if commit.parent_ids.iter().any(|parent_id| parent_id == commit_id) {
+                let mut parent_ids = Vec::new();
+                for parent_id in commit.parent_ids.drain(..) {
+                    let replacement = if parent_id == commit_id {
+                        removed.parent_ids.clone()
+                    } else {
+                        vec![parent_id]
+                    };
+                    for id in replacement {
+                        if !parent_ids.contains(&id) {
+                            parent_ids.push(id);
+                        }
+                    }
+                }
+                commit.parent_ids = parent_ids;
+            }
+        }
+
+        for branch in &mut refs.branches {
+            if branch.head_commit_id.as_deref() == Some(commit_id) {
+                branch.head_commit_id = removed.parent_ids.first().cloned();
+            }
+        }
+
+        let mut transaction = self.transaction();
+        self.stage_document_commits(&mut transaction, &commits_dir, &commits)?;
+        self.stage_document_refs(&mut transaction, book_id, document_id, &refs)?;
+        transaction.commit()
+            .context("Failed to write document commits")
+    }
+
+    // ===== 文档分支管理方法 =====
+
+    /// 加载文档的分支引用，旧文档会自动生成默认分支
+    pub fn load_document_refs(&self, book_id: &str, document_id: &str) -> Result<DocumentRefs> {
+        let doc_dir = self.document_dir(book_id, document_id)?;
+        if !doc_dir.exists() {
+            return Err(anyhow::anyhow!("Document not found: {}", document_id));
+        }
+
+        let refs_path = doc_dir.join("refs.json");
+        if refs_path.exists() {
+            let refs_json = fs::read_to_string(&refs_path)
+                .context("Failed to read document refs")?;
+            return serde_json::from_str(&refs_json)
+                .context("Failed to parse document refs");
+        }
+
+        // 没有分支信息时，把已有的线性历史归入默认分支
+        let now = Utc::now();
+        let branch_id = Uuid::new_v4().to_string();
+        let commits_dir = doc_dir.join("commits");
+        let mut commits = self.read_document_commits(&commits_dir)?;
+        let mut transaction = self.transaction();
+
+        if !commits.is_empty() {
+            let older_ids: Vec<Option<String>> = commits
+                .iter()
+                .skip(1)
+                .map(|commit| Some(commit.id.clone()))
+                .chain(std::iter::once(None))
+                .collect();
+            for (commit, parent_id) in commits.iter_mut().zip(older_ids) {
+                if commit.parent_ids.is_empty() {
+                    commit.parent_ids = parent_id.into_iter().collect();
+                }
+                if commit.branch_id.is_none() {
+                    commit.branch_id = Some(branch_id.clone());
+                }
+            }
+            self.stage_document_commits(&mut transaction, &commits_dir, &commits)?;
+        }
+
+        let refs = DocumentRefs {
+            head: branch_id.clone(),
+            branches: vec![BranchInfo {
+                id: branch_id,
+                name: DEFAULT_BRANCH_NAME.to_string(),
+                head_commit_id: commits.first().map(|commit| commit.id.clone()),
+                created_at: now,
+                last_modified: now,
+            }],
+        };
+        self.stage_document_refs(&mut transaction, book_id, document_id, &refs)?;
+        transaction.commit()
+            .context("Failed to write document refs")?;
+
+        Ok(refs)
+    }
+
+    /// 写入文档的分支引用
+    fn write_document_refs(&self, book_id: &str, document_id: &str, refs: &DocumentRefs) -> Result<()> {
+        let mut transaction = self.transaction();
+        self.stage_document_refs(&mut transaction, book_id, document_id, refs)?;
+        transaction.commit()
+            .context("Failed to write document refs")
+    }
+
+    /// 把文档的分支引用加入写入事务
+    fn stage_document_refs(&self, transaction: &mut Transaction, book_id: &str, document_id: &str, refs: &DocumentRefs) -> Result<()> {
+        let refs_path = self.document_dir(book_id, document_id)?.join("refs.json");
+        let refs_json = serde_json::to_string_pretty(refs)
+            .context("Failed to serialize document refs")?;
+        transaction.write(&refs_path, refs_json);
+        Ok(())
+    }
+
+    /// 校验分支名称
+    fn validate_branch_name(refs: &DocumentRefs, name: &str, except_id: Option<&str>) -> Result<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Branch name cannot be empty"));
+        }
+        if name.chars().count() > 100 {
+            return Err(anyhow::anyhow!("Branch name is too long"));
+        }
+        if refs.branches.iter().any(|branch| branch.name == name && Some(branch.id.as_str()) != except_id) {
+            return Err(anyhow::anyhow!("Branch already exists: {}", name));
+        }
+
+        Ok(name.to_string())
+    }
+
+    /// 创建分支，默认从当前分支的最新提交开始
+    pub fn create_document_branch(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        name: &str,
+        from_commit_id: Option<&str>,
+    ) -> Result<BranchInfo> {
+        let mut refs = self.load_document_refs(book_id, document_id)?;
+        let name = Self::validate_branch_name(&refs, name, None)?;
+
+        let head_commit_id = match from_commit_id {
+            Some(commit_id) => {
+                let commits = self.list_document_commits(book_id, document_id)?;
+                if !commits.iter().any(|commit| commit.id == commit_id) {
+                    return Err(anyhow::anyhow!("Commit not found: {}", commit_id));
+                }
+                Some(commit_id.to_string())
+            }
+            None => refs.head_branch()?.head_commit_id.clone(),
+        };
+
+        let now = Utc::now();
+        let branch = BranchInfo {
+            id: Uuid::new_v4().to_string(),
+            name,
+            head_commit_id,
+            created_at: now,
+            last_modified: now,
+        };
+
+        refs.branches.push(branch.clone());
+        self.write_document_refs(book_id, document_id, &refs)?;
+
+        Ok(branch)
+    }
+
+    /// 切换分支，并把工作副本替换为该分支的最新内容
+    ///
+    /// 工作副本有未提交的修改时拒绝切换，避免覆盖正文。
+    pub fn switch_document_branch(&self, book_id: &str, document_id: &str, branch_id: &str) -> Result<String> {
+        let mut refs = self.load_document_refs(book_id, document_id)?;
+        let target = refs.branch(branch_id)?.clone();
+
+        self.ensure_clean_working_copy(book_id, document_id, &refs)?;
+
+        let content = match &target.head_commit_id {
+            Some(commit_id) => self.load_document_commit(book_id, document_id, commit_id)?,
+            None => String::new(),
+        };
+
+        self.save_document(book_id, document_id, &content)?;
+        refs.head = target.id;
+        self.write_document_refs(book_id, document_id, &refs)?;
+
+        Ok(content)
+    }
+
+    /// 当前分支最新提交的内容，还没有提交时为空
+    fn branch_head_content(&self, book_id: &str, document_id: &str, refs: &DocumentRefs) -> Result<String> {
+        match &refs.head_branch()?.head_commit_id {
+            Some(commit_id) => self.load_document_commit(book_id, document_id, commit_id),
+            None => Ok(String::new()),
+        }
+    }
+
+    /// 确认工作副本与当前分支的最新提交一致
+    fn ensure_clean_working_copy(&self, book_id: &str, document_id: &str, refs: &DocumentRefs) -> Result<()> {
+        let current_content = self.load_document(book_id, document_id)?;
+        let head_content = self.branch_head_content(book_id, document_id, refs)?;
+
+        if current_content != head_content {
+            return Err(anyhow::anyhow!("Document has uncommitted changes"));
+        }
+
+        Ok(())
+    }
+
+    /// 重命名分支
+    pub fn rename_document_branch(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        branch_id: &str,
+        new_name: &str,
+    ) -> Result<BranchInfo> {
+        let mut refs = self.load_document_refs(book_id, document_id)?;
+        let name = Self::validate_branch_name(&refs, new_name, Some(branch_id))?;
+
+        let branch = refs.branch_mut(branch_id)?;
+        branch.name = name;
+        branch.last_modified = Utc::now();
+        let branch = branch.clone();
+
+        self.write_document_refs(book_id, document_id, &refs)?;
+
+        Ok(branch)
+    }
+
+    /// 删除分支（提交历史保留）
+    pub fn delete_document_branch(&self, book_id: &str, document_id: &str, branch_id: &str) -> Result<()> {
+        let mut refs = self.load_document_refs(book_id, document_id)?;
+        refs.branch(branch_id)?;
+
+        if refs.head == branch_id {
+            return Err(anyhow::anyhow!("Cannot delete the current branch"));
+        }
+
+        refs.branches.retain(|branch| branch.id != branch_id);
+        self.write_document_refs(book_id, document_id, &refs)
+    }
+
+    // ===== 文档合并方法 =====
+
+    /// 查找两个提交的合并基础（最近的公共祖先）
+    fn find_merge_base(commits: &[CommitInfo], ours: &str, theirs: &str) -> Option<String> {
+        let by_id: HashMap<&str, &CommitInfo> = commits
+            .iter()
+            .map(|commit| (commit.id.as_str(), commit))
+            .collect();
+
+        let ancestors = |start: &str| {
+            let mut seen = HashSet::new();
+            let mut queue = VecDeque::from([start.to_string()]);
+            while let Some(id) = queue.pop_front() {
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                if let Some(commit) = by_id.get(id.as_str()) {
+                    queue.extend(commit.parent_ids.iter().cloned());
+                }
+            }
+            seen
+        };
+
+        let our_ancestors = ancestors(ours);
+        let their_ancestors = ancestors(theirs);
+
+        commits
+            .iter()
+            .filter(|commit| our_ancestors.contains(&commit.id) && their_ancestors.contains(&commit.id))
+            .max_by_key(|commit| commit.timestamp)
+            .map(|commit| commit.id.clone())
+    }
+
+    /// 把另一个分支合并到当前分支
+    ///
+    /// 无冲突时写入工作副本并记录带两个父提交的合并提交；
+    /// 有冲突时不修改任何内容，只返回冲突区域，由 `resolve_document_merge` 完成合并。
+    pub fn merge_document_branch(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        branch_id: &str,
+        message: &str,
+    ) -> Result<DocumentMergeOutcome> {
+        let refs = self.load_document_refs(book_id, document_id)?;
+        if refs.head == branch_id {
+            return Err(anyhow::anyhow!("Cannot merge a branch into itself"));
+        }
+        self.ensure_clean_working_copy(book_id, document_id, &refs)?;
+
+        let ours_commit_id = refs.head_branch()?.head_commit_id.clone();
+        let theirs_commit_id = refs.branch(branch_id)?.head_commit_id.clone()
+            .ok_or_else(|| anyhow::anyhow!("Branch has no commits: {}", branch_id))?;
+
+        let commits = self.list_document_commits(book_id, document_id)?;
+        let base_commit_id = ours_commit_id
+            .as_deref()
+            .and_then(|ours| Self::find_merge_base(&commits, ours, &theirs_commit_id));
+
+        let load = |commit_id: Option<&str>| match commit_id {
+            Some(commit_id) => self.load_document_commit(book_id, document_id, commit_id),
+            None => Ok(String::new()),
+        };
+        let base = load(base_commit_id.as_deref())?;
+        let ours = load(ours_commit_id.as_deref())?;
+        let theirs = load(Some(&theirs_commit_id))?;
+
+        let result = merge::merge_texts(&base, &ours, &theirs);
+
+        let commit = match &result.content {
+            Some(content) if base_commit_id.as_deref() != Some(theirs_commit_id.as_str()) => {
+                self.save_document(book_id, document_id, content)?;
+                let options = CommitOptions {
+                    extra_parents: std::slice::from_ref(&theirs_commit_id),
+                    ..Default::default()
+                };
+                Some(self.record_document_commit(book_id, document_id, content, message, options)?)
+            }
+            _ => None, // 有冲突，或对方分支已包含在当前分支中
+        };
+
+        Ok(DocumentMergeOutcome {
+            base_commit_id,
+            ours_commit_id,
+            theirs_commit_id,
+            result,
+            commit,
+        })
+    }
+
+    /// 用解决冲突后的内容完成合并，记录带两个父提交的合并提交
+    pub fn resolve_document_merge(
+        &self,
+        book_id: &str,
+        document_id: &str,
+        theirs_commit_id: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<CommitInfo> {
+        let commits = self.list_document_commits(book_id, document_id)?;
+        if !commits.iter().any(|commit| commit.id == theirs_commit_id) {
+            return Err(anyhow::anyhow!("Commit not found: {}", theirs_commit_id));
+        }
+
+        self.save_document(book_id, document_id, content)?;
+        let extra_parents = [theirs_commit_id.to_string()];
+        let options = CommitOptions { extra_parents: &extra_parents, ..Default::default() };
+        self.record_document_commit(book_id, document_id, content, message, options)
+    }
+
+    /// 重建文档历史的增量链
+    pub fn repack_document(&self, book_id: &str, document_id: &str) -> Result<RepackStats> {
+        let commits = self.list_document_commits(book_id, document_id)?;
+        let hashes: Vec<String> = commits
+            .iter()
+            .rev()
+            .map(|commit| commit.document_hash.clone())
+            .collect();
+
+        self.objects.repack(&hashes)
+    }
+
+    // ===== 书籍导出方法 =====
+
+    /// 按目录顺序收集要导出的章节，跳过笔记及其下级文档
+    ///
+    /// 卷和章各自成为一个导出章节，场景并入所属的章节。
+    fn export_sections<'a>(&self, book_data: &'a BookData) -> Result<Vec<(&'a DocumentConfig, Chapter)>> {
+        let mut sections: Vec<(&DocumentConfig, Chapter)> = Vec::new();
+        let mut skipped: HashSet<&str> = HashSet::new();
+
+        for document in outline::reading_order(&book_data.documents) {
+            let under_note = document.parent_id.as_deref().is_some_and(|parent_id| skipped.contains(parent_id));
+            if document.doc_type == DocumentType::Note || under_note {
+                skipped.insert(&document.id);
+                continue;
+            }
+
+            let content = self.load_document(&book_data.config.id, &document.id)?;
+            if document.doc_type == DocumentType::Scene {
+                if let Some((chapter_document, chapter)) = sections.last_mut() {
+                    if outline::is_ancestor(&book_data.documents, &chapter_document.id, &document.id) {
+                        append_scene(&mut chapter.content, &content);
+                        continue;
+                    }
+                }
+            }
+            sections.push((document, Chapter { title: document.title.clone(), content }));
+        }
+
+        Ok(sections)
+    }
+
+    /// 按顺序收集要导出的章节
+    fn book_chapters(&self, book_data: &BookData) -> Result<Vec<Chapter>> {
+        Ok(self
+            .export_sections(book_data)?
+            .into_iter()
+            .map(|(_, chapter)| chapter)
+            .collect())
+    }
+
+    /// 导出书籍为 EPUB 3
+    pub fn export_book(&self, book_id: &str, export_path: &Path) -> Result<()> {
+        let book_data = self.load_book(book_id)?;
+        let chapters = self.book_chapters(&book_data)?;
+
+        let cover = match &book_data.config.cover_image {
+            Some(cover_image) => export::load_cover(cover_image, &self.book_dir(book_id)?)?,
+            None => None,
+        };
+
+        let epub = epub::build(&book_data.config, &chapters, cover.as_ref())?;
+        atomic::write_file(export_path, epub)
+            .context("Failed to write EPUB file")
+    }
+
+    /// 导出书籍为投稿格式的 DOCX
+    pub fn export_book_docx(&self, book_id: &str, export_path: &Path, options: &ManuscriptOptions) -> Result<()> {
+        let book_data = self.load_book(book_id)?;
+        let chapters = self.book_chapters(&book_data)?;
+
+        let docx = docx::build(&book_data.config, &chapters, options)?;
+        atomic::write_file(export_path, docx)
+            .context("Failed to write DOCX file")
+    }
+
+    /// 导出书籍为可印刷的 PDF
+    pub fn export_book_pdf(&self, book_id: &str, export_path: &Path, options: &PdfOptions) -> Result<()> {
+        let book_data = self.load_book(book_id)?;
+        let chapters = self.book_chapters(&book_data)?;
+
+        let pdf = pdf::build(&book_data.config, &chapters, options)?;
+        atomic::write_file(export_path, pdf)
+            .context("Failed to write PDF file")
+    }
+
+    /// 导出单个文档为 PDF（不含书名页）
+    pub fn export_document_pdf(&self, book_id: &str, document_id: &str, export_path: &Path, options: &PdfOptions) -> Result<()> {
+        let book_data = self.load_book(book_id)?;
+        let document = book_data
+            .documents
+            .iter()
+            .find(|document| document.id == document_id)
+            .ok_or_else(|| anyhow::anyhow!("Document not found: {}", document_id))?;
+        let chapter = Chapter {
+            title: document.title.clone(),
+            content: self.load_document(book_id, document_id)?,
+        };
+
+        let options = PdfOptions { title_page: false, ..options.clone() };
+        let pdf = pdf::build(&book_data.config, &[chapter], &options)?;
+        atomic::write_file(export_path, pdf)
+            .context("Failed to write PDF file")
+    }
+
+    /// 导出书籍为静态网站，写入指定目录
+    pub fn export_book_site(&self, book_id: &str, export_dir: &Path, options: &SiteOptions) -> Result<()> {
+        let book_data = self.load_book(book_id)?;
+        let chapters: Vec<SiteChapter> = self
+            .export_sections(&book_data)?
+            .into_iter()
+            .map(|(document, chapter)| SiteChapter {
+                id: document.id.clone(),
+                chapter,
+                published: document.last_modified,
+            })
+            .collect();
+
+        let cover = match &book_data.config.cover_image {
+            Some(cover_image) => export::load_cover(cover_image, &self.book_dir(book_id)?)?,
+            None => None,
+        };
+
+        let files = site::build(&book_data.config, &chapters, cover.as_ref(), options);
+        fs::create_dir_all(export_dir)
+            .context("Failed to create site directory")?;
+
+        // 清理上次导出生成、这次不再生成的文件（如已删除的章节）。目录是用户选的，
+        // 只删除清单中记录的文件，不按文件名猜测
+        let manifest_path = export_dir.join(site::MANIFEST_FILE);
+        let previous: Vec<String> = fs::read(&manifest_path)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default();
+        let written: HashSet<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        for name in previous.iter().filter(|name| !written.contains(name.as_str())) {
+            let mut components = Path::new(name).components();
+            if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+                log::warn!("Ignoring unexpected entry in site manifest: {}", name);
+                continue;
+            }
+            match fs::remove_file(export_dir.join(name)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("Failed to remove stale site file {}", name));
+                }
+                _ => {}
+            }
+        }
+
+        for (name, data) in &files {
+            atomic::write_file(&export_dir.join(name), data)
+                .with_context(|| format!("Failed to write site file {}", name))?;
+        }
+
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        let manifest_json = serde_json::to_vec_pretty(&names)
+            .context("Failed to serialize site manifest")?;
+        atomic::write_file(&manifest_path, manifest_json)
+            .context("Failed to write site manifest")?;
+
+        Ok(())
+    }
+
+    /// 保存项目数据
+    pub fn save_project(&self, project_data: &ProjectData) -> Result<()> {
+        let project_dir = self.project_dir(&project_data.config.id)?;
+        let mut transaction = self.transaction();
+        
+        // 保存项目配置
+        let config_json = serde_json::to_string_pretty(&project_data.config)
+            .context("Failed to serialize project config")?;
+        transaction.write(&project_dir.join("config.json"), config_json);
+
+        // 保存文档内容
+        transaction.write(&project_dir.join("document.md"), project_data.document_content.as_str());
+
+        // 保存文档元数据，统计信息以后端计算为准
+        let text_stats = stats::compute(
+            &project_data.document_content,
+            project_data.config.settings.count_markdown_syntax,
+        );
+        let mut document_metadata = project_data.document_metadata.clone();
+        document_metadata.word_count = text_stats.word_count;
+        document_metadata.character_count = text_stats.character_count;
+        document_metadata.line_count = text_stats.line_count;
+
+        let metadata_json = serde_json::to_string_pretty(&document_metadata)
+            .context("Failed to serialize document metadata")?;
+        transaction.write(&project_dir.join("metadata.json"), metadata_json);
+
+        // 提交 ID 会拼成旧快照的文件名，先全部校验
+        for commit in &project_data.commits {
+            parse_commit_id(&commit.id)?;
+        }
+
+        // 保存提交数据到对象存储，提交哈希指向真实对象
+        let legacy_commits_dir = project_dir.join("commit_data");
+        let mut legacy_files = Vec::new();
+        let mut commits = project_data.commits.clone();
+        let mut parent_hash: Option<String> = None;
+
+        // 提交按从新到旧排列，从最旧的开始保存，每个版本以前一版本为增量基础
+        for commit in commits.iter_mut().rev() {
+            if let Some(content) = project_data.commit_data.get(&commit.id) {
+                // 历史版本不会改变，只统计还没有统计信息的提交
+                if commit.document_hash.is_empty() || (commit.word_count == 0 && commit.character_count == 0) {
+                    let commit_stats = stats::compute(content, project_data.config.settings.count_markdown_syntax);
+                    commit.word_count = commit_stats.word_count;
+                    commit.character_count = commit_stats.character_count;
+                }
+
+                commit.document_hash = self.objects.put_with_base(content, parent_hash.as_deref())?;
+                parent_hash = Some(commit.document_hash.clone());
+
+                let legacy_file = legacy_commits_dir.join(format!("{}.md", commit.id));
+                if legacy_file.exists() {
+                    legacy_files.push(legacy_file);
+                }
+            }
+        }
+
+        // 保存提交历史
+        let commits_json = serde_json::to_string_pretty(&commits)
+            .context("Failed to serialize commits")?;
+        transaction.write(&project_dir.join("commits.json"), commits_json);
+
+        transaction.commit()
+            .context("Failed to write project data")?;
+
+        // 提交历史已指向对象存储后，旧快照文件才可以删除
+        for legacy_file in legacy_files {
+            fs::remove_file(&legacy_file)
+                .context("Failed to remove legacy commit data")?;
+        }
+
+        Ok(())
+    }
+
+    /// 加载项目数据
+    pub fn load_project(&self, project_id: &str) -> Result<ProjectData> {
+        let project_dir = self.project_dir(project_id)?;
+        
+        if !project_dir.exists() {
+            return Err(anyhow::anyhow!("Project not found: {}", project_id));
+        }
+
+        // 加载项目配置
+        let config_path = project_dir.join("config.json");
+        let config_json = fs::read_to_string(&config_path)
+            .context("Failed to read project config")?;
+        let config: ProjectConfig = serde_json::from_str(&config_json)
+            .context("Failed to parse project config")?;
+
+        // 加载文档内容
+        let content_path = project_dir.join("document.md");
+        let document_content = fs::read_to_string(&content_path)
+            .unwrap_or_default();
+
+        // 加载文档元数据
+        let metadata_path = project_dir.join("metadata.json");
+        let metadata_json = fs::read_to_string(&metadata_path)
+            .context("Failed to read document metadata")?;
+        let document_metadata: DocumentMetadata = serde_json::from_str(&metadata_json)
             .context("Failed to parse document metadata")?;
 
         // 加载提交历史
         let commits_path = project_dir.join("commits.json");
-        let commits = if commits_path.exists() {
+        let commits: Vec<CommitInfo> = if commits_path.exists() {
             let commits_json = fs::read_to_string(&commits_path)
                 .context("Failed to read commits")?;
             serde_json::from_str(&commits_json)
@@ -623,10 +2538,18 @@ impl FileSystemManager {
             vec![]
         };
 
-        // 加载提交数据
-        let commits_dir = project_dir.join("commit_data");
+        // 加载提交数据，优先从对象存储读取
         let mut commit_data = HashMap::new();
-        
+
+        for commit in &commits {
+            if self.objects.contains(&commit.document_hash) {
+                commit_data.insert(commit.id.clone(), self.objects.get(&commit.document_hash)?);
+            }
+        }
+
+        // 兼容尚未迁移到对象存储的旧项目
+        let commits_dir = project_dir.join("commit_data");
+
         if commits_dir.exists() {
             for entry in fs::read_dir(&commits_dir)? {
                 let entry = entry?;
@@ -638,6 +2561,10 @@ impl FileSystemManager {
                         .unwrap_or_default()
                         .to_string();
                     
+                    if commit_data.contains_key(&commit_id) {
+                        continue;
+                    }
+
                     let content = fs::read_to_string(&path)
                         .unwrap_or_default();
                     
@@ -655,6 +2582,30 @@ impl FileSystemManager {
         })
     }
 
+    /// 重建项目历史的增量链
+    pub fn repack_project(&self, project_id: &str) -> Result<RepackStats> {
+        let project_data = self.load_project(project_id)?;
+
+        // 旧项目的快照需要先迁入对象存储
+        if project_data.commits.iter().any(|commit| !self.objects.contains(&commit.document_hash)) {
+            self.save_project(&project_data)?;
+        }
+
+        let commits_path = self.project_dir(project_id)?.join("commits.json");
+        let commits_json = fs::read_to_string(&commits_path)
+            .context("Failed to read commits")?;
+        let commits: Vec<CommitInfo> = serde_json::from_str(&commits_json)
+            .context("Failed to parse commits")?;
+
+        let hashes: Vec<String> = commits
+            .iter()
+            .rev()
+            .map(|commit| commit.document_hash.clone())
+            .collect();
+
+        self.objects.repack(&hashes)
+    }
+
     /// 列出所有项目
     pub fn list_projects(&self) -> Result<Vec<ProjectConfig>> {
         let mut projects = Vec::new();
@@ -683,14 +2634,14 @@ impl FileSystemManager {
         }
 
         // 按最后修改时间排序
-        projects.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+        projects.sort_by_key(|project| std::cmp::Reverse(project.last_modified));
 
         Ok(projects)
     }
 
     /// 删除项目
     pub fn delete_project(&self, project_id: &str) -> Result<()> {
-        let project_dir = self.projects_dir.join(project_id);
+        let project_dir = self.project_dir(project_id)?;
         
         if project_dir.exists() {
             fs::remove_dir_all(&project_dir)
@@ -710,7 +2661,7 @@ impl FileSystemManager {
 
         // 导出主文档
         let main_doc_path = export_path.join("document.md");
-        fs::write(&main_doc_path, &project_data.document_content)
+        atomic::write_file(&main_doc_path, &project_data.document_content)
             .context("Failed to export main document")?;
 
         // 导出项目信息
@@ -724,7 +2675,7 @@ impl FileSystemManager {
         );
         
         let info_path = export_path.join("project_info.md");
-        fs::write(&info_path, project_info)
+        atomic::write_file(&info_path, project_info)
             .context("Failed to export project info")?;
 
         // 导出版本历史
@@ -735,7 +2686,7 @@ impl FileSystemManager {
 
             for commit in &project_data.commits {
                 if let Some(content) = project_data.commit_data.get(&commit.id) {
-                    let commit_file = history_dir.join(format!("{}.md", commit.id));
+                    let commit_file = history_dir.join(format!("{}.md", parse_commit_id(&commit.id)?));
                     let commit_content = format!(
                         "# 版本: {}\n\n**时间**: {}\n**类型**: {}\n**字数**: {}\n\n---\n\n{}",
                         commit.message,
@@ -744,7 +2695,7 @@ impl FileSystemManager {
                         commit.word_count,
                         content
                     );
-                    fs::write(&commit_file, commit_content)
+                    atomic::write_file(&commit_file, commit_content)
                         .context("Failed to export commit")?;
                 }
             }
@@ -777,4 +2728,833 @@ impl FileSystemManager {
 
         Ok(stats)
     }
+
+    // ===== 项目迁移方法 =====
+
+    /// 把旧项目迁移为同 ID 的书籍
+    ///
+    /// 唯一的文档成为第一章，提交历史迁入该文档的默认分支。
+    /// 书籍已存在时不做任何修改，因此可以重复执行；原项目保留，由调用方决定是否删除。
+    pub fn migrate_project(&self, project_id: &str) -> Result<ProjectMigrationReport> {
+        let project = self.load_project(project_id)?;
+        let book_dir = self.book_dir(project_id)?;
+
+        // config.json 与其他文件在同一事务中写入，存在即说明迁移已完成
+        if book_dir.join("config.json").exists() {
+            let book_data = self.load_book(project_id)?;
+            return Ok(ProjectMigrationReport {
+                project_id: project_id.to_string(),
+                project_name: project.config.name,
+                status: MigrationStatus::AlreadyMigrated,
+                book_id: book_data.config.id,
+                document_id: book_data.documents.first().map(|document| document.id.clone()),
+                commits_migrated: 0,
+                missing_commits: vec![],
+                dropped_settings: vec![],
+                error: None,
+            });
+        }
+
+        let settings = &project.config.settings;
+        let count_markdown_syntax = settings.count_markdown_syntax;
+        let config = BookConfig {
+            id: project.config.id.clone(),
+            name: project.config.name.clone(),
+            description: project.config.description.clone(),
+            author: project.config.author.clone(),
+            genre: String::new(),
+            created_at: project.config.created_at,
+            last_modified: project.config.last_modified,
+            cover_image: None,
+            tags: project.document_metadata.tags.clone(),
+            settings: BookSettings {
+                auto_save_interval: settings.auto_save_interval,
+                editor_theme: settings.editor_theme.clone(),
+                font_size: settings.font_size,
+                line_height: settings.line_height,
+                font_family: settings.font_family.clone(),
+                count_markdown_syntax,
+                ..BookSettings::default()
+            },
+        };
+        let dropped_settings = vec![
+            format!("auto_commit_threshold = {}", settings.auto_commit_threshold),
+            format!("backup_enabled = {}", settings.backup_enabled),
+            format!("backup_interval = {}", settings.backup_interval),
+        ];
+
+        let metadata = &project.document_metadata;
+        let text_stats = stats::compute(&project.document_content, count_markdown_syntax);
+        let document_config = DocumentConfig {
+            id: metadata.id.clone(),
+            book_id: config.id.clone(),
+            parent_id: None,
+            title: metadata.title.clone(),
+            order: 1,
+            doc_type: DocumentType::Chapter,
+            created_at: metadata.created_at,
+            last_modified: metadata.last_modified,
+            word_count: text_stats.word_count,
+            character_count: text_stats.character_count,
+            status: "draft".to_string(),
+        };
+
+        // 从最旧的提交开始重建线性历史，缺少内容的提交被跳过，后续提交改接到前一个可用提交
+        let branch_id = Uuid::new_v4().to_string();
+        let mut commits: Vec<CommitInfo> = Vec::new();
+        let mut missing_commits = Vec::new();
+        for commit in project.commits.iter().rev() {
+            let Some(content) = project.commit_data.get(&commit.id) else {
+                missing_commits.push(commit.id.clone());
+                continue;
+            };
+
+            let parent = commits.first();
+            let commit_stats = stats::compute(content, count_markdown_syntax);
+            let migrated = CommitInfo {
+                document_hash: self.objects.put_with_base(content, parent.map(|parent| parent.document_hash.as_str()))?,
+                word_count: commit_stats.word_count,
+                character_count: commit_stats.character_count,
+                parent_ids: parent.map(|parent| parent.id.clone()).into_iter().collect(),
+                branch_id: Some(branch_id.clone()),
+                ..commit.clone()
+            };
+            commits.insert(0, migrated);
+        }
+
+        let refs = DocumentRefs {
+            head: branch_id.clone(),
+            branches: vec![BranchInfo {
+                id: branch_id,
+                name: DEFAULT_BRANCH_NAME.to_string(),
+                head_commit_id: commits.first().map(|commit| commit.id.clone()),
+                created_at: project.config.created_at,
+                last_modified: commits.first().map_or(project.config.last_modified, |commit| commit.timestamp),
+            }],
+        };
+
+        let book_data = BookData {
+            config,
+            documents: vec![document_config.clone()],
+            current_document_id: Some(document_config.id.clone()),
+        };
+
+        let doc_dir = self.document_dir(project_id, &document_config.id)?;
+        let commits_dir = doc_dir.join("commits");
+        fs::create_dir_all(&commits_dir)
+            .context("Failed to create document directory")?;
+
+        let mut transaction = self.transaction();
+        transaction.write(&doc_dir.join("content.md"), project.document_content.as_str());
+        let metadata_json = serde_json::to_string_pretty(&document_config)
+            .context("Failed to serialize document metadata")?;
+        transaction.write(&doc_dir.join("metadata.json"), metadata_json);
+        self.stage_document_commits(&mut transaction, &commits_dir, &commits)?;
+        self.stage_document_refs(&mut transaction, project_id, &document_config.id, &refs)?;
+        self.stage_book(&mut transaction, &book_data)?;
+        schema::stage_current_version(&mut transaction, &book_dir)?;
+        transaction.commit()
+            .context("Failed to write migrated book")?;
+
+        Ok(ProjectMigrationReport {
+            project_id: project_id.to_string(),
+            project_name: project.config.name,
+            status: MigrationStatus::Migrated,
+            book_id: book_data.config.id,
+            document_id: Some(document_config.id),
+            commits_migrated: commits.len(),
+            missing_commits,
+            dropped_settings,
+            error: None,
+        })
+    }
+
+    /// 迁移所有旧项目，单个项目失败不影响其他项目
+    pub fn migrate_all_projects(&self) -> Result<Vec<ProjectMigrationReport>> {
+        let reports = self
+            .list_projects()?
+            .into_iter()
+            .map(|project| {
+                self.migrate_project(&project.id).unwrap_or_else(|e| ProjectMigrationReport {
+                    book_id: project.id.clone(),
+                    project_id: project.id,
+                    project_name: project.name,
+                    status: MigrationStatus::Failed,
+                    document_id: None,
+                    commits_migrated: 0,
+                    missing_commits: vec![],
+                    dropped_settings: vec![],
+                    error: Some(e.to_string()),
+                })
+            })
+            .collect();
+
+        Ok(reports)
+    }
+}
+
+/// 把场景接在所属章节的正文后面，场景之间用分隔线隔开
+fn append_scene(content: &mut String, scene: &str) {
+    if content.trim().is_empty() {
+        *content = scene.to_string();
+        return;
+    }
+    content.truncate(content.trim_end().len());
+    content.push_str("\n\n* * *\n\n");
+    content.push_str(scene);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn manager() -> (TempDir, FileSystemManager) {
+        let dir = TempDir::new().unwrap();
+        let manager = FileSystemManager::with_data_dir(dir.path()).unwrap();
+        (dir, manager)
+    }
+
+    fn version(step: usize) -> String {
+        (0..1500)
+            .map(|i| if i == step % 1500 {
+                format!("第{}段在第{}次修改时被改写。\n", i, step)
+            } else {
+                format!("Paragraph {} 保持不变，the story goes on.\n", i)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn project_history_round_trips_through_delta_storage() {
+        let (_dir, manager) = manager();
+        let mut project = manager.create_project("长篇", "", "作者").unwrap();
+
+        for step in 0..300 {
+            let content = version(step);
+            let commit = CommitInfo {
+                id: format!("commit-{}", step),
+                timestamp: Utc::now(),
+                message: format!("版本 {}", step),
+                is_auto_commit: true,
+                document_hash: String::new(),
+                word_count: 0,
+                character_count: 0,
+                parent_ids: vec![],
+                branch_id: None,
+                group_id: None,
+            };
+            project.commits.insert(0, commit.clone());
+            project.commit_data.insert(commit.id, content);
+        }
+        manager.save_project(&project).unwrap();
+
+        let mut loaded = manager.load_project(&project.config.id).unwrap();
+        assert_eq!(loaded.commit_data, project.commit_data);
+        for commit in &loaded.commits {
+            assert_eq!(commit.document_hash, ObjectStore::hash(&project.commit_data[&commit.id]));
+            assert!(commit.character_count > 0);
+        }
+
+        // 已有统计信息的历史提交再次保存时不重新统计
+        loaded.commits[1].word_count = 12345;
+        manager.save_project(&loaded).unwrap();
+        let resaved = manager.load_project(&project.config.id).unwrap();
+        assert_eq!(resaved.commits[1].word_count, 12345);
+
+        let stats = manager.repack_project(&project.config.id).unwrap();
+        assert_eq!(stats.objects, 300);
+        let repacked = manager.load_project(&project.config.id).unwrap();
+        assert_eq!(repacked.commit_data, project.commit_data);
+    }
+
+    #[test]
+    fn document_history_round_trips_through_delta_storage() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let document = manager.create_document(&book.config.id, "第一章", DocumentType::Chapter, None).unwrap();
+
+        let mut expected = Vec::new();
+        for step in 0..200 {
+            let content = version(step);
+            manager.save_document(&book.config.id, &document.id, &content).unwrap();
+            let commit = manager
+                .create_document_commit(&book.config.id, &document.id, "自动保存", true)
+                .unwrap();
+            expected.push((commit.id, content));
+        }
+
+        manager.repack_document(&book.config.id, &document.id).unwrap();
+
+        for (commit_id, content) in &expected {
+            let loaded = manager
+                .load_document_commit(&book.config.id, &document.id, commit_id)
+                .unwrap();
+            assert_eq!(&loaded, content);
+        }
+    }
+
+    #[test]
+    fn unsaved_edits_are_recovered_from_journal() {
+        let (dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let doc_id = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+
+        manager.save_document(book_id, &doc_id, "已保存").unwrap();
+        manager.append_document_journal(book_id, &doc_id, "已保存，还在写").unwrap();
+        manager.append_document_journal(book_id, &doc_id, "已保存，还在写第二句").unwrap();
+
+        // 模拟异常退出后重新启动
+        drop(manager);
+        let manager = FileSystemManager::with_data_dir(dir.path()).unwrap();
+        let recoverable = manager.list_recoverable_documents().unwrap();
+        assert_eq!(recoverable.len(), 1);
+        assert_eq!(recoverable[0].content, "已保存，还在写第二句");
+
+        let content = manager.recover_document_journal(book_id, &doc_id).unwrap();
+        assert_eq!(manager.load_document(book_id, &doc_id).unwrap(), content);
+        assert!(manager.list_recoverable_documents().unwrap().is_empty());
+
+        // 保存请求晚于更新的日志到达时，日志保留到包含这些修改的保存完成
+        let journal_path = manager.document_dir(book_id, &doc_id).unwrap().join(JOURNAL_FILE);
+        manager.append_document_journal(book_id, &doc_id, "新的修改").unwrap();
+        manager.append_document_journal(book_id, &doc_id, "新的修改，又写了一句").unwrap();
+        manager.save_document(book_id, &doc_id, "新的修改").unwrap();
+        assert!(journal_path.exists());
+        manager.save_document(book_id, &doc_id, "新的修改，又写了一句").unwrap();
+        assert!(!journal_path.exists());
+        assert!(manager.list_recoverable_documents().unwrap().is_empty());
+    }
+
+    #[test]
+    fn upgrades_books_at_startup_and_lists_without_upgrading() {
+        let (dir, manager) = manager();
+        let old = manager.create_book("旧书", "", "作者", "小说").unwrap().config.id;
+        let newer = manager.create_book("新版本的书", "", "作者", "小说").unwrap().config.id;
+        drop(manager);
+
+        let books_dir = dir.path().join("books");
+        fs::remove_file(books_dir.join(&old).join("schema.json")).unwrap();
+        fs::write(books_dir.join(&newer).join("schema.json"), r#"{"version":99,"app_version":"9.9.9"}"#).unwrap();
+
+        // 旧格式的书籍在启动时升级并备份一次，之后列出书籍不再升级
+        let manager = FileSystemManager::with_data_dir(dir.path()).unwrap();
+        assert_eq!(schema::read_version(&books_dir.join(&old)).unwrap(), schema::CURRENT_VERSION);
+        let names: Vec<String> = manager.list_books().unwrap().into_iter().map(|book| book.name).collect();
+        assert_eq!(names, vec!["旧书"]);
+        manager.list_books().unwrap();
+        assert_eq!(fs::read_dir(dir.path().join("backups")).unwrap().count(), 1);
+
+        assert!(manager.load_book(&newer).is_err());
+    }
+
+    #[test]
+    fn imports_book_from_single_file() {
+        let (dir, manager) = manager();
+        let source = dir.path().join("雨夜.md");
+        fs::write(&source, "## 第一章\n\n雨停了。\n\n## 第二章\n\n风起了，很大。\n").unwrap();
+
+        let book = manager.import_book(&source, &ImportOptions::default()).unwrap();
+        assert_eq!(book.config.name, "雨夜");
+        let titles: Vec<&str> = book.documents.iter().map(|document| document.title.as_str()).collect();
+        assert_eq!(titles, vec!["第一章", "第二章"]);
+        assert_eq!(book.documents[1].order, 2);
+        assert_eq!(book.documents[1].word_count, 5);
+        assert_eq!(book.current_document_id.as_ref(), Some(&book.documents[0].id));
+
+        let loaded = manager.load_book(&book.config.id).unwrap();
+        assert_eq!(loaded.documents.len(), 2);
+        assert_eq!(manager.load_document(&book.config.id, &book.documents[0].id).unwrap(), "雨停了。");
+
+        // 无法读取的来源不会留下空书
+        let books = manager.list_books().unwrap().len();
+        assert!(manager.import_book(&dir.path().join("missing.md"), &ImportOptions::default()).is_err());
+        assert_eq!(manager.list_books().unwrap().len(), books);
+    }
+
+    #[test]
+    fn migrates_project_to_book_once() {
+        let (_dir, manager) = manager();
+        let mut project = manager.create_project("旧稿", "简介", "作者").unwrap();
+        project.config.settings.font_size = 18;
+        project.document_content = "第三稿".to_string();
+        for (step, content) in ["初稿", "第二稿"].iter().enumerate() {
+            let commit = CommitInfo {
+                id: format!("commit-{}", step),
+                timestamp: Utc::now(),
+                message: format!("版本 {}", step),
+                is_auto_commit: false,
+                document_hash: String::new(),
+                word_count: 0,
+                character_count: 0,
+                parent_ids: vec![],
+                branch_id: None,
+                group_id: None,
+            };
+            project.commits.insert(0, commit.clone());
+            project.commit_data.insert(commit.id, content.to_string());
+        }
+        manager.save_project(&project).unwrap();
+        let project_id = project.config.id.as_str();
+
+        let report = manager.migrate_project(project_id).unwrap();
+        assert_eq!(report.status, MigrationStatus::Migrated);
+        assert_eq!(report.commits_migrated, 2);
+        assert!(report.missing_commits.is_empty());
+
+        let book = manager.load_book(project_id).unwrap();
+        assert_eq!(book.config.name, "旧稿");
+        assert_eq!(book.config.settings.font_size, 18);
+        assert_eq!(book.documents.len(), 1);
+        let document_id = book.documents[0].id.as_str();
+        assert_eq!(manager.load_document(project_id, document_id).unwrap(), "第三稿");
+
+        let commits = manager.list_document_commits(project_id, document_id).unwrap();
+        assert_eq!(commits.iter().map(|commit| commit.id.as_str()).collect::<Vec<_>>(), vec!["commit-1", "commit-0"]);
+        assert_eq!(commits[0].parent_ids, vec!["commit-0".to_string()]);
+        assert_eq!(manager.load_document_commit(project_id, document_id, "commit-0").unwrap(), "初稿");
+        let refs = manager.load_document_refs(project_id, document_id).unwrap();
+        assert_eq!(refs.head_branch().unwrap().head_commit_id.as_deref(), Some("commit-1"));
+
+        // 再次迁移不会重复创建
+        let reports = manager.migrate_all_projects().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].status, MigrationStatus::AlreadyMigrated);
+        assert_eq!(manager.list_books().unwrap().len(), 1);
+        assert_eq!(manager.list_document_commits(project_id, document_id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn search_finds_saved_documents() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("凡人", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let document = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap();
+        manager.save_document(book_id, &document.id, "山村少年。\n韩立第一次出场。").unwrap();
+
+        let results = manager.search("韩立", &SearchOptions::default()).unwrap();
+        assert_eq!(results.total, 1);
+        assert_eq!(results.hits[0].document_title, "第一章");
+        assert_eq!(results.hits[0].line, 2);
+
+        // 保存后索引立即更新
+        manager.save_document(book_id, &document.id, "山村少年。").unwrap();
+        assert_eq!(manager.search("韩立", &SearchOptions::default()).unwrap().total, 0);
+        let other_book = SearchOptions { book_ids: vec!["other".to_string()], limit: None };
+        assert_eq!(manager.search("山村", &other_book).unwrap().total, 0);
+    }
+
+    #[test]
+    fn nests_documents_and_exports_scenes_within_chapters() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let part = manager.create_document(book_id, "第一卷", DocumentType::Part, None).unwrap().id;
+        let chapter = manager.create_document(book_id, "第一章", DocumentType::Chapter, Some(&part)).unwrap().id;
+        let scene = manager.create_document(book_id, "雨夜", DocumentType::Scene, None).unwrap().id;
+        let other_scene = manager.create_document(book_id, "天亮", DocumentType::Scene, Some(&chapter)).unwrap().id;
+        manager.save_document(book_id, &chapter, "序。\n").unwrap();
+        manager.save_document(book_id, &scene, "雨下了一夜。\n").unwrap();
+        manager.save_document(book_id, &other_scene, "天亮了。\n").unwrap();
+        assert!(manager.create_document(book_id, "孤儿", DocumentType::Scene, Some("missing")).is_err());
+
+        let tree = manager.move_document(book_id, &scene, Some(&chapter), Some(0)).unwrap();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].children[0].children[0].document.id, scene);
+        assert_eq!(tree[0].total_word_count, tree[0].children[0].total_word_count);
+        assert!(manager.move_document(book_id, &part, Some(&scene), None).is_err());
+
+        // 元数据与 documents.json 保持一致
+        let metadata = fs::read_to_string(manager.document_dir(book_id, &scene).unwrap().join("metadata.json")).unwrap();
+        let metadata: DocumentConfig = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(metadata.parent_id.as_deref(), Some(chapter.as_str()));
+        assert_eq!(metadata.order, 1);
+
+        let book_data = manager.load_book(book_id).unwrap();
+        let chapters = manager.book_chapters(&book_data).unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "第一卷");
+        assert_eq!(chapters[1].content, "序。\n\n* * *\n\n雨下了一夜。\n\n* * *\n\n天亮了。\n");
+
+        // 删除章节后场景上移到卷下
+        manager.delete_document(book_id, &chapter).unwrap();
+        let tree = manager.get_document_tree(book_id).unwrap();
+        let children: Vec<&str> = tree[0].children.iter().map(|node| node.document.id.as_str()).collect();
+        assert_eq!(children, vec![scene.as_str(), other_scene.as_str()]);
+    }
+
+    #[test]
+    fn site_export_only_removes_files_it_generated() {
+        let (dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        for title in ["第一章", "第二章"] {
+            let id = manager.create_document(book_id, title, DocumentType::Chapter, None).unwrap().id;
+            manager.save_document(book_id, &id, "正文。\n").unwrap();
+        }
+
+        // 用户目录中原有的同名样式文件不属于导出结果
+        let site_dir = dir.path().join("site");
+        fs::create_dir_all(&site_dir).unwrap();
+        fs::write(site_dir.join("chapter-099.html"), "mine").unwrap();
+        fs::write(site_dir.join("feed.xml"), "mine").unwrap();
+
+        manager.export_book_site(book_id, &site_dir, &site::SiteOptions::default()).unwrap();
+        assert!(site_dir.join("chapter-002.html").exists());
+
+        let second = manager.load_book(book_id).unwrap().documents[1].id.clone();
+        manager.delete_document(book_id, &second).unwrap();
+        manager.export_book_site(book_id, &site_dir, &site::SiteOptions::default()).unwrap();
+        assert!(site_dir.join("chapter-001.html").exists());
+        assert!(!site_dir.join("chapter-002.html").exists());
+        assert_eq!(fs::read_to_string(site_dir.join("chapter-099.html")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(site_dir.join("feed.xml")).unwrap(), "mine");
+    }
+
+    #[test]
+    fn status_changes_follow_workflow_and_are_recorded() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let doc_id = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+
+        let rule = |from: &str, to: &str| StatusRule { from: from.to_string(), to: to.to_string() };
+        let workflow = StatusWorkflow {
+            statuses: ["outline", "draft", "revise", "copyedit", "final"].map(String::from).to_vec(),
+            transitions: vec![rule("outline", "draft"), rule("draft", "revise"), rule("revise", "draft"), rule("revise", "copyedit"), rule("copyedit", "final")],
+        };
+        // 仍有文档处于 draft，不能从流程中删掉它
+        let without_draft = StatusWorkflow { statuses: vec!["outline".to_string()], transitions: vec![] };
+        assert!(manager.save_status_workflow(book_id, &without_draft).is_err());
+        let broken = StatusWorkflow { transitions: vec![rule("draft", "published")], ..workflow.clone() };
+        assert!(manager.save_status_workflow(book_id, &broken).is_err());
+        manager.save_status_workflow(book_id, &workflow).unwrap();
+
+        // 新文档使用流程的第一个状态
+        let outlined = manager.create_document(book_id, "第二章", DocumentType::Chapter, None).unwrap();
+        assert_eq!(outlined.status, "outline");
+
+        assert!(manager.set_document_status(book_id, &doc_id, "final").is_err());
+        assert!(manager.set_document_status(book_id, &doc_id, "fnial").is_err());
+        manager.set_document_status(book_id, &doc_id, "revise").unwrap();
+
+        // 通过 save_book 修改状态同样受流程约束并记入历史
+        let mut book_data = manager.load_book(book_id).unwrap();
+        book_data.documents.iter_mut().find(|document| document.id == doc_id).unwrap().status = "outline".to_string();
+        assert!(manager.save_book(&book_data).is_err());
+        book_data.documents.iter_mut().find(|document| document.id == doc_id).unwrap().status = "copyedit".to_string();
+        manager.save_book(&book_data).unwrap();
+
+        let history = manager.list_status_history(book_id, &doc_id).unwrap();
+        let changes: Vec<(&str, &str)> = history.iter().map(|change| (change.from.as_str(), change.to.as_str())).collect();
+        assert_eq!(changes, vec![("draft", "revise"), ("revise", "copyedit")]);
+        let metadata = fs::read_to_string(manager.document_dir(book_id, &doc_id).unwrap().join("metadata.json")).unwrap();
+        assert!(metadata.contains("\"status\": \"copyedit\""));
+    }
+
+    #[test]
+    fn deleted_books_and_documents_can_be_restored_from_trash() {
+        let (dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let first = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+        let second = manager.create_document(book_id, "第二章", DocumentType::Chapter, None).unwrap().id;
+        let scene = manager.create_document(book_id, "场景", DocumentType::Scene, Some(&second)).unwrap().id;
+        let third = manager.create_document(book_id, "第三章", DocumentType::Chapter, None).unwrap().id;
+        manager.save_document(book_id, &second, "第二章正文").unwrap();
+        manager.create_document_commit(book_id, &second, "保存", false).unwrap();
+
+        manager.delete_document(book_id, &second).unwrap();
+        assert!(manager.search("第二章正文", &SearchOptions::default()).unwrap().hits.is_empty());
+        let trash = manager.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].title, "第二章");
+
+        // 恢复后回到原来的位置，上移的场景重新归到它下面，历史也还在
+        manager.restore_from_trash(&trash[0].id).unwrap();
+        let ids: Vec<String> = manager.list_documents(book_id).unwrap().into_iter().map(|document| document.id).collect();
+        assert_eq!(ids, vec![first.clone(), second.clone(), scene.clone(), third.clone()]);
+        assert_eq!(manager.load_document(book_id, &second).unwrap(), "第二章正文");
+        assert_eq!(manager.list_document_commits(book_id, &second).unwrap().len(), 1);
+        assert!(manager.list_trash().unwrap().is_empty());
+
+        // 写入文档列表失败时，恢复出来的文档回到回收站
+        manager.delete_document(book_id, &second).unwrap();
+        let entry = manager.list_trash().unwrap().remove(0);
+        let metadata_path = dir.path().join("trash").join(&entry.id).join("item").join("metadata.json");
+        fs::remove_file(&metadata_path).unwrap();
+        fs::create_dir_all(metadata_path.join("blocked")).unwrap();
+        assert!(manager.restore_from_trash(&entry.id).is_err());
+        assert!(!manager.document_dir(book_id, &second).unwrap().exists());
+        assert_eq!(manager.list_trash().unwrap().len(), 1);
+        assert_eq!(manager.list_documents(book_id).unwrap().len(), 3);
+        fs::remove_dir_all(&metadata_path).unwrap();
+        manager.restore_from_trash(&entry.id).unwrap();
+        assert_eq!(manager.list_documents(book_id).unwrap().len(), 4);
+
+        manager.delete_book(book_id).unwrap();
+        assert!(manager.load_book(book_id).is_err());
+        let entry = manager.list_trash().unwrap().remove(0);
+        assert_eq!(entry.title, "书");
+        manager.restore_from_trash(&entry.id).unwrap();
+        assert_eq!(manager.list_documents(book_id).unwrap().len(), 4);
+
+        manager.delete_document(book_id, &third).unwrap();
+        assert_eq!(manager.empty_trash().unwrap(), 1);
+        assert!(manager.list_trash().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_ids_that_could_leave_the_data_directory() {
+        let (dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let document = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap();
+        manager.save_document(book_id, &document.id, "正文").unwrap();
+
+        // 数据目录旁边的目录，`..` 若被接受就会被读到或移走
+        let outside = dir.path().parent().unwrap().join(format!("{}-outside", Uuid::new_v4()));
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("config.json"), "{}").unwrap();
+        let outside_name = outside.file_name().unwrap().to_str().unwrap().to_string();
+
+        let uuid = Uuid::new_v4();
+        let hostile = [
+            "".to_string(),
+            ".".to_string(),
+            "..".to_string(),
+            "../..".to_string(),
+            format!("../../{}", outside_name),
+            format!("..\\..\\{}", outside_name),
+            outside.to_string_lossy().to_string(),
+            format!("{}/..", book_id),
+            format!("{}\0", book_id),
+            format!("{{{}}}", uuid),
+            format!("urn:uuid:{}", uuid),
+            uuid.simple().to_string(),
+            "../../.ssh".to_string(),
+        ];
+
+        for id in &hostile {
+            assert!(manager.load_book(id).is_err(), "load_book({:?})", id);
+            assert!(manager.delete_book(id).is_err(), "delete_book({:?})", id);
+            assert!(manager.create_document(id, "章", DocumentType::Chapter, None).is_err());
+            assert!(manager.load_document(id, &document.id).is_err());
+            assert!(manager.load_document(book_id, id).is_err(), "load_document({:?})", id);
+            assert!(manager.save_document(book_id, id, "覆盖").is_err(), "save_document({:?})", id);
+            assert!(manager.delete_document(book_id, id).is_err());
+            assert!(manager.list_document_commits(book_id, id).is_err());
+            assert!(manager.load_status_workflow(id).is_err());
+            assert!(manager.load_project(id).is_err());
+            assert!(manager.delete_project(id).is_err());
+            assert!(manager.restore_from_trash(id).is_err());
+        }
+
+        // 项目提交的 ID 会拼成旧快照文件名，保存时删除旧快照，不能指向外面的文件
+        let victim = outside.join("victim.md");
+        fs::write(&victim, "留着").unwrap();
+        let mut project = manager.create_project("项目", "", "作者").unwrap();
+        let project_dir = dir.path().join("projects").join(&project.config.id);
+        let relative_victim = format!("../../../../{}/victim", outside_name);
+        fs::create_dir_all(project_dir.join("commit_data")).unwrap();
+        for id in [relative_victim.as_str(), "a/b", "..", ""] {
+            project.commits = vec![CommitInfo {
+                id: id.to_string(),
+                timestamp: Utc::now(),
+                message: "恶意提交".to_string(),
+                is_auto_commit: false,
+                document_hash: String::new(),
+                word_count: 0,
+                character_count: 0,
+                parent_ids: vec![],
+                branch_id: None,
+                group_id: None,
+            }];
+            project.commit_data = HashMap::from([(id.to_string(), "内容".to_string())]);
+            assert!(manager.save_project(&project).is_err(), "save_project({:?})", id);
+        }
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "留着");
+
+        // 写进书籍数据的 ID 同样检查
+        let mut tampered = manager.load_book(book_id).unwrap();
+        tampered.config.id = format!("../../{}", outside_name);
+        assert!(manager.save_book(&tampered).is_err());
+        let mut tampered = manager.load_book(book_id).unwrap();
+        tampered.documents[0].id = "../../..".to_string();
+        assert!(manager.save_book(&tampered).is_err());
+
+        assert_eq!(fs::read_to_string(outside.join("config.json")).unwrap(), "{}");
+        assert_eq!(manager.load_document(book_id, &document.id).unwrap(), "正文");
+        assert_eq!(manager.list_books().unwrap().len(), 1);
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn replaces_across_book_and_reverts_as_a_group() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let first = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+        let second = manager.create_document(book_id, "第二章", DocumentType::Chapter, None).unwrap().id;
+        manager.save_document(book_id, &first, "韩立见到了墨大夫。\n\n天黑了。\n").unwrap();
+        manager.create_document_commit(book_id, &first, "保存", false).unwrap();
+        manager.save_document(book_id, &second, "墨大夫说：“墨大夫不在。”").unwrap();
+
+        let options = ReplaceOptions { find: "墨大夫".to_string(), replace: "墨老".to_string(), dry_run: true, ..Default::default() };
+        let preview = manager.replace_in_book(book_id, &options).unwrap();
+        assert_eq!(preview.replacement_count, 3);
+        assert_eq!(preview.documents, vec![first.clone(), second.clone()]);
+        assert_eq!(preview.matches[2].before, "墨大夫说：“");
+        assert!(preview.group_id.is_none());
+        assert_eq!(manager.load_document(book_id, &first).unwrap(), "韩立见到了墨大夫。\n\n天黑了。\n");
+
+        let filtered = ReplaceOptions { statuses: vec!["final".to_string()], ..options.clone() };
+        assert_eq!(manager.replace_in_book(book_id, &filtered).unwrap().replacement_count, 0);
+
+        // 第三篇文档的分支引用损坏，替换失败，不影响其余文档，分组仍可撤销
+        let broken = manager.create_document(book_id, "第三章", DocumentType::Chapter, None).unwrap().id;
+        manager.save_document(book_id, &broken, "墨大夫").unwrap();
+        let broken_refs = manager.document_dir(book_id, &broken).unwrap().join("refs.json");
+        fs::write(&broken_refs, "{").unwrap();
+
+        let applied = manager.replace_in_book(book_id, &ReplaceOptions { dry_run: false, ..options }).unwrap();
+        let group_id = applied.group_id.unwrap();
+        assert_eq!(applied.documents, vec![first.clone(), second.clone()]);
+        assert_eq!(applied.failed, vec![broken.clone()]);
+        assert_eq!(manager.load_document(book_id, &broken).unwrap(), "墨大夫");
+        assert_eq!(manager.load_document(book_id, &second).unwrap(), "墨老说：“墨老不在。”");
+        // 未提交的内容先自动保存，再记录替换
+        let commits = manager.list_document_commits(book_id, &second).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].group_id.as_deref(), Some(group_id.as_str()));
+        assert!(commits[1].is_auto_commit);
+
+        // 撤销时保留替换之后的编辑
+        manager.save_document(book_id, &first, "韩立见到了墨老。\n\n第二天一早。\n").unwrap();
+        let reverted = manager.revert_commit_group(book_id, &group_id).unwrap();
+        assert_eq!(reverted.documents, vec![first.clone(), second.clone()]);
+        assert!(reverted.conflicts.is_empty());
+        assert_eq!(manager.load_document(book_id, &first).unwrap(), "韩立见到了墨大夫。\n\n第二天一早。\n");
+        assert_eq!(manager.load_document(book_id, &second).unwrap(), "墨大夫说：“墨大夫不在。”");
+
+        assert!(manager.revert_commit_group(book_id, "missing").is_err());
+    }
+
+    #[test]
+    fn history_search_reports_when_text_appears_and_disappears() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let doc_id = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+
+        let mut commit_ids = Vec::new();
+        for content in ["雨夜。", "雨夜。\n他在桥下等她。", "雨夜。\n他在桥下等她。天亮了。", "雨夜。\n天亮了。"] {
+            manager.save_document(book_id, &doc_id, content).unwrap();
+            commit_ids.push(manager.create_document_commit(book_id, &doc_id, "保存", false).unwrap().id);
+        }
+
+        let matches = manager.search_history(book_id, Some(&doc_id), "桥下").unwrap();
+        let changes: Vec<(HistoryChange, &str)> = matches.iter().map(|found| (found.change, found.commit.id.as_str())).collect();
+        assert_eq!(changes, vec![
+            (HistoryChange::Appeared, commit_ids[1].as_str()),
+            (HistoryChange::Disappeared, commit_ids[3].as_str()),
+        ]);
+        // 消失时的片段取自之前的版本
+        assert_eq!(matches[1].line, 2);
+        assert!(matches[1].snippet.contains("桥下"));
+
+        assert!(manager.search_history(book_id, None, "不存在").unwrap().is_empty());
+        assert!(manager.search_history(book_id, Some("missing"), "桥下").is_err());
+    }
+
+    #[test]
+    fn branches_track_parents_and_switch_working_copy() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let doc_id = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+
+        manager.save_document(book_id, &doc_id, "开头").unwrap();
+        let root = manager.create_document_commit(book_id, &doc_id, "开头", false).unwrap();
+        assert!(root.parent_ids.is_empty());
+
+        let main_id = manager.load_document_refs(book_id, &doc_id).unwrap().head;
+        let alt = manager.create_document_branch(book_id, &doc_id, "另一种结局", None).unwrap();
+        assert_eq!(alt.head_commit_id.as_deref(), Some(root.id.as_str()));
+        assert!(manager.create_document_branch(book_id, &doc_id, "另一种结局", None).is_err());
+
+        manager.switch_document_branch(book_id, &doc_id, &alt.id).unwrap();
+        manager.save_document(book_id, &doc_id, "开头，然后她离开了").unwrap();
+        let alt_commit = manager.create_document_commit(book_id, &doc_id, "离开", false).unwrap();
+        assert_eq!(alt_commit.parent_ids, vec![root.id.clone()]);
+        assert_eq!(alt_commit.branch_id.as_deref(), Some(alt.id.as_str()));
+
+        // 未提交的修改会阻止切换
+        manager.save_document(book_id, &doc_id, "未保存的草稿").unwrap();
+        assert!(manager.switch_document_branch(book_id, &doc_id, &main_id).is_err());
+        manager.save_document(book_id, &doc_id, "开头，然后她离开了").unwrap();
+
+        let content = manager.switch_document_branch(book_id, &doc_id, &main_id).unwrap();
+        assert_eq!(content, "开头");
+        assert_eq!(manager.load_document(book_id, &doc_id).unwrap(), "开头");
+
+        let renamed = manager.rename_document_branch(book_id, &doc_id, &alt.id, "悲剧结局").unwrap();
+        assert_eq!(renamed.name, "悲剧结局");
+        assert!(manager.delete_document_branch(book_id, &doc_id, &main_id).is_err());
+        manager.delete_document_branch(book_id, &doc_id, &alt.id).unwrap();
+
+        let refs = manager.load_document_refs(book_id, &doc_id).unwrap();
+        assert_eq!(refs.branches.len(), 1);
+        assert_eq!(manager.list_document_commits(book_id, &doc_id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn merging_branches_records_two_parents() {
+        let (_dir, manager) = manager();
+        let book = manager.create_book("书", "", "作者", "小说").unwrap();
+        let book_id = book.config.id.as_str();
+        let doc_id = manager.create_document(book_id, "第一章", DocumentType::Chapter, None).unwrap().id;
+
+        manager.save_document(book_id, &doc_id, "雨停了。\n\n她推开门。\n").unwrap();
+        let base = manager.create_document_commit(book_id, &doc_id, "初稿", false).unwrap();
+        let main_id = manager.load_document_refs(book_id, &doc_id).unwrap().head;
+        let alt = manager.create_document_branch(book_id, &doc_id, "改写", None).unwrap();
+
+        manager.save_document(book_id, &doc_id, "雨停了很久。\n\n她推开门。\n").unwrap();
+        let ours = manager.create_document_commit(book_id, &doc_id, "改开头", false).unwrap();
+
+        manager.switch_document_branch(book_id, &doc_id, &alt.id).unwrap();
+        manager.save_document(book_id, &doc_id, "雨停了。\n\n她轻轻推开门。\n").unwrap();
+        let theirs = manager.create_document_commit(book_id, &doc_id, "改结尾", false).unwrap();
+
+        manager.switch_document_branch(book_id, &doc_id, &main_id).unwrap();
+        let outcome = manager.merge_document_branch(book_id, &doc_id, &alt.id, "合并改写").unwrap();
+
+        assert_eq!(outcome.base_commit_id.as_deref(), Some(base.id.as_str()));
+        let merge_commit = outcome.commit.unwrap();
+        assert_eq!(merge_commit.parent_ids, vec![ours.id, theirs.id]);
+        assert_eq!(
+            manager.load_document(book_id, &doc_id).unwrap(),
+            "雨停了很久。\n\n她轻轻推开门。\n"
+        );
+
+        // 冲突时不修改工作副本
+        manager.save_document(book_id, &doc_id, "雪停了很久。\n\n她轻轻推开门。\n").unwrap();
+        manager.create_document_commit(book_id, &doc_id, "改成雪", false).unwrap();
+        manager.switch_document_branch(book_id, &doc_id, &alt.id).unwrap();
+        manager.save_document(book_id, &doc_id, "风停了很久。\n\n她轻轻推开门。\n").unwrap();
+        let windy = manager.create_document_commit(book_id, &doc_id, "改成风", false).unwrap();
+        manager.switch_document_branch(book_id, &doc_id, &main_id).unwrap();
+
+        let conflicted = manager.merge_document_branch(book_id, &doc_id, &alt.id, "合并").unwrap();
+        assert_eq!(conflicted.result.conflict_count, 1);
+        assert!(conflicted.commit.is_none());
+
+        let resolved = manager
+            .resolve_document_merge(book_id, &doc_id, &windy.id, "风雪停了很久。\n\n她轻轻推开门。\n", "解决冲突")
+            .unwrap();
+        assert_eq!(resolved.parent_ids.len(), 2);
+        assert_eq!(resolved.parent_ids[1], windy.id);
+    }
 }
@@ -0,0 +1,536 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::text;
+
+/// 扫描字体目录的最大深度
+const MAX_SCAN_DEPTH: usize = 6;
+
+/// 字形样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    pub fn from_flags(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => Self::Regular,
+            (true, false) => Self::Bold,
+            (false, true) => Self::Italic,
+            (true, true) => Self::BoldItalic,
+        }
+    }
+}
+
+/// CSS 通用字体族对应的常见字体
+const SERIF: &[&str] = &["Times New Roman", "Times", "Liberation Serif", "DejaVu Serif", "Noto Serif", "Georgia"];
+const SANS_SERIF: &[&str] = &["Arial", "Helvetica", "Liberation Sans", "DejaVu Sans", "Noto Sans", "Segoe UI"];
+const MONOSPACE: &[&str] = &["DejaVu Sans Mono", "Liberation Mono", "Courier New", "Menlo", "Consolas", "Noto Sans Mono"];
+
+/// 主字体缺字时用于中日韩文字的后备字体
+const CJK_FALLBACKS: &[&str] = &[
+    "Noto Serif CJK", "Noto Sans CJK", "Source Han Serif", "Source Han Sans", "Songti", "PingFang",
+    "Hiragino Sans GB", "STSong", "STHeiti", "SimSun", "Microsoft YaHei", "msyh", "simsun",
+    "WenQuanYi Zen Hei", "wqy-zenhei", "wqy-microhei", "Droid Sans Fallback", "AR PL UMing",
+];
+
+/// 一个已加载的 TrueType 字体
+pub struct Font {
+    pub data: Vec<u8>,
+    units_per_em: f32,
+    advances: HashMap<char, Option<f32>>, // 以 em 为单位的字宽
+}
+
+impl Font {
+    /// 加载字体文件，只接受 TrueType 轮廓（PDF 以 FontFile2 嵌入）
+    ///
+    /// 字体集（.ttc）只取第一个字体，另存为独立的字体数据。CFF 轮廓的 OpenType 字体
+    /// （多数 .otf，包括思源宋体等 OTF 版本的中日韩字体）无法嵌入，会返回错误。
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut data = fs::read(path)
+            .with_context(|| format!("Failed to read font {}", path.display()))?;
+        if data.starts_with(b"ttcf") {
+            data = extract_first_face(&data)
+                .with_context(|| format!("Failed to read font collection {}", path.display()))?;
+        }
+        let face = ttf_parser::Face::parse(&data, 0)
+            .map_err(|e| anyhow::anyhow!("Failed to parse font {}: {}", path.display(), e))?;
+        if face.tables().glyf.is_none() {
+            return Err(anyhow::anyhow!(
+                "Font has no TrueType outlines (CFF-based OpenType fonts are not supported; choose a .ttf or .ttc font): {}",
+                path.display()
+            ));
+        }
+        let units_per_em = face.units_per_em() as f32;
+
+        Ok(Self { data, units_per_em, advances: HashMap::new() })
+    }
+
+    /// 字符的宽度（em），字体中没有该字时返回 None
+    pub fn advance(&mut self, c: char) -> Option<f32> {
+        if let Some(advance) = self.advances.get(&c) {
+            return *advance;
+        }
+
+        let advance = ttf_parser::Face::parse(&self.data, 0).ok().and_then(|face| {
+            let glyph = face.glyph_index(c).filter(|glyph| glyph.0 > 0)?;
+            face.glyph_hor_advance(glyph).map(|width| width as f32 / self.units_per_em)
+        });
+        self.advances.insert(c, advance);
+        advance
+    }
+
+    /// 只保留 `chars` 用到的字形轮廓，返回用于嵌入的字体数据
+    ///
+    /// 字形编号保持不变，其余字形的轮廓清空，`cmap`、`hmtx` 等表原样保留，
+    /// 因此 PDF 中的字宽和编码不受影响。中日韩字体的体积几乎都在轮廓上，
+    /// 完整嵌入会让 PDF 达到十几 MB。
+    pub fn subset(&self, chars: impl IntoIterator<Item = char>) -> Result<Vec<u8>> {
+        let face = ttf_parser::Face::parse(&self.data, 0)
+            .map_err(|e| anyhow::anyhow!("Failed to parse font: {}", e))?;
+        let mut glyphs: BTreeSet<u16> = chars
+            .into_iter()
+            .filter_map(|c| face.glyph_index(c))
+            .map(|glyph| glyph.0)
+            .collect();
+        glyphs.insert(0); // 缺字时显示的 .notdef
+
+        let tables = table_records(&self.data)?;
+        let table = |tag: &[u8; 4]| -> Result<&[u8]> {
+            tables
+                .iter()
+                .find(|record| &record.tag == tag)
+                .and_then(|record| self.data.get(record.offset..record.offset + record.length))
+                .ok_or_else(|| anyhow::anyhow!("Font is missing the {} table", String::from_utf8_lossy(tag)))
+        };
+        let head = table(b"head")?;
+        let glyf = table(b"glyf")?;
+        let loca = table(b"loca")?;
+        let maxp = table(b"maxp")?;
+        let glyph_count = maxp.get(4..6).map(|b| u16::from_be_bytes([b[0], b[1]])).context("Truncated maxp table")? as usize;
+        let long_offsets = head.get(50..52).context("Truncated head table")? != [0, 0];
+
+        let glyph_range = |glyph: usize| -> Option<(usize, usize)> {
+            let (start, end) = if long_offsets {
+                let read = |index: usize| loca.get(index * 4..index * 4 + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+                (read(glyph)?, read(glyph + 1)?)
+            } else {
+                let read = |index: usize| loca.get(index * 2..index * 2 + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize * 2);
+                (read(glyph)?, read(glyph + 1)?)
+            };
+            (start <= end && end <= glyf.len()).then_some((start, end))
+        };
+
+        // 复合字形引用的部件也要保留
+        let mut pending: Vec<u16> = glyphs.iter().copied().collect();
+        while let Some(glyph) = pending.pop() {
+            let Some((start, end)) = glyph_range(glyph as usize) else { continue };
+            for component in composite_components(&glyf[start..end]) {
+                if glyphs.insert(component) {
+                    pending.push(component);
+                }
+            }
+        }
+
+        // 新的 glyf 和长格式 loca
+        let mut new_glyf = Vec::new();
+        let mut new_loca = Vec::with_capacity((glyph_count + 1) * 4);
+        for glyph in 0..glyph_count {
+            new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+            if glyphs.contains(&(glyph as u16)) {
+                if let Some((start, end)) = glyph_range(glyph) {
+                    new_glyf.extend_from_slice(&glyf[start..end]);
+                    new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+                }
+            }
+        }
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+        let mut new_head = head.to_vec();
+        new_head[8..12].copy_from_slice(&[0; 4]); // checkSumAdjustment，嵌入时不需要
+        new_head[50..52].copy_from_slice(&1u16.to_be_bytes());
+
+        let replaced: Vec<([u8; 4], &[u8])> = tables
+            .iter()
+            .map(|record| {
+                let data: &[u8] = match &record.tag {
+                    b"glyf" => &new_glyf,
+                    b"loca" => &new_loca,
+                    b"head" => &new_head,
+                    _ => &self.data[record.offset..record.offset + record.length],
+                };
+                (record.tag, data)
+            })
+            .collect();
+        Ok(write_font(&self.data[..4], &replaced))
+    }
+}
+
+/// 字体表目录中的一项
+struct TableRecord {
+    tag: [u8; 4],
+    offset: usize,
+    length: usize,
+}
+
+/// 读取字体的表目录
+fn table_records(data: &[u8]) -> Result<Vec<TableRecord>> {
+    let truncated = || anyhow::anyhow!("Truncated font");
+    let count = data.get(4..6).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or_else(truncated)? as usize;
+    (0..count)
+        .map(|index| {
+            let record = data.get(12 + index * 16..28 + index * 16).ok_or_else(truncated)?;
+            let offset = u32::from_be_bytes([record[8], record[9], record[10], record[11]]) as usize;
+            let length = u32::from_be_bytes([record[12], record[13], record[14], record[15]]) as usize;
+            if data.len() < offset + length {
+                return Err(truncated());
+            }
+            Ok(TableRecord { tag: [record[0], record[1], record[2], record[3]], offset, length })
+        })
+        .collect()
+}
+
+/// 复合字形引用的部件字形
+fn composite_components(glyph: &[u8]) -> Vec<u16> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    let read_u16 = |offset: usize| glyph.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let mut components = Vec::new();
+    // 轮廓数为负表示复合字形，部件列表从第 10 字节开始
+    if !read_u16(0).is_some_and(|contours| (contours as i16) < 0) {
+        return components;
+    }
+
+    let mut offset = 10;
+    while let (Some(flags), Some(component)) = (read_u16(offset), read_u16(offset + 2)) {
+        components.push(component);
+        offset += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        offset += if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    components
+}
+
+/// 按给定的表写出字体文件，表数据按 4 字节对齐并重新计算校验和
+fn write_font(version: &[u8], tables: &[([u8; 4], &[u8])]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let mut entry_selector = 0u16;
+    while 2u16.pow(entry_selector as u32 + 1) <= count {
+        entry_selector += 1;
+    }
+    let search_range = 2u16.pow(entry_selector as u32) * 16;
+
+    let mut font = version.to_vec();
+    for value in [count, search_range, entry_selector, count * 16 - search_range] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut offset = 12 + tables.len() * 16;
+    let mut body = Vec::new();
+    for (tag, data) in tables {
+        let checksum = data
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0u8; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_be_bytes(word)
+            })
+            .fold(0u32, u32::wrapping_add);
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum.to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+        body.extend_from_slice(data);
+        body.resize(body.len().next_multiple_of(4), 0);
+        offset = 12 + tables.len() * 16 + body.len();
+    }
+    font.extend_from_slice(&body);
+    font
+}
+
+/// 从字体集中取出第一个字体，重新排列表目录和表数据
+fn extract_first_face(collection: &[u8]) -> Result<Vec<u8>> {
+    let read_u16 = |offset: usize| -> Result<u16> {
+        collection
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(|| anyhow::anyhow!("Truncated font collection"))
+    };
+    let read_u32 = |offset: usize| -> Result<u32> {
+        collection
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or_else(|| anyhow::anyhow!("Truncated font collection"))
+    };
+
+    let face_offset = read_u32(12)? as usize;
+    let table_count = read_u16(face_offset + 4)? as usize;
+    let header_len = 12 + table_count * 16;
+
+    let mut header = collection
+        .get(face_offset..face_offset + header_len)
+        .ok_or_else(|| anyhow::anyhow!("Truncated font collection"))?
+        .to_vec();
+    let mut tables = Vec::new();
+
+    for index in 0..table_count {
+        let record = face_offset + 12 + index * 16;
+        let offset = read_u32(record + 8)? as usize;
+        let length = read_u32(record + 12)? as usize;
+        let table = collection
+            .get(offset..offset + length)
+            .ok_or_else(|| anyhow::anyhow!("Truncated font collection"))?;
+
+        let new_offset = (header_len + tables.len()) as u32;
+        header[12 + index * 16 + 8..12 + index * 16 + 12].copy_from_slice(&new_offset.to_be_bytes());
+        tables.extend_from_slice(table);
+        // 每个表按 4 字节对齐
+        tables.resize(tables.len().next_multiple_of(4), 0);
+    }
+
+    header.extend_from_slice(&tables);
+    Ok(header)
+}
+
+/// 系统字体索引（按文件名匹配字体族）
+pub struct FontLibrary {
+    files: Vec<(String, PathBuf)>, // 规范化后的文件名和路径
+}
+
+impl FontLibrary {
+    /// 扫描系统字体目录
+    pub fn scan() -> Self {
+        let mut files = Vec::new();
+        for dir in system_font_dirs() {
+            collect_font_files(&dir, 0, &mut files);
+        }
+        Self { files }
+    }
+
+    /// 按 CSS `font-family` 列表查找字体，依次尝试直到能加载
+    pub fn load_stack(&self, font_family: &str, style: FontStyle) -> Option<Font> {
+        let families: Vec<String> = font_family
+            .split(',')
+            .map(|family| family.trim().trim_matches(|c| c == '\'' || c == '"').to_string())
+            .filter(|family| !family.is_empty())
+            .collect();
+
+        families
+            .iter()
+            .flat_map(|family| match family.to_ascii_lowercase().as_str() {
+                "serif" => SERIF.iter().map(|name| name.to_string()).collect(),
+                "sans-serif" | "system-ui" | "-apple-system" => SANS_SERIF.iter().map(|name| name.to_string()).collect(),
+                "monospace" => MONOSPACE.iter().map(|name| name.to_string()).collect(),
+                _ => vec![family.clone()],
+            })
+            .find_map(|family| self.load_family(&family, style))
+    }
+
+    /// 加载能显示中日韩文字的后备字体
+    pub fn load_cjk_fallback(&self) -> Option<Font> {
+        CJK_FALLBACKS.iter().find_map(|family| {
+            let mut font = self.load_family(family, FontStyle::Regular)?;
+            font.advance('中').is_some().then_some(font)
+        })
+    }
+
+    /// 加载最后的兜底字体
+    pub fn load_any(&self) -> Option<Font> {
+        SERIF
+            .iter()
+            .chain(SANS_SERIF)
+            .find_map(|family| self.load_family(family, FontStyle::Regular))
+    }
+
+    /// 加载指定字体族的某个样式
+    fn load_family(&self, family: &str, style: FontStyle) -> Option<Font> {
+        self.candidates(family, style)
+            .into_iter()
+            .find_map(|path| Font::load(path).ok())
+    }
+
+    /// 按匹配程度排序的候选文件
+    fn candidates(&self, family: &str, style: FontStyle) -> Vec<&Path> {
+        let key = normalize(family);
+        if key.is_empty() {
+            return vec![];
+        }
+
+        let mut matches: Vec<(usize, &Path)> = self
+            .files
+            .iter()
+            .filter_map(|(name, path)| {
+                let rest = name.strip_prefix(&key)?;
+                // 避免 “DejaVu Sans” 匹配到 “DejaVu Sans Mono”
+                let other_family = rest.contains("mono") || rest.contains("serif") || rest.contains("sans");
+                (!other_family && style_matches(rest, style)).then_some((rest.len(), path.as_path()))
+            })
+            .collect();
+        matches.sort_by_key(|(rest, _)| *rest);
+        matches.into_iter().map(|(_, path)| path).collect()
+    }
+}
+
+/// 文件名剩余部分是否符合所需样式
+fn style_matches(rest: &str, style: FontStyle) -> bool {
+    const OTHER_WEIGHTS: &[&str] = &["light", "thin", "black", "heavy", "medium", "semibold", "extrabold", "demi", "condensed", "narrow", "extralight"];
+    if OTHER_WEIGHTS.iter().any(|weight| rest.contains(weight)) {
+        return false;
+    }
+
+    // Windows 字体文件的简写后缀，如 timesbd.ttf、ariali.ttf
+    let (bold, italic) = match rest {
+        "b" | "bd" => (true, false),
+        "i" => (false, true),
+        "bi" | "z" => (true, true),
+        _ => (
+            rest.contains("bold"),
+            rest.contains("italic") || rest.contains("oblique"),
+        ),
+    };
+    FontStyle::from_flags(bold, italic) == style
+}
+
+/// 规范化字体名：小写并去掉非字母数字
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// 递归收集字体文件
+fn collect_font_files(dir: &Path, depth: usize, files: &mut Vec<(String, PathBuf)>) {
+    if depth > MAX_SCAN_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_font_files(&path, depth + 1, files);
+            continue;
+        }
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase());
+        if matches!(extension.as_deref(), Some("ttf" | "ttc" | "otf")) {
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                files.push((normalize(stem), path));
+            }
+        }
+    }
+}
+
+/// 各平台的系统字体目录
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(target_os = "windows") {
+        let windows = std::env::var_os("WINDIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+        dirs.push(windows.join("Fonts"));
+        if let Some(local) = dirs::data_local_dir() {
+            dirs.push(local.join("Microsoft").join("Windows").join("Fonts"));
+        }
+    } else if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/System/Library/Fonts"));
+        dirs.push(PathBuf::from("/Library/Fonts"));
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(home.join("Library").join("Fonts"));
+        }
+    } else {
+        dirs.push(PathBuf::from("/usr/share/fonts"));
+        dirs.push(PathBuf::from("/usr/local/share/fonts"));
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(home.join(".local").join("share").join("fonts"));
+            dirs.push(home.join(".fonts"));
+        }
+    }
+    dirs
+}
+
+/// 文本是否需要中日韩字体
+pub fn needs_cjk(content: &str) -> bool {
+    content.chars().any(text::is_cjk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn style_matching_uses_file_name_suffix() {
+        assert!(style_matches("", FontStyle::Regular));
+        assert!(style_matches("regular", FontStyle::Regular));
+        assert!(style_matches("bold", FontStyle::Bold));
+        assert!(style_matches("bolditalic", FontStyle::BoldItalic));
+        assert!(style_matches("oblique", FontStyle::Italic));
+        assert!(!style_matches("condensedbold", FontStyle::Bold));
+        assert!(!style_matches("bold", FontStyle::Regular));
+        assert!(style_matches("bd", FontStyle::Bold));
+        assert_eq!(normalize("'Times New Roman'"), "timesnewroman");
+    }
+
+    #[test]
+    fn extracts_first_face_from_collection() {
+        // 只有一个 4 字节表的字体集：文件头、一个字体的偏移、表目录、表数据
+        let mut collection = b"ttcf".to_vec();
+        collection.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        collection.extend_from_slice(&1u32.to_be_bytes());
+        collection.extend_from_slice(&16u32.to_be_bytes());
+        collection.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        collection.extend_from_slice(&[0, 1, 0, 16, 0, 0, 0, 0]);
+        collection.extend_from_slice(b"test");
+        collection.extend_from_slice(&0u32.to_be_bytes());
+        collection.extend_from_slice(&44u32.to_be_bytes());
+        collection.extend_from_slice(&3u32.to_be_bytes());
+        collection.extend_from_slice(b"abc\0");
+
+        let face = extract_first_face(&collection).unwrap();
+        assert_eq!(&face[..4], &0x0001_0000u32.to_be_bytes());
+        assert_eq!(&face[20..24], &28u32.to_be_bytes()); // 新偏移紧跟表目录
+        assert_eq!(&face[28..], b"abc\0");
+    }
+
+    #[test]
+    fn subset_keeps_only_used_outlines() {
+        let Some(mut font) = FontLibrary::scan().load_any() else {
+            return; // 测试环境没有可用字体
+        };
+        let subset = font.subset("Night".chars()).unwrap();
+        assert!(subset.len() < font.data.len() / 2, "{} >= {}", subset.len(), font.data.len());
+
+        // 字形编号和字宽不变，用到的字形仍有轮廓，其余字形被清空
+        let original = ttf_parser::Face::parse(&font.data, 0).unwrap();
+        let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        assert_eq!(face.number_of_glyphs(), original.number_of_glyphs());
+        let glyph = face.glyph_index('N').unwrap();
+        assert_eq!(Some(glyph), original.glyph_index('N'));
+        assert_eq!(face.glyph_hor_advance(glyph), original.glyph_hor_advance(glyph));
+        assert!(face.glyph_bounding_box(glyph).is_some());
+        assert!(face.glyph_bounding_box(face.glyph_index('Q').unwrap()).is_none());
+        assert!(font.advance('Q').is_some());
+    }
+}
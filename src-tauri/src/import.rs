@@ -0,0 +1,524 @@
+use anyhow::{Context, Result};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use quick_xml::escape::resolve_html5_entity;
+use quick_xml::events::{BytesStart, BytesText};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{Read, Seek};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::export;
+use crate::file_system::DocumentType;
+use crate::{import_docx, import_epub};
+
+/// 自动识别时尝试的章节标题格式
+const CHAPTER_PATTERNS: &[&str] = &[
+    r"^第[0-9０-９零〇一二三四五六七八九十百千万两]+[章回节卷部]",
+    r"^(?i:chapter)\s+([0-9]+|[ivxlc]+|[a-z]+)\b",
+];
+
+/// 单个文件的拆分方式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SplitRule {
+    /// 先按出现两次以上的最高级标题拆分（更高级的单个标题视为书名），其次按常见的章节标题格式
+    Auto,
+    /// 按不低于 `max_level` 级的 Markdown 标题拆分
+    Heading { max_level: u8 },
+    /// 按匹配正则表达式的行拆分，匹配行作为章节标题
+    Pattern { pattern: String },
+    /// 整个文件作为一篇文档
+    None,
+}
+
+/// 导入选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    pub name: Option<String>, // 书名，默认取文件中的书名标题或文件名
+    pub description: String,
+    pub author: String,
+    pub genre: String,
+    pub split: SplitRule, // EPUB 总是按书脊拆分
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            name: None,
+            description: String::new(),
+            author: String::new(),
+            genre: String::new(),
+            split: SplitRule::Auto,
+        }
+    }
+}
+
+/// 导入得到的一篇文档
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedDocument {
+    pub title: String,
+    pub doc_type: DocumentType, // 章节或开篇前的小节
+    pub content: String,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Default)]
+pub struct ImportedBook {
+    pub title: String, // 为空时使用文件名
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub documents: Vec<ImportedDocument>,
+}
+
+/// 读取要导入的文件或文件夹
+pub fn read_source(path: &Path, options: &ImportOptions) -> Result<ImportedBook> {
+    let mut book = read_book(path, options)?;
+    if book.documents.is_empty() {
+        return Err(anyhow::anyhow!("No text found in {}", path.display()));
+    }
+    if book.title.trim().is_empty() {
+        book.title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+    }
+    Ok(book)
+}
+
+fn read_book(path: &Path, options: &ImportOptions) -> Result<ImportedBook> {
+    if path.is_dir() {
+        let files = manuscript_files(path)?;
+        if files.is_empty() {
+            return Err(anyhow::anyhow!("No .md or .txt files found in {}", path.display()));
+        }
+
+        let documents = files
+            .iter()
+            .map(|file| {
+                let text = read_text(file)?;
+                let (heading, content) = leading_heading(&text);
+                let title = heading.unwrap_or_else(|| title_from_file_name(file));
+                Ok(ImportedDocument { title, doc_type: DocumentType::Chapter, content })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(ImportedBook { documents, ..ImportedBook::default() });
+    }
+
+    let extension = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("docx") => import_docx::read(path, &options.split),
+        Some("epub") => import_epub::read(path),
+        _ if is_manuscript_file(path) => {
+            let text = read_text(path)?;
+            let (title, documents) = split(&text, &options.split)?;
+            Ok(ImportedBook { title: title.unwrap_or_default(), documents, ..ImportedBook::default() })
+        }
+        _ => Err(anyhow::anyhow!("Unsupported manuscript file: {}", path.display())),
+    }
+}
+
+/// 拆分单个文件，返回识别到的书名标题和文档列表
+pub fn split(text: &str, rule: &SplitRule) -> Result<(Option<String>, Vec<ImportedDocument>)> {
+    let language = export::detect_language(&[text]);
+    let breaks = match rule {
+        SplitRule::Auto => {
+            let headings = headings(text);
+            match auto_heading_level(&headings) {
+                Some(level) => heading_breaks(&headings, level..=level),
+                None => CHAPTER_PATTERNS
+                    .iter()
+                    .map(|pattern| pattern_breaks(text, &Regex::new(pattern).expect("valid chapter pattern")))
+                    .find(|breaks| breaks.len() >= 2)
+                    .unwrap_or_default(),
+            }
+        }
+        SplitRule::Heading { max_level } => heading_breaks(&headings(text), 1..=(*max_level).clamp(1, 6)),
+        SplitRule::Pattern { pattern } => {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("Invalid split pattern: {}", pattern))?;
+            pattern_breaks(text, &regex)
+        }
+        SplitRule::None => Vec::new(),
+    };
+
+    if breaks.is_empty() {
+        let (heading, content) = leading_heading(text);
+        let title = heading.clone().unwrap_or_else(|| numbered_title(1, language));
+        return Ok((heading, vec![ImportedDocument { title, doc_type: DocumentType::Chapter, content }]));
+    }
+
+    let mut book_title = None;
+    let mut documents = Vec::new();
+
+    // 第一个拆分点之前的内容：只有一个标题时作为书名，否则作为前言
+    let preface = &text[..breaks[0].line.start];
+    let (heading, content) = leading_heading(preface);
+    if content.is_empty() {
+        book_title = heading;
+    } else {
+        let title = heading.unwrap_or_else(|| preface_title(language).to_string());
+        documents.push(ImportedDocument { title, doc_type: DocumentType::Section, content });
+    }
+
+    for (index, current) in breaks.iter().enumerate() {
+        let end = breaks.get(index + 1).map_or(text.len(), |next| next.line.start);
+        let chapter_number = documents.iter().filter(|document| document.doc_type == DocumentType::Chapter).count() + 1;
+        let title = current.title.clone().unwrap_or_else(|| numbered_title(chapter_number, language));
+        documents.push(ImportedDocument {
+            title,
+            doc_type: DocumentType::Chapter,
+            content: text[current.line.end..end].trim_matches('\n').to_string(),
+        });
+    }
+
+    Ok((book_title, documents))
+}
+
+/// 拆分点：标题所在的行和标题文字
+#[derive(Debug)]
+struct Break {
+    line: Range<usize>,
+    title: Option<String>,
+}
+
+/// 文本中的 Markdown 标题（代码块中的除外）
+fn headings(text: &str) -> Vec<(u8, Break)> {
+    let mut headings = Vec::new();
+    let mut current: Option<(u8, Range<usize>, String)> = None;
+
+    for (event, range) in Parser::new(text).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some((heading_level(level), range, String::new()));
+            }
+            Event::Text(content) | Event::Code(content) => {
+                if let Some((_, _, title)) = current.as_mut() {
+                    title.push_str(&content);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, range, title)) = current.take() {
+                    let title = title.trim().to_string();
+                    let title = (!title.is_empty()).then_some(title);
+                    headings.push((level, Break { line: line_range(text, range), title }));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    headings
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// 出现两次以上的最高级标题
+fn auto_heading_level(headings: &[(u8, Break)]) -> Option<u8> {
+    (1..=6).find(|level| headings.iter().filter(|(heading, _)| heading == level).count() >= 2)
+}
+
+fn heading_breaks(headings: &[(u8, Break)], levels: RangeInclusive<u8>) -> Vec<Break> {
+    headings
+        .iter()
+        .filter(|(level, _)| levels.contains(level))
+        .map(|(_, heading)| Break { line: heading.line.clone(), title: heading.title.clone() })
+        .collect()
+}
+
+/// 按正则表达式匹配的行拆分，匹配行没有文字时（如 `***`）使用编号标题
+fn pattern_breaks(text: &str, regex: &Regex) -> Vec<Break> {
+    let mut breaks = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim();
+        if !content.is_empty() && regex.is_match(content) {
+            let title = content.trim_start_matches('#').trim();
+            let title = title.chars().any(char::is_alphanumeric).then(|| title.to_string());
+            breaks.push(Break { line: start..start + line.len(), title });
+        }
+        start += line.len();
+    }
+    breaks
+}
+
+/// 把范围扩展到完整的行
+fn line_range(text: &str, range: Range<usize>) -> Range<usize> {
+    let start = text[..range.start].rfind('\n').map_or(0, |index| index + 1);
+    let end = text[range.end..].find('\n').map_or(text.len(), |index| range.end + index + 1);
+    // 标题范围可能已经包含行尾换行
+    let end = if range.end > 0 && text.as_bytes()[range.end - 1] == b'\n' { range.end } else { end };
+    start..end
+}
+
+/// 取出开头的标题，返回标题和去掉标题后的正文
+pub fn leading_heading(text: &str) -> (Option<String>, String) {
+    match headings(text).into_iter().next() {
+        Some((_, heading)) if text[..heading.line.start].trim().is_empty() => {
+            (heading.title, text[heading.line.end..].trim_matches('\n').to_string())
+        }
+        _ => (None, text.trim_matches('\n').to_string()),
+    }
+}
+
+pub fn numbered_title(number: usize, language: &str) -> String {
+    match language {
+        "zh" | "ja" => format!("第{}章", number),
+        _ => format!("Chapter {}", number),
+    }
+}
+
+fn preface_title(language: &str) -> &'static str {
+    match language {
+        "zh" | "ja" => "前言",
+        _ => "Front Matter",
+    }
+}
+
+fn is_manuscript_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase());
+    matches!(extension.as_deref(), Some("md" | "markdown" | "txt"))
+}
+
+/// 文件夹中的稿件文件，按文件名自然排序（chapter2 在 chapter10 之前）
+fn manuscript_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read folder {}", dir.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_manuscript_file(path))
+        .filter(|path| !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')))
+        .collect();
+
+    files.sort_by(|a, b| natural_cmp(&a.file_name().unwrap_or_default().to_string_lossy(), &b.file_name().unwrap_or_default().to_string_lossy()));
+    Ok(files)
+}
+
+/// 自然排序：数字部分按数值比较
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// 由文件名得到标题，去掉开头的编号（如 `01 - 启程.md`）
+fn title_from_file_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let title = stem
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start_matches(|c: char| c == '-' || c == '_' || c == '.' || c.is_whitespace());
+    if title.is_empty() { stem } else { title.to_string() }
+}
+
+/// 读取文本文件：识别编码，统一换行符，纯文本的单行段落转为 Markdown 段落
+fn read_text(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let text = decode(&bytes).replace("\r\n", "\n").replace('\r', "\n");
+
+    let is_plain_text = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt"));
+    Ok(if is_plain_text { paragraphs_from_lines(&text) } else { text })
+}
+
+/// 按 BOM 或内容猜测编码（常见于 GBK、Big5、Shift_JIS 的旧稿件）
+fn decode(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_length..]).0.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// 读取压缩包中的文本文件，不存在时返回 None
+pub fn read_zip_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {}", name)),
+    };
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to read {}", name))?;
+    Ok(Some(decode(&bytes)))
+}
+
+/// 按本地名（不含命名空间前缀）读取 XML 属性
+pub fn xml_attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attribute| attribute.key.local_name().as_ref() == name.as_bytes())
+        .map(|attribute| {
+            attribute
+                .unescape_value_with(resolve_html5_entity)
+                .map(|value| value.into_owned())
+                .unwrap_or_else(|_| String::from_utf8_lossy(&attribute.value).into_owned())
+        })
+}
+
+/// 解码 XML 文本，支持 HTML 命名实体（如 `&nbsp;`）
+pub fn xml_text(text: &BytesText) -> String {
+    text.unescape_with(resolve_html5_entity)
+        .map(|text| text.into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(text).into_owned())
+}
+
+/// 转义行内会被当作 Markdown 语法的字符
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 转义段首会被当作标题、引用或列表的字符
+pub fn escape_block_start(paragraph: &str) -> String {
+    let trimmed = paragraph.trim_start();
+    let ordered_list = trimmed
+        .split_once(['.', ')'])
+        .is_some_and(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()));
+
+    if trimmed.starts_with(['#', '>', '-', '+', '=']) {
+        format!("\\{}", trimmed)
+    } else if ordered_list {
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        format!("{}\\{}", &trimmed[..digits], &trimmed[digits..])
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// 纯文本通常一行一段且没有空行，转成以空行分隔的段落
+fn paragraphs_from_lines(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let blank_lines = lines.iter().filter(|line| line.trim().is_empty()).count();
+    if blank_lines * 3 >= lines.len() {
+        return text.to_string();
+    }
+
+    lines
+        .iter()
+        .map(|line| line.trim_start_matches(['\u{3000}', ' ', '\t']).trim_end())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(documents: &[ImportedDocument]) -> Vec<&str> {
+        documents.iter().map(|document| document.title.as_str()).collect()
+    }
+
+    #[test]
+    fn auto_split_uses_repeated_heading_level() {
+        let text = "# 雨夜\n\n## 第一章 启程\n\n雨停了。\n\n### 小节\n\n```\n## 不是标题\n```\n\n## 第二章\n\n风起了。\n";
+        let (book_title, documents) = split(text, &SplitRule::Auto).unwrap();
+
+        assert_eq!(book_title.as_deref(), Some("雨夜"));
+        assert_eq!(titles(&documents), vec!["第一章 启程", "第二章"]);
+        assert!(documents[0].content.starts_with("雨停了。"));
+        assert!(documents[0].content.contains("### 小节"));
+        assert!(documents[0].content.contains("## 不是标题"));
+        assert_eq!(documents[1].content, "风起了。");
+    }
+
+    #[test]
+    fn auto_split_recognizes_chapter_lines_and_keeps_front_matter() {
+        let text = "献给读者。\n\n第一章 开始\n\n正文一。\n\n第二章 继续\n\n正文二。";
+        let (book_title, documents) = split(text, &SplitRule::Auto).unwrap();
+
+        assert_eq!(book_title, None);
+        assert_eq!(titles(&documents), vec!["前言", "第一章 开始", "第二章 继续"]);
+        assert_eq!(documents[0].doc_type, DocumentType::Section);
+        assert_eq!(documents[2].content, "正文二。");
+    }
+
+    #[test]
+    fn separator_pattern_uses_numbered_titles() {
+        let text = "One.\n\n***\n\nTwo.\n\n***\n\nThree.\n";
+        let rule = SplitRule::Pattern { pattern: r"^\*\*\*$".to_string() };
+        let (_, documents) = split(text, &rule).unwrap();
+
+        assert_eq!(titles(&documents), vec!["Front Matter", "Chapter 1", "Chapter 2"]);
+        assert_eq!(documents[1].content, "Two.");
+
+        let rule = SplitRule::Pattern { pattern: "(".to_string() };
+        assert!(split(text, &rule).is_err());
+    }
+
+    #[test]
+    fn reads_folder_in_natural_order_and_plain_text_paragraphs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("chapter10.md"), "# 结局\n\n完。").unwrap();
+        fs::write(dir.path().join("chapter2.txt"), "　　第一段。\n　　第二段。\n").unwrap();
+        fs::write(dir.path().join("notes.json"), "{}").unwrap();
+        let (gbk, _, _) = encoding_rs::GBK.encode("中文内容。");
+        fs::write(dir.path().join("chapter1.txt"), &gbk).unwrap();
+
+        let book = read_source(dir.path(), &ImportOptions::default()).unwrap();
+        assert_eq!(titles(&book.documents), vec!["chapter1", "chapter2", "结局"]);
+        assert_eq!(book.documents[0].content, "中文内容。");
+        assert_eq!(book.documents[1].content, "第一段。\n\n第二段。");
+        assert_eq!(book.documents[2].content, "完。");
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::import::{self, ImportedBook, SplitRule};
+
+/// 被当作场景分隔符的段落文字
+const SCENE_BREAKS: &[&str] = &["#", "*", "***", "* * *", "＊＊＊", "◇", "◇◇◇"];
+
+/// 段落样式的含义
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StyleKind {
+    Body,
+    Title,
+    Heading(u8),
+    Quote,
+    List,
+    Code,
+    SceneBreak,
+    Skip, // 标题页上的作者、字数等信息
+}
+
+/// styles.xml 中的段落样式
+#[derive(Debug, Default)]
+struct StyleInfo {
+    name: String,
+    based_on: Option<String>,
+    outline_level: Option<u8>,
+}
+
+/// 读取 Word 文档，按标题拆分为多篇文档
+pub fn read(path: &Path, rule: &SplitRule) -> Result<ImportedBook> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut archive = ZipArchive::new(file).context("Not a valid DOCX file")?;
+
+    let document = import::read_zip_entry(&mut archive, "word/document.xml")?
+        .context("DOCX file has no word/document.xml")?;
+    let styles = match import::read_zip_entry(&mut archive, "word/styles.xml")? {
+        Some(xml) => parse_styles(&xml)?,
+        None => HashMap::new(),
+    };
+    let properties = match import::read_zip_entry(&mut archive, "docProps/core.xml")? {
+        Some(xml) => parse_core_properties(&xml)?,
+        None => HashMap::new(),
+    };
+
+    let (markdown, title_paragraph) = document_markdown(&document, &styles)?;
+    let (heading_title, documents) = import::split(&markdown, rule)?;
+
+    let property = |name: &str| properties.get(name).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+    Ok(ImportedBook {
+        title: property("title").or(title_paragraph).or(heading_title).unwrap_or_default(),
+        author: property("creator"),
+        description: property("description"),
+        documents,
+    })
+}
+
+/// 读取 docProps/core.xml 中的 Dublin Core 元数据
+fn parse_core_properties(xml: &str) -> Result<HashMap<String, String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut properties = HashMap::new();
+    let mut current: Option<String> = None;
+
+    loop {
+        match reader.read_event().context("Failed to parse DOCX properties")? {
+            Event::Start(element) => current = Some(local_name(element.local_name().as_ref())),
+            Event::Text(text) => {
+                if let Some(name) = &current {
+                    properties.entry(name.clone()).or_insert_with(String::new).push_str(&import::xml_text(&text));
+                }
+            }
+            Event::End(_) => current = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(properties)
+}
+
+/// 读取段落样式表
+fn parse_styles(xml: &str) -> Result<HashMap<String, StyleInfo>> {
+    let mut reader = Reader::from_str(xml);
+    let mut styles = HashMap::new();
+    let mut current: Option<(String, StyleInfo)> = None;
+
+    loop {
+        match reader.read_event().context("Failed to parse DOCX styles")? {
+            Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+                b"style" => {
+                    let is_paragraph = import::xml_attribute(&element, "type").as_deref() == Some("paragraph");
+                    current = import::xml_attribute(&element, "styleId")
+                        .filter(|_| is_paragraph)
+                        .map(|id| (id, StyleInfo::default()));
+                }
+                b"name" => {
+                    if let Some((_, style)) = current.as_mut() {
+                        style.name = import::xml_attribute(&element, "val").unwrap_or_default();
+                    }
+                }
+                b"basedOn" => {
+                    if let Some((_, style)) = current.as_mut() {
+                        style.based_on = import::xml_attribute(&element, "val");
+                    }
+                }
+                b"outlineLvl" => {
+                    if let Some((_, style)) = current.as_mut() {
+                        style.outline_level = import::xml_attribute(&element, "val").and_then(|level| level.parse().ok());
+                    }
+                }
+                _ => {}
+            },
+            Event::End(element) if element.local_name().as_ref() == b"style" => {
+                if let Some((id, style)) = current.take() {
+                    styles.insert(id, style);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(styles)
+}
+
+/// 由样式 ID 判断段落类型，未识别时沿 basedOn 向上查找
+fn style_kind(styles: &HashMap<String, StyleInfo>, style_id: &str) -> StyleKind {
+    let mut id = style_id.to_string();
+    for _ in 0..10 {
+        let style = styles.get(&id);
+        let name = style.map_or(id.as_str(), |style| style.name.as_str());
+        if let Some(kind) = kind_from_name(name).or_else(|| kind_from_name(&id)) {
+            return kind;
+        }
+        if let Some(level) = style.and_then(|style| style.outline_level).filter(|level| *level < 6) {
+            return StyleKind::Heading(level + 1);
+        }
+        match style.and_then(|style| style.based_on.clone()) {
+            Some(parent) => id = parent,
+            None => break,
+        }
+    }
+    StyleKind::Body
+}
+
+fn kind_from_name(name: &str) -> Option<StyleKind> {
+    let name: String = name.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(level) = name.strip_prefix("heading").and_then(|level| level.parse::<u8>().ok()) {
+        return Some(StyleKind::Heading(level.clamp(1, 6)));
+    }
+
+    match name.as_str() {
+        "title" => Some(StyleKind::Title),
+        "subtitle" | "byline" | "titlepageinfo" => Some(StyleKind::Skip),
+        "scenebreak" => Some(StyleKind::SceneBreak),
+        "code" | "htmlpreformatted" | "plaintext" | "sourcecode" => Some(StyleKind::Code),
+        _ if name.contains("quote") => Some(StyleKind::Quote),
+        _ if name.starts_with("list") => Some(StyleKind::List),
+        _ => None,
+    }
+}
+
+fn local_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+/// 一段格式相同的文字
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Run {
+    text: String,
+    bold: bool,
+    italic: bool,
+    strike: bool,
+}
+
+/// 正在读取的段落
+#[derive(Debug, Default)]
+struct Paragraph {
+    style: Option<String>,
+    outline_level: Option<u8>,
+    numbered: bool,
+    runs: Vec<Run>,
+}
+
+impl Paragraph {
+    fn push(&mut self, text: &str, format: &Run) {
+        match self.runs.last_mut() {
+            Some(run) if (run.bold, run.italic, run.strike) == (format.bold, format.italic, format.strike) => {
+                run.text.push_str(text);
+            }
+            _ => self.runs.push(Run { text: text.to_string(), ..format.clone() }),
+        }
+    }
+
+    fn plain_text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+
+    /// 转为带强调标记的 Markdown 行内文字
+    fn markdown(&self) -> String {
+        self.runs
+            .iter()
+            .map(|run| {
+                run.text
+                    .split('\n')
+                    .map(|line| emphasize(&import::escape_markdown(line), run))
+                    .collect::<Vec<_>>()
+                    .join("\\\n")
+            })
+            .collect()
+    }
+}
+
+/// 给文字加上强调标记，首尾空白放在标记外
+fn emphasize(text: &str, run: &Run) -> String {
+    let core = text.trim();
+    let marker = match (run.bold, run.italic) {
+        (true, true) => "***",
+        (true, false) => "**",
+        (false, true) => "*",
+        (false, false) => "",
+    };
+    let strike = if run.strike { "~~" } else { "" };
+    if core.is_empty() || (marker.is_empty() && strike.is_empty()) {
+        return text.to_string();
+    }
+
+    let leading = &text[..text.len() - text.trim_start().len()];
+    let trailing = &text[text.trim_end().len()..];
+    format!("{leading}{strike}{marker}{core}{marker}{strike}{trailing}")
+}
+
+/// 把 document.xml 转为 Markdown，返回正文和 Title 样式段落的文字
+fn document_markdown(xml: &str, styles: &HashMap<String, StyleInfo>) -> Result<(String, Option<String>)> {
+    let mut reader = Reader::from_str(xml);
+    let mut blocks: Vec<String> = Vec::new();
+    let mut code_lines: Vec<String> = Vec::new();
+    let mut title = None;
+
+    let mut paragraph: Option<Paragraph> = None;
+    let mut format = Run::default();
+    let mut in_run_properties = false;
+    let mut in_text = false;
+    let mut skip_depth = 0usize; // 已删除的修订、脚注引用等不需要的内容
+
+    loop {
+        let event = reader.read_event().context("Failed to parse DOCX document")?;
+        if skip_depth > 0 {
+            match event {
+                Event::Start(_) => skip_depth += 1,
+                Event::End(_) => skip_depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            // 空的容器元素没有结束标签，不能改变状态
+            Event::Empty(element) if is_container(element.local_name().as_ref()) => {}
+            Event::Start(element) | Event::Empty(element) => {
+                let name = element.local_name();
+                match name.as_ref() {
+                    b"p" => paragraph = Some(Paragraph::default()),
+                    b"del" | b"moveFrom" | b"instrText" | b"delText" => skip_depth = 1,
+                    b"r" => format = Run::default(),
+                    b"rPr" => in_run_properties = true,
+                    b"b" | b"i" | b"strike" | b"dstrike" if in_run_properties => {
+                        let on = !matches!(import::xml_attribute(&element, "val").as_deref(), Some("0" | "false" | "none"));
+                        match name.as_ref() {
+                            b"b" => format.bold = on,
+                            b"i" => format.italic = on,
+                            _ => format.strike = on,
+                        }
+                    }
+                    b"pStyle" => {
+                        if let Some(paragraph) = paragraph.as_mut() {
+                            paragraph.style = import::xml_attribute(&element, "val");
+                        }
+                    }
+                    b"outlineLvl" if !in_run_properties => {
+                        if let Some(paragraph) = paragraph.as_mut() {
+                            paragraph.outline_level = import::xml_attribute(&element, "val").and_then(|level| level.parse().ok());
+                        }
+                    }
+                    b"numPr" => {
+                        if let Some(paragraph) = paragraph.as_mut() {
+                            paragraph.numbered = true;
+                        }
+                    }
+                    b"t" => in_text = true,
+                    b"tab" if !in_run_properties => {
+                        if let Some(paragraph) = paragraph.as_mut() {
+                            paragraph.push(" ", &format);
+                        }
+                    }
+                    b"br" | b"cr" => {
+                        // 分页符不需要保留
+                        let page_break = import::xml_attribute(&element, "type").as_deref() == Some("page");
+                        if let (Some(paragraph), false) = (paragraph.as_mut(), page_break) {
+                            paragraph.push("\n", &format);
+                        }
+                    }
+                    b"noBreakHyphen" => {
+                        if let Some(paragraph) = paragraph.as_mut() {
+                            paragraph.push("-", &format);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(text) if in_text => {
+                if let Some(paragraph) = paragraph.as_mut() {
+                    paragraph.push(&import::xml_text(&text), &format);
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"rPr" => in_run_properties = false,
+                b"p" => {
+                    if let Some(paragraph) = paragraph.take() {
+                        emit_paragraph(paragraph, styles, &mut blocks, &mut code_lines, &mut title);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    flush_code(&mut blocks, &mut code_lines);
+    Ok((blocks.join("\n\n"), title))
+}
+
+fn is_container(name: &[u8]) -> bool {
+    matches!(name, b"p" | b"rPr" | b"t" | b"del" | b"moveFrom" | b"instrText" | b"delText")
+}
+
+/// 按段落样式输出 Markdown 块
+fn emit_paragraph(
+    paragraph: Paragraph,
+    styles: &HashMap<String, StyleInfo>,
+    blocks: &mut Vec<String>,
+    code_lines: &mut Vec<String>,
+    title: &mut Option<String>,
+) {
+    let mut kind = paragraph
+        .style
+        .as_deref()
+        .map_or(StyleKind::Body, |style| style_kind(styles, style));
+    if let Some(level) = paragraph.outline_level.filter(|level| *level < 6) {
+        if kind == StyleKind::Body {
+            kind = StyleKind::Heading(level + 1);
+        }
+    }
+    if paragraph.numbered && kind == StyleKind::Body {
+        kind = StyleKind::List;
+    }
+
+    let plain = paragraph.plain_text();
+    if kind == StyleKind::Code {
+        code_lines.extend(plain.split('\n').map(str::to_string));
+        return;
+    }
+    flush_code(blocks, code_lines);
+
+    let trimmed = plain.trim();
+    if trimmed.is_empty() {
+        return;
+    }
+    if SCENE_BREAKS.contains(&trimmed) {
+        kind = StyleKind::SceneBreak;
+    }
+
+    match kind {
+        StyleKind::Title => {
+            if title.is_none() {
+                *title = Some(trimmed.to_string());
+            }
+        }
+        StyleKind::Skip => {}
+        StyleKind::Heading(level) => {
+            let heading = import::escape_markdown(&trimmed.replace('\n', " "));
+            blocks.push(format!("{} {}", "#".repeat(level as usize), heading));
+        }
+        StyleKind::SceneBreak => blocks.push("---".to_string()),
+        StyleKind::Quote => blocks.push(format!("> {}", import::escape_block_start(paragraph.markdown().trim()))),
+        StyleKind::List => blocks.push(format!("- {}", import::escape_block_start(paragraph.markdown().trim()))),
+        StyleKind::Body | StyleKind::Code => blocks.push(import::escape_block_start(paragraph.markdown().trim())),
+    }
+}
+
+/// 连续的代码段落合成一个代码块
+fn flush_code(blocks: &mut Vec<String>, code_lines: &mut Vec<String>) {
+    if !code_lines.is_empty() {
+        blocks.push(format!("```\n{}\n```", code_lines.join("\n")));
+        code_lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docx::{self, ManuscriptOptions};
+    use crate::export::Chapter;
+    use crate::file_system::BookConfig;
+
+    #[test]
+    fn converts_formatting_and_styles_to_markdown() {
+        let styles = parse_styles(
+            r#"<w:styles xmlns:w="w"><w:style w:type="paragraph" w:styleId="1"><w:name w:val="heading 1"/></w:style>
+            <w:style w:type="paragraph" w:styleId="MyHeading"><w:name w:val="Custom"/><w:basedOn w:val="1"/></w:style></w:styles>"#,
+        )
+        .unwrap();
+        let document = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>第一章</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Plain </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">bold </w:t></w:r><w:r><w:rPr><w:i/></w:rPr><w:t>it*</w:t></w:r><w:del><w:r><w:t>gone</w:t></w:r></w:del></w:p>
+            <w:p><w:r><w:t>* * *</w:t></w:r></w:p>
+            <w:p><w:pPr><w:pStyle w:val="MyHeading"/></w:pPr><w:r><w:t>第二章</w:t></w:r></w:p>
+            <w:p><w:r><w:t>- not a list</w:t><w:br/><w:t>next line</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+
+        let (markdown, title) = document_markdown(document, &styles).unwrap();
+        assert_eq!(title, None);
+        assert_eq!(markdown, "# 第一章\n\nPlain **bold** *it\\**\n\n---\n\n# 第二章\n\n\\- not a list\\\nnext line");
+    }
+
+    #[test]
+    fn reads_exported_manuscript_back() {
+        let config = BookConfig::sample("The Long Night", "Jane Roe");
+        let chapters = vec![
+            Chapter { title: "One".into(), content: "It was *dark*.\n\n> A quote.".into() },
+            Chapter { title: "Two".into(), content: "Dawn came.".into() },
+        ];
+        let bytes = docx::build(&config, &chapters, &ManuscriptOptions::default()).unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("manuscript.docx");
+        std::fs::write(&path, bytes).unwrap();
+
+        let book = read(&path, &SplitRule::Auto).unwrap();
+        assert_eq!(book.title, "The Long Night");
+        assert_eq!(book.author.as_deref(), Some("Jane Roe"));
+        let titles: Vec<&str> = book.documents.iter().map(|document| document.title.as_str()).collect();
+        assert_eq!(titles, vec!["One", "Two"]);
+        assert_eq!(book.documents[0].content, "It was *dark*.\n\n> A quote.");
+    }
+}
@@ -0,0 +1,642 @@
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::export;
+use crate::file_system::DocumentType;
+use crate::import::{self, ImportedBook, ImportedDocument};
+
+/// 不输出内容的元素
+const SKIPPED_ELEMENTS: &[&[u8]] = &[b"head", b"script", b"style", b"svg"];
+
+/// 作为段落边界的块级元素
+const BLOCK_ELEMENTS: &[&[u8]] = &[
+    b"p", b"div", b"section", b"article", b"header", b"footer", b"aside", b"main", b"nav",
+    b"figure", b"figcaption", b"table", b"tr", b"dl", b"dt", b"dd", b"body",
+];
+
+/// OPF 清单中的一项
+#[derive(Debug)]
+struct ManifestItem {
+    href: String,
+    media_type: String,
+    properties: String,
+}
+
+/// content.opf 的内容
+#[derive(Debug, Default)]
+struct Package {
+    metadata: HashMap<String, String>,
+    manifest: HashMap<String, ManifestItem>,
+    spine: Vec<String>,
+    ncx: Option<String>, // EPUB 2 目录的清单 ID
+}
+
+/// 读取 EPUB 电子书，每个书脊条目作为一章
+pub fn read(path: &Path) -> Result<ImportedBook> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut archive = ZipArchive::new(file).context("Not a valid EPUB file")?;
+
+    let container = import::read_zip_entry(&mut archive, "META-INF/container.xml")?
+        .context("EPUB file has no META-INF/container.xml")?;
+    let package_path = rootfile_path(&container)?;
+    let package_xml = import::read_zip_entry(&mut archive, &package_path)?
+        .with_context(|| format!("EPUB package {} not found", package_path))?;
+    let package = parse_package(&package_xml)?;
+
+    // 目录中的标题，按文件路径索引
+    let nav = package.manifest.values().find(|item| has_property(item, "nav"));
+    let ncx = package.ncx.as_ref().and_then(|id| package.manifest.get(id));
+    let toc = match (nav, ncx) {
+        (Some(item), _) | (None, Some(item)) => {
+            let toc_path = resolve_href(&package_path, &item.href);
+            match import::read_zip_entry(&mut archive, &toc_path)? {
+                Some(xml) if nav.is_some() => parse_nav(&xml, &toc_path)?,
+                Some(xml) => parse_ncx(&xml, &toc_path)?,
+                None => HashMap::new(),
+            }
+        }
+        (None, None) => HashMap::new(),
+    };
+
+    let mut chapters: Vec<(Option<String>, String)> = Vec::new();
+    for id in &package.spine {
+        let Some(item) = package.manifest.get(id) else { continue };
+        if has_property(item, "nav") || !item.media_type.contains("html") {
+            continue;
+        }
+
+        let item_path = resolve_href(&package_path, &item.href);
+        let Some(xhtml) = import::read_zip_entry(&mut archive, &item_path)? else { continue };
+        let markdown = xhtml_to_markdown(&xhtml)
+            .with_context(|| format!("Failed to convert {}", item_path))?;
+        // 只有封面图片的页面没有文字
+        if markdown.trim().is_empty() {
+            continue;
+        }
+
+        let (heading, content) = import::leading_heading(&markdown);
+        chapters.push((heading.or_else(|| toc.get(&item_path).cloned()), content));
+    }
+
+    let samples: Vec<&str> = chapters.iter().map(|(_, content)| content.as_str()).collect();
+    let language = export::detect_language(&samples);
+    let documents = chapters
+        .into_iter()
+        .enumerate()
+        .map(|(index, (title, content))| ImportedDocument {
+            title: title.unwrap_or_else(|| import::numbered_title(index + 1, language)),
+            doc_type: DocumentType::Chapter,
+            content,
+        })
+        .collect();
+
+    let metadata = |name: &str| package.metadata.get(name).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+    Ok(ImportedBook {
+        title: metadata("title").unwrap_or_default(),
+        author: metadata("creator"),
+        description: metadata("description"),
+        documents,
+    })
+}
+
+fn has_property(item: &ManifestItem, property: &str) -> bool {
+    item.properties.split_whitespace().any(|value| value == property)
+}
+
+fn xml_reader(xml: &str) -> Reader<&[u8]> {
+    let mut reader = Reader::from_str(xml);
+    // 不少电子书的 XHTML 并不严格
+    reader.config_mut().check_end_names = false;
+    reader
+}
+
+/// 从 container.xml 找到 OPF 文件路径
+fn rootfile_path(xml: &str) -> Result<String> {
+    let mut reader = xml_reader(xml);
+    loop {
+        match reader.read_event().context("Failed to parse EPUB container")? {
+            Event::Start(element) | Event::Empty(element) if element.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = import::xml_attribute(&element, "full-path") {
+                    return Ok(path);
+                }
+            }
+            Event::Eof => return Err(anyhow::anyhow!("EPUB container has no rootfile")),
+            _ => {}
+        }
+    }
+}
+
+/// 读取 OPF 中的元数据、清单和书脊
+fn parse_package(xml: &str) -> Result<Package> {
+    let mut reader = xml_reader(xml);
+    let mut package = Package::default();
+    let mut in_metadata = false;
+    let mut current: Option<String> = None;
+
+    loop {
+        match reader.read_event().context("Failed to parse EPUB package")? {
+            Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+                b"metadata" => in_metadata = true,
+                b"item" => {
+                    if let (Some(id), Some(href)) = (import::xml_attribute(&element, "id"), import::xml_attribute(&element, "href")) {
+                        package.manifest.insert(id, ManifestItem {
+                            href,
+                            media_type: import::xml_attribute(&element, "media-type").unwrap_or_default(),
+                            properties: import::xml_attribute(&element, "properties").unwrap_or_default(),
+                        });
+                    }
+                }
+                b"spine" => package.ncx = import::xml_attribute(&element, "toc"),
+                b"itemref" => {
+                    let linear = import::xml_attribute(&element, "linear");
+                    if let (Some(id), false) = (import::xml_attribute(&element, "idref"), linear.as_deref() == Some("no")) {
+                        package.spine.push(id);
+                    }
+                }
+                name if in_metadata => current = Some(String::from_utf8_lossy(name).into_owned()),
+                _ => {}
+            },
+            Event::Text(text) => {
+                // 同名元数据（如多位作者）只取第一个
+                if let Some(name) = current.take() {
+                    package.metadata.entry(name).or_insert_with(|| import::xml_text(&text));
+                }
+            }
+            Event::End(element) => {
+                current = None;
+                if element.local_name().as_ref() == b"metadata" {
+                    in_metadata = false;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(package)
+}
+
+/// 读取 EPUB 3 导航文档中的目录链接
+fn parse_nav(xml: &str, nav_path: &str) -> Result<HashMap<String, String>> {
+    let mut reader = xml_reader(xml);
+    let mut titles = HashMap::new();
+    let mut link: Option<(String, String)> = None;
+
+    loop {
+        match reader.read_event().context("Failed to parse EPUB navigation")? {
+            Event::Start(element) if element.local_name().as_ref() == b"a" => {
+                link = import::xml_attribute(&element, "href").map(|href| (resolve_href(nav_path, &href), String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, label)) = link.as_mut() {
+                    label.push_str(&import::xml_text(&text));
+                }
+            }
+            Event::End(element) if element.local_name().as_ref() == b"a" => {
+                if let Some((path, label)) = link.take() {
+                    insert_title(&mut titles, path, &label);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(titles)
+}
+
+/// 读取 EPUB 2 的 toc.ncx
+fn parse_ncx(xml: &str, ncx_path: &str) -> Result<HashMap<String, String>> {
+    let mut reader = xml_reader(xml);
+    let mut titles = HashMap::new();
+    let mut label = String::new();
+    let mut in_label = false;
+
+    loop {
+        match reader.read_event().context("Failed to parse EPUB table of contents")? {
+            Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+                b"navPoint" => label.clear(),
+                b"text" => in_label = true,
+                b"content" => {
+                    if let Some(src) = import::xml_attribute(&element, "src") {
+                        insert_title(&mut titles, resolve_href(ncx_path, &src), &label);
+                    }
+                    label.clear();
+                }
+                _ => {}
+            },
+            Event::Text(text) if in_label => label.push_str(&import::xml_text(&text)),
+            Event::End(element) if element.local_name().as_ref() == b"text" => in_label = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(titles)
+}
+
+/// 同一文件的多个目录项（如文件内的小节）只保留第一个
+fn insert_title(titles: &mut HashMap<String, String>, path: String, label: &str) {
+    let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !label.is_empty() {
+        titles.entry(path).or_insert(label);
+    }
+}
+
+/// 把相对于某个文件的链接解析为压缩包内的路径，去掉锚点
+fn resolve_href(base_file: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode(href);
+
+    let mut parts: Vec<&str> = base_file.split('/').collect();
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes.get(index + 1..index + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[index], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// 把 XHTML 章节转为 Markdown
+fn xhtml_to_markdown(xhtml: &str) -> Result<String> {
+    let mut reader = xml_reader(xhtml);
+    let mut writer = MarkdownWriter::default();
+    let mut skip_depth = 0usize;
+
+    loop {
+        let event = reader.read_event().context("Failed to parse XHTML")?;
+        if skip_depth > 0 {
+            match event {
+                Event::Start(_) => skip_depth += 1,
+                Event::End(_) => skip_depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            Event::Start(element) => {
+                if SKIPPED_ELEMENTS.contains(&element.local_name().as_ref()) {
+                    skip_depth = 1;
+                } else {
+                    writer.start(&element);
+                }
+            }
+            Event::Empty(element) => writer.empty(&element),
+            Event::End(element) => writer.end(element.local_name().as_ref()),
+            Event::Text(text) => writer.text(&import::xml_text(&text)),
+            Event::CData(data) => writer.text(&String::from_utf8_lossy(&data)),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    writer.flush();
+    Ok(writer.blocks.join("\n\n"))
+}
+
+/// 逐个元素生成 Markdown
+#[derive(Debug, Default)]
+struct MarkdownWriter {
+    blocks: Vec<String>,
+    inline: String,
+    pending_space: bool,
+    pending_break: bool,
+    pending_open: String, // 还没有遇到文字的开始标记，空元素时直接丢弃
+    heading: Option<usize>,
+    quote_depth: usize,
+    lists: Vec<Option<usize>>, // 无序列表为 None，有序列表为下一个序号
+    item_started: bool,
+    links: Vec<Option<String>>,
+    code_depth: usize,
+    pre: Option<String>,
+    last_block: Option<(usize, bool)>, // 上一块的引用层级和是否为列表项
+}
+
+impl MarkdownWriter {
+    fn start(&mut self, element: &BytesStart) {
+        let name = element.local_name();
+        match name.as_ref() {
+            b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => {
+                self.flush();
+                self.heading = Some((name.as_ref()[1] - b'0') as usize);
+            }
+            b"blockquote" => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            b"ul" | b"ol" => {
+                self.flush();
+                let start = import::xml_attribute(element, "start").and_then(|start| start.parse().ok());
+                self.lists.push((name.as_ref() == b"ol").then_some(start.unwrap_or(1)));
+            }
+            b"li" => {
+                self.flush();
+                self.item_started = false;
+            }
+            b"pre" => {
+                self.flush();
+                self.pre = Some(String::new());
+            }
+            b"em" | b"i" | b"cite" | b"dfn" => self.open("*"),
+            b"strong" | b"b" => self.open("**"),
+            b"s" | b"del" | b"strike" => self.open("~~"),
+            b"code" | b"kbd" | b"samp" if self.pre.is_none() => {
+                self.open("`");
+                self.code_depth += 1;
+            }
+            b"a" => {
+                let href = import::xml_attribute(element, "href")
+                    .filter(|href| href.starts_with("http://") || href.starts_with("https://"));
+                if href.is_some() {
+                    self.open("[");
+                }
+                self.links.push(href);
+            }
+            b"td" | b"th" => self.cell(),
+            name if BLOCK_ELEMENTS.contains(&name) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn empty(&mut self, element: &BytesStart) {
+        match element.local_name().as_ref() {
+            b"br" => match self.pre.as_mut() {
+                Some(pre) => pre.push('\n'),
+                None => self.pending_break = !self.inline.is_empty(),
+            },
+            b"hr" => {
+                self.flush();
+                self.push_block("---".to_string(), false);
+            }
+            b"td" | b"th" => self.cell(),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &[u8]) {
+        match name {
+            b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" | b"li" => self.flush(),
+            b"blockquote" => {
+                self.flush();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            b"ul" | b"ol" => {
+                self.flush();
+                self.lists.pop();
+            }
+            b"pre" => {
+                if let Some(code) = self.pre.take() {
+                    let code = code.trim_matches('\n');
+                    if !code.trim().is_empty() {
+                        self.push_block(format!("```\n{}\n```", code), false);
+                    }
+                }
+            }
+            b"em" | b"i" | b"cite" | b"dfn" => self.close("*"),
+            b"strong" | b"b" => self.close("**"),
+            b"s" | b"del" | b"strike" => self.close("~~"),
+            b"code" | b"kbd" | b"samp" if self.pre.is_none() => {
+                self.code_depth = self.code_depth.saturating_sub(1);
+                self.close("`");
+            }
+            b"a" => {
+                if let Some(Some(href)) = self.links.pop() {
+                    if self.pending_open.ends_with('[') {
+                        self.pending_open.pop();
+                    } else {
+                        self.inline.push_str(&format!("]({})", href));
+                    }
+                }
+            }
+            name if BLOCK_ELEMENTS.contains(&name) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(pre) = self.pre.as_mut() {
+            pre.push_str(text);
+            return;
+        }
+
+        if text.starts_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+        for (index, word) in text.split_whitespace().enumerate() {
+            if index > 0 {
+                self.pending_space = true;
+            }
+            self.write_pending();
+            if self.code_depth > 0 {
+                self.inline.push_str(word);
+            } else {
+                self.inline.push_str(&import::escape_markdown(word));
+            }
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+    }
+
+    /// 开始标记等到有文字时才写入，空格放在标记前
+    fn open(&mut self, marker: &str) {
+        self.pending_open.push_str(marker);
+    }
+
+    /// 结束标记紧跟文字，之后的空格留到下一段文字前
+    fn close(&mut self, marker: &str) {
+        if self.pending_open.ends_with(marker) {
+            self.pending_open.truncate(self.pending_open.len() - marker.len());
+        } else {
+            self.inline.push_str(marker);
+        }
+    }
+
+    fn cell(&mut self) {
+        if !self.inline.is_empty() {
+            self.pending_space = false;
+            self.inline.push_str(" | ");
+        }
+    }
+
+    fn write_pending(&mut self) {
+        if !self.inline.is_empty() {
+            if self.pending_break {
+                self.inline.push_str("\\\n");
+            } else if self.pending_space {
+                self.inline.push(' ');
+            }
+        }
+        self.pending_space = false;
+        self.pending_break = false;
+        let open = std::mem::take(&mut self.pending_open);
+        self.inline.push_str(&open);
+    }
+
+    /// 结束当前段落
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.inline);
+        self.pending_space = false;
+        self.pending_break = false;
+        self.pending_open.clear();
+        let heading = self.heading.take();
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+
+        if let Some(level) = heading {
+            self.push_block(format!("{} {}", "#".repeat(level), text.replace("\\\n", " ")), false);
+            return;
+        }
+
+        let indent = "    ".repeat(self.lists.len().saturating_sub(1));
+        let Some(list) = self.lists.last_mut() else {
+            self.push_block(import::escape_block_start(text), false);
+            return;
+        };
+        let marker = match list {
+            Some(number) => {
+                *number += 1;
+                format!("{}. ", *number - 1)
+            }
+            None => "- ".to_string(),
+        };
+        let (prefix, is_item) = if self.item_started {
+            // 同一列表项中的后续段落
+            (format!("{}{}", indent, " ".repeat(marker.len())), false)
+        } else {
+            self.item_started = true;
+            (format!("{}{}", indent, marker), true)
+        };
+        let continuation = format!("\n{}", " ".repeat(prefix.len()));
+        let text = import::escape_block_start(text).replace('\n', &continuation);
+        self.push_block(format!("{}{}", prefix, text), is_item);
+    }
+
+    /// 加上引用前缀后追加，同一引用或同一列表中的块不断开
+    fn push_block(&mut self, block: String, is_item: bool) {
+        let depth = self.quote_depth;
+        let quote = "> ".repeat(depth);
+        let block = if depth > 0 {
+            block.lines().map(|line| format!("{}{}", quote, line).trim_end().to_string()).collect::<Vec<_>>().join("\n")
+        } else {
+            block
+        };
+
+        match (self.last_block, self.blocks.last_mut()) {
+            (Some((last_depth, true)), Some(last)) if is_item && last_depth == depth => {
+                last.push('\n');
+                last.push_str(&block);
+            }
+            (Some((last_depth, _)), Some(last)) if depth > 0 && last_depth == depth => {
+                last.push('\n');
+                last.push_str(quote.trim_end());
+                last.push('\n');
+                last.push_str(&block);
+            }
+            _ => self.blocks.push(block),
+        }
+        self.last_block = Some((depth, is_item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epub;
+    use crate::export::Chapter;
+    use crate::file_system::BookConfig;
+
+    #[test]
+    fn converts_xhtml_to_markdown() {
+        let xhtml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml"><head><title>x</title><style>p { color: red }</style></head>
+<body>
+<h2>Chapter  One</h2>
+<p>It was <em>very </em>dark&nbsp;and <a href="https://example.com">cold</a>.<br/>
+Second line with 5*3.</p>
+<blockquote><p>First.</p><p>Second.</p></blockquote>
+<ul><li>Apples</li><li>Pears<ol><li>Small</li></ol></li></ul>
+<hr/>
+<pre><code>let x = 1;
+let y = 2;</code></pre>
+<p>  </p>
+<p>- not a list</p>
+</body></html>"#;
+
+        assert_eq!(
+            xhtml_to_markdown(xhtml).unwrap(),
+            "## Chapter One\n\n\
+             It was *very* dark and [cold](https://example.com).\\\nSecond line with 5\\*3.\n\n\
+             > First.\n>\n> Second.\n\n\
+             - Apples\n- Pears\n    1. Small\n\n\
+             ---\n\n\
+             ```\nlet x = 1;\nlet y = 2;\n```\n\n\
+             \\- not a list"
+        );
+    }
+
+    #[test]
+    fn resolves_relative_hrefs() {
+        assert_eq!(resolve_href("OEBPS/content.opf", "text/ch%201.xhtml#top"), "OEBPS/text/ch 1.xhtml");
+        assert_eq!(resolve_href("OEBPS/text/nav.xhtml", "../text/./ch2.xhtml"), "OEBPS/text/ch2.xhtml");
+        assert_eq!(resolve_href("content.opf", "ch3.xhtml"), "ch3.xhtml");
+    }
+
+    #[test]
+    fn reads_exported_epub_back() {
+        let config = BookConfig { description: "一本短篇集".to_string(), ..BookConfig::sample("雨夜", "作者") };
+        let chapters = vec![
+            Chapter { title: "第一章".into(), content: "雨下了一夜。\n\n**天亮**了。".into() },
+            Chapter { title: "第二章".into(), content: "## 归途\n\n他回家了。".into() },
+        ];
+        let bytes = epub::build(&config, &chapters, None).unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("book.epub");
+        std::fs::write(&path, bytes).unwrap();
+
+        let book = read(&path).unwrap();
+        assert_eq!(book.title, "雨夜");
+        assert_eq!(book.author.as_deref(), Some("作者"));
+        assert_eq!(book.description.as_deref(), Some("一本短篇集"));
+        assert_eq!(book.documents.len(), 2);
+        assert_eq!(book.documents[0].title, "第一章");
+        assert_eq!(book.documents[0].content, "雨下了一夜。\n\n**天亮**了。");
+        assert_eq!(book.documents[1].title, "归途");
+        assert_eq!(book.documents[1].content, "他回家了。");
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::atomic;
+
+/// 日志条目超过该数量时重写为一个完整快照
+const MAX_JOURNAL_ENTRIES: usize = 500;
+
+/// 编辑日志中的一条记录，每行一个 JSON 对象
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JournalEntry {
+    /// 完整内容，总是日志的第一条
+    Snapshot { timestamp: DateTime<Utc>, content: String },
+    /// 从第 `start` 个字符起删除 `delete` 个字符并插入 `insert`
+    Splice { timestamp: DateTime<Utc>, start: usize, delete: usize, insert: String },
+}
+
+/// 重放日志得到的内容
+#[derive(Debug, Clone)]
+pub struct ReplayedJournal {
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 日志最后写入的内容，保存在内存中，追加时不必重放整个日志
+#[derive(Debug, Clone)]
+pub struct JournalHead {
+    content: String,
+    entries: usize,
+}
+
+impl JournalHead {
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+/// 把编辑器的最新内容追加到日志，返回写入后的状态
+///
+/// 只记录与 `head` 的差异；没有 `head`（本次运行还没写过，或上次写入失败）、
+/// 日志文件已不存在或日志过长时改为写入完整快照，覆盖原有的日志。
+pub fn append(path: &Path, head: Option<JournalHead>, content: &str) -> Result<JournalHead> {
+    let timestamp = Utc::now();
+
+    let head = match head {
+        Some(head) if head.entries < MAX_JOURNAL_ENTRIES && path.exists() => head,
+        _ => {
+            let snapshot = JournalEntry::Snapshot { timestamp, content: content.to_string() };
+            atomic::write_file(path, encode(&snapshot)?)
+                .context("Failed to write journal snapshot")?;
+            return Ok(JournalHead { content: content.to_string(), entries: 1 });
+        }
+    };
+
+    let Some((start, delete, insert)) = splice(&head.content, content) else {
+        return Ok(head);
+    };
+
+    let entry = JournalEntry::Splice { timestamp, start, delete, insert };
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .context("Failed to open journal")?;
+    file.write_all(encode(&entry)?.as_bytes())
+        .context("Failed to append to journal")?;
+    file.sync_data()
+        .context("Failed to sync journal")?;
+
+    Ok(JournalHead { content: content.to_string(), entries: head.entries + 1 })
+}
+
+/// 重放日志，日志不存在或没有有效快照时返回 None
+pub fn replay(path: &Path) -> Result<Option<ReplayedJournal>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let journal = fs::read_to_string(path)
+        .context("Failed to read journal")?;
+
+    let mut replayed: Option<ReplayedJournal> = None;
+    for line in journal.split_inclusive('\n') {
+        // 崩溃时最后一行可能只写了一半，之后的内容一律忽略
+        let Some(Ok(entry)) = line.strip_suffix('\n').map(serde_json::from_str::<JournalEntry>) else {
+            break;
+        };
+
+        match (entry, replayed.as_mut()) {
+            (JournalEntry::Snapshot { timestamp, content }, _) => {
+                replayed = Some(ReplayedJournal { content, timestamp });
+            }
+            (JournalEntry::Splice { timestamp, start, delete, insert }, Some(state)) => {
+                if !apply_splice(&mut state.content, start, delete, &insert) {
+                    break;
+                }
+                state.timestamp = timestamp;
+            }
+            (JournalEntry::Splice { .. }, None) => break,
+        }
+    }
+
+    Ok(replayed)
+}
+
+/// 删除日志（正常保存后调用）
+pub fn remove(path: &Path) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)
+            .context("Failed to remove journal")?;
+    }
+
+    Ok(())
+}
+
+/// 编码为一行日志
+fn encode(entry: &JournalEntry) -> Result<String> {
+    let mut line = serde_json::to_string(entry)
+        .context("Failed to serialize journal entry")?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// 求出把 `old` 变为 `new` 的最小替换（按字符计），内容相同时返回 None
+fn splice(old: &str, new: &str) -> Option<(usize, usize, String)> {
+    if old == new {
+        return None;
+    }
+
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+
+    let prefix = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_chars[prefix..]
+        .iter()
+        .rev()
+        .zip(new_chars[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let delete = old_chars.len() - prefix - suffix;
+    let insert = new_chars[prefix..new_chars.len() - suffix].iter().collect();
+    Some((prefix, delete, insert))
+}
+
+/// 应用一次替换，位置越界时返回 false
+fn apply_splice(content: &mut String, start: usize, delete: usize, insert: &str) -> bool {
+    let byte_offset = |chars: usize| {
+        content
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(content.len()))
+            .nth(chars)
+    };
+
+    match (byte_offset(start), byte_offset(start + delete)) {
+        (Some(from), Some(to)) => {
+            content.replace_range(from..to, insert);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn replays_appended_edits() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.log");
+
+        let mut head = None;
+        for content in ["她", "她推开", "她推开了门。", "她轻轻推开了门。", "她轻轻推开了窗。", "她轻轻推开了窗。"] {
+            head = Some(append(&path, head, content).unwrap());
+        }
+
+        let replayed = replay(&path).unwrap().unwrap();
+        assert_eq!(replayed.content, "她轻轻推开了窗。");
+        assert_eq!(head.unwrap().entries, 5);
+
+        // 只有第一条是完整快照
+        let journal = fs::read_to_string(&path).unwrap();
+        assert_eq!(journal.matches("snapshot").count(), 1);
+    }
+
+    #[test]
+    fn ignores_torn_tail_and_starts_fresh() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.log");
+        let head = append(&path, None, "first draft").unwrap();
+        append(&path, Some(head), "first draft, revised").unwrap();
+
+        // 模拟写到一半时断电
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"type":"splice","timestamp":"2024-"#).unwrap();
+
+        let replayed = replay(&path).unwrap().unwrap();
+        assert_eq!(replayed.content, "first draft, revised");
+
+        // 重新启动后内存中没有状态，从完整快照开始
+        let head = append(&path, None, "second draft").unwrap();
+        append(&path, Some(head), "second draft!").unwrap();
+        let replayed = replay(&path).unwrap().unwrap();
+        assert_eq!(replayed.content, "second draft!");
+
+        // 日志已被删除时也重新写快照
+        remove(&path).unwrap();
+        let head = append(&path, None, "third").unwrap();
+        remove(&path).unwrap();
+        append(&path, Some(head), "third draft").unwrap();
+        assert_eq!(replay(&path).unwrap().unwrap().content, "third draft");
+    }
+
+    #[test]
+    fn splice_works_on_characters() {
+        assert_eq!(splice("春眠不觉晓", "春眠不知晓"), Some((3, 1, "知".to_string())));
+        assert_eq!(splice("same", "same"), None);
+
+        let mut content = "春眠不觉晓".to_string();
+        assert!(apply_splice(&mut content, 3, 1, "知"));
+        assert_eq!(content, "春眠不知晓");
+        assert!(!apply_splice(&mut content, 9, 1, ""));
+    }
+}
@@ -1,4 +1,26 @@
+mod atomic;
+mod diff;
+mod docx;
+mod epub;
+mod export;
 mod file_system;
+mod fonts;
+mod import;
+mod import_docx;
+mod import_epub;
+mod journal;
+mod merge;
+mod object_store;
+mod outline;
+mod pdf;
+mod replace;
+mod sandbox;
+mod schema;
+mod search;
+mod site;
+mod stats;
+mod text;
+mod trash;
 mod commands;
 
 use commands::AppState;
@@ -18,8 +40,12 @@ pub fn run() {
       commands::delete_project,
       commands::export_project,
       commands::get_project_stats,
+      commands::repack_project,
+      commands::migrate_project,
+      commands::migrate_all_projects,
       commands::select_folder,
       commands::select_file,
+      commands::select_save_file,
       commands::show_message,
       commands::file_exists,
       commands::create_directory,
@@ -32,16 +58,71 @@ pub fn run() {
       commands::get_desktop_dir,
       // 书籍管理命令
       commands::create_book,
+      commands::import_book,
       commands::list_books,
       commands::load_book,
       commands::save_book,
       commands::delete_book,
+      // 回收站命令
+      commands::list_trash,
+      commands::restore_from_trash,
+      commands::empty_trash,
+      commands::get_trash_settings,
+      commands::save_trash_settings,
+      // 全文搜索命令
+      commands::search,
+      commands::search_history,
+      // 查找替换命令
+      commands::replace_in_book,
+      commands::revert_commit_group,
+      // 书籍导出命令
+      commands::export_book,
+      commands::export_book_docx,
+      commands::export_book_pdf,
+      commands::export_document_pdf,
+      commands::export_book_site,
       // 文档管理命令
       commands::create_document,
       commands::list_documents,
       commands::load_document,
       commands::save_document,
       commands::delete_document,
+      commands::refresh_book_stats,
+      commands::get_text_stats,
+      // 状态流程命令
+      commands::get_status_workflow,
+      commands::save_status_workflow,
+      commands::set_document_status,
+      commands::list_status_history,
+      // 目录结构命令
+      commands::get_document_tree,
+      commands::move_document,
+      commands::reorder_documents,
+      commands::renumber_documents,
+      // 编辑日志命令
+      commands::append_document_journal,
+      commands::list_recoverable_documents,
+      commands::recover_document_journal,
+      commands::discard_document_journal,
+      // 文档版本管理命令
+      commands::create_document_commit,
+      commands::list_document_commits,
+      commands::load_document_commit,
+      commands::delete_document_commit,
+      commands::repack_document,
+      // 文档分支管理命令
+      commands::list_document_branches,
+      commands::create_document_branch,
+      commands::switch_document_branch,
+      commands::rename_document_branch,
+      commands::delete_document_branch,
+      // 文档合并命令
+      commands::merge_text,
+      commands::merge_document_branch,
+      commands::resolve_document_merge,
+      // 差异比较命令
+      commands::diff_text,
+      commands::diff_versions,
     ])
     .setup(|app| {
       if cfg!(debug_assertions) {
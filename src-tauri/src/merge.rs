@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+use similar::DiffTag;
+
+use crate::diff;
+
+/// 合并结果中的一个区域
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MergeRegion {
+    Resolved { text: String },
+    Conflict { base: String, ours: String, theirs: String },
+}
+
+/// 三方合并结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    pub regions: Vec<MergeRegion>,
+    pub conflict_count: usize,
+    pub content: Option<String>, // 无冲突时的合并文本
+}
+
+impl MergeResult {
+    fn from_regions(regions: Vec<MergeRegion>) -> Self {
+        let conflict_count = regions
+            .iter()
+            .filter(|region| matches!(region, MergeRegion::Conflict { .. }))
+            .count();
+
+        let content = if conflict_count == 0 {
+            Some(regions.iter().map(|region| match region {
+                MergeRegion::Resolved { text } => text.as_str(),
+                MergeRegion::Conflict { .. } => "",
+            }).collect())
+        } else {
+            None
+        };
+
+        Self { regions, conflict_count, content }
+    }
+}
+
+/// 三方合并：先按段落合并，双方改动同一段落时再按句子合并
+pub fn merge_texts(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_paragraphs = split_paragraphs(base);
+    let our_paragraphs = split_paragraphs(ours);
+    let their_paragraphs = split_paragraphs(theirs);
+
+    let mut regions = Vec::new();
+    for chunk in diff3(&base_paragraphs, &our_paragraphs, &their_paragraphs) {
+        match chunk {
+            Chunk::Resolved(text) => push_resolved(&mut regions, text),
+            Chunk::Conflict { base, ours, theirs } => {
+                let base_sentences = split_sentences(&base);
+                let our_sentences = split_sentences(&ours);
+                let their_sentences = split_sentences(&theirs);
+
+                for chunk in diff3(&base_sentences, &our_sentences, &their_sentences) {
+                    match chunk {
+                        Chunk::Resolved(text) => push_resolved(&mut regions, text),
+                        Chunk::Conflict { base, ours, theirs } => {
+                            regions.push(MergeRegion::Conflict { base, ours, theirs });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    MergeResult::from_regions(regions)
+}
+
+/// 追加已解决的文本，与前一个已解决区域合并
+fn push_resolved(regions: &mut Vec<MergeRegion>, text: String) {
+    if text.is_empty() {
+        return;
+    }
+
+    if let Some(MergeRegion::Resolved { text: previous }) = regions.last_mut() {
+        previous.push_str(&text);
+    } else {
+        regions.push(MergeRegion::Resolved { text });
+    }
+}
+
+/// diff3 的中间结果
+enum Chunk {
+    Resolved(String),
+    Conflict { base: String, ours: String, theirs: String },
+}
+
+/// 一方相对基础版本的改动：基础版本 `base_start..base_end` 被替换为该方到 `end` 为止的内容
+#[derive(Clone, Copy)]
+struct Hunk {
+    base_start: usize,
+    base_end: usize,
+    end: usize,
+}
+
+/// 对三个切分好的序列做三方合并
+///
+/// 双方改动的基础区间互不重叠时（包括紧挨着的改动）都会被采纳，
+/// 只有改动了同一区间或在同一位置插入时才视为冲突。
+fn diff3(base: &[&str], ours: &[&str], theirs: &[&str]) -> Vec<Chunk> {
+    let our_hunks = hunks(&match_positions(base, ours), ours.len());
+    let their_hunks = hunks(&match_positions(base, theirs), theirs.len());
+
+    // 按基础版本位置排序，标记来自哪一方
+    let mut all: Vec<(Hunk, bool)> = our_hunks
+        .iter()
+        .map(|hunk| (*hunk, true))
+        .chain(their_hunks.iter().map(|hunk| (*hunk, false)))
+        .collect();
+    all.sort_by_key(|(hunk, _)| (hunk.base_start, hunk.base_end));
+
+    let mut chunks = Vec::new();
+    let mut cursor = 0;
+    let (mut our_offset, mut their_offset) = (0isize, 0isize);
+    let shift = |position: usize, offset: isize| (position as isize + offset) as usize;
+
+    let mut i = 0;
+    while i < all.len() {
+        // 把相互重叠的改动聚成一组
+        let cluster_start = all[i].0.base_start;
+        let mut cluster_end = all[i].0.base_end;
+        let (mut has_ours, mut has_theirs) = (false, false);
+        let (mut our_end_offset, mut their_end_offset) = (our_offset, their_offset);
+        let mut j = i;
+
+        while j < all.len() {
+            let (hunk, is_ours) = all[j];
+            let overlaps = j == i
+                || (hunk.base_start < cluster_end && cluster_start < hunk.base_end)
+                || hunk.base_start == cluster_start;
+            if !overlaps {
+                break;
+            }
+
+            cluster_end = cluster_end.max(hunk.base_end);
+            let offset = hunk.end as isize - hunk.base_end as isize;
+            if is_ours {
+                has_ours = true;
+                our_end_offset = offset;
+            } else {
+                has_theirs = true;
+                their_end_offset = offset;
+            }
+            j += 1;
+        }
+
+        if cursor < cluster_start {
+            chunks.push(Chunk::Resolved(base[cursor..cluster_start].concat()));
+        }
+
+        let base_text = &base[cluster_start..cluster_end];
+        let our_text = &ours[shift(cluster_start, our_offset)..shift(cluster_end, our_end_offset)];
+        let their_text = &theirs[shift(cluster_start, their_offset)..shift(cluster_end, their_end_offset)];
+
+        chunks.push(match (has_ours, has_theirs) {
+            (true, false) => Chunk::Resolved(our_text.concat()),
+            (false, true) => Chunk::Resolved(their_text.concat()),
+            _ => classify(base_text, our_text, their_text),
+        });
+
+        cursor = cluster_end;
+        our_offset = our_end_offset;
+        their_offset = their_end_offset;
+        i = j;
+    }
+
+    if cursor < base.len() {
+        chunks.push(Chunk::Resolved(base[cursor..].concat()));
+    }
+
+    chunks
+}
+
+/// 根据匹配位置求出一方的改动区间
+fn hunks(matches: &[Option<usize>], other_len: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut base_start, mut start) = (0, 0);
+
+    for base_index in 0..=matches.len() {
+        let matched = if base_index < matches.len() { matches[base_index] } else { Some(other_len) };
+
+        if let Some(other_index) = matched {
+            if base_index > base_start || other_index > start {
+                hunks.push(Hunk { base_start, base_end: base_index, end: other_index });
+            }
+            base_start = base_index + 1;
+            start = other_index + 1;
+        }
+    }
+
+    hunks
+}
+
+/// 判断双方都改动过的区域能否自动解决
+fn classify(base: &[&str], ours: &[&str], theirs: &[&str]) -> Chunk {
+    if ours == theirs || theirs == base {
+        Chunk::Resolved(ours.concat())
+    } else if ours == base {
+        Chunk::Resolved(theirs.concat())
+    } else {
+        Chunk::Conflict {
+            base: base.concat(),
+            ours: ours.concat(),
+            theirs: theirs.concat(),
+        }
+    }
+}
+
+/// 求出 `base` 中每个元素在 `other` 中的对应位置
+fn match_positions(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+
+    for (tag, base_range, other_range) in diff::diff_slices(base, other) {
+        if tag == DiffTag::Equal {
+            for (b, o) in base_range.zip(other_range) {
+                matches[b] = Some(o);
+            }
+        }
+    }
+
+    matches
+}
+
+/// 按段落切分：每段包含其后的空行，拼接后与原文完全一致
+fn split_paragraphs(text: &str) -> Vec<&str> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    let mut in_blank = false;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let is_blank = line.trim().is_empty();
+        if in_blank && !is_blank {
+            paragraphs.push(&text[start..offset]);
+            start = offset;
+        }
+        in_blank = is_blank;
+        offset += line.len();
+    }
+
+    if start < text.len() {
+        paragraphs.push(&text[start..]);
+    }
+
+    paragraphs
+}
+
+/// 按句子切分（支持中英文标点），句末的引号和空白归入前一句
+fn split_sentences(text: &str) -> Vec<&str> {
+    const TERMINATORS: &[char] = &['。', '！', '？', '!', '?', '.', '…', '\n'];
+    const CLOSERS: &[char] = &['"', '\'', '”', '’', '」', '』', '）', ')', '》'];
+
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if !TERMINATORS.contains(&c) {
+            continue;
+        }
+
+        // 英文句点后必须跟空白或结尾，避免切开 3.14 之类的写法
+        if c == '.' {
+            match chars.peek() {
+                Some((_, next)) if !next.is_whitespace() && !CLOSERS.contains(next) && *next != '.' => continue,
+                _ => {}
+            }
+        }
+
+        while let Some(&(_, next)) = chars.peek() {
+            if TERMINATORS.contains(&next) || CLOSERS.contains(&next) || (next.is_whitespace() && next != '\n') {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        let end = chars.peek().map(|(i, _)| *i).unwrap_or(text.len());
+        sentences.push(&text[start..end]);
+        start = end;
+    }
+
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+
+    sentences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitting_preserves_text() {
+        let text = "第一段。她说：“走吧！”\n\n\nSecond paragraph. Pi is 3.14... really?\n";
+        assert_eq!(split_paragraphs(text).concat(), text);
+        assert_eq!(split_paragraphs(text).len(), 2);
+        assert_eq!(split_sentences(text).concat(), text);
+        assert_eq!(split_sentences("她说：“走吧！”然后离开。"), vec!["她说：“走吧！”", "然后离开。"]);
+    }
+
+    #[test]
+    fn merges_changes_to_different_paragraphs() {
+        let base = "开头。\n\n中间。\n\n结尾。\n";
+        let ours = "开头改了。\n\n中间。\n\n结尾。\n";
+        let theirs = "开头。\n\n中间。\n\n结尾也改了。\n";
+
+        let result = merge_texts(base, ours, theirs);
+        assert_eq!(result.conflict_count, 0);
+        assert_eq!(result.content.as_deref(), Some("开头改了。\n\n中间。\n\n结尾也改了。\n"));
+
+        // 紧挨着的两段分别被改动也能合并
+        let result = merge_texts("甲。\n\n乙。\n", "甲甲。\n\n乙。\n", "甲。\n\n乙乙。\n");
+        assert_eq!(result.content.as_deref(), Some("甲甲。\n\n乙乙。\n"));
+    }
+
+    #[test]
+    fn merges_changes_to_different_sentences_of_one_paragraph() {
+        let base = "He walked in. The room was dark. She waited.\n";
+        let ours = "He stormed in. The room was dark. She waited.\n";
+        let theirs = "He walked in. The room was dark. She waited alone.\n";
+
+        let result = merge_texts(base, ours, theirs);
+        assert_eq!(
+            result.content.as_deref(),
+            Some("He stormed in. The room was dark. She waited alone.\n")
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_sentences() {
+        let base = "雨停了。她推开门。\n";
+        let ours = "雪停了。她推开门。\n";
+        let theirs = "风停了。她推开门。\n";
+
+        let result = merge_texts(base, ours, theirs);
+        assert_eq!(result.conflict_count, 1);
+        assert!(result.content.is_none());
+        assert_eq!(
+            result.regions,
+            vec![
+                MergeRegion::Conflict {
+                    base: "雨停了。".to_string(),
+                    ours: "雪停了。".to_string(),
+                    theirs: "风停了。".to_string(),
+                },
+                MergeRegion::Resolved { text: "她推开门。\n".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_changes_do_not_conflict() {
+        let result = merge_texts("a\n\nb\n", "a\n\nc\n", "a\n\nc\n");
+        assert_eq!(result.content.as_deref(), Some("a\n\nc\n"));
+        assert_eq!(merge_texts("", "新内容", "").content.as_deref(), Some("新内容"));
+    }
+}
@@ -0,0 +1,568 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::atomic;
+
+/// 默认的增量链最大长度（每隔多少个版本保存一次完整快照）
+pub const DEFAULT_MAX_CHAIN_LENGTH: usize = 32;
+
+/// 查找匹配行时每行最多检查的候选位置数
+const MAX_MATCH_CANDIDATES: usize = 64;
+
+/// 解析增量链时允许的最大深度，防止损坏的数据造成死循环
+const MAX_RESOLVE_DEPTH: usize = 4096;
+
+/// 增量操作（以基础版本的行为单位）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DeltaOp {
+    Copy { start: usize, len: usize },
+    Insert { text: String },
+}
+
+/// 增量对象
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DeltaObject {
+    base: String,
+    ops: Vec<DeltaOp>,
+}
+
+/// 重新打包统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepackStats {
+    pub objects: usize,
+    pub full_objects: usize,
+    pub delta_objects: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// 内容寻址对象存储
+///
+/// 每个快照按其 SHA-256 哈希存放在 `objects/<前两位>/<其余位>`，
+/// 相同内容只会写入一次，项目和书籍文档共用同一个存储。
+/// 快照可以保存为完整内容，也可以保存为相对父版本的增量（`.delta` 文件），
+/// 增量链长度达到上限时会重新写入完整快照。
+pub struct ObjectStore {
+    objects_dir: PathBuf,
+    max_chain_length: usize,
+}
+
+impl ObjectStore {
+    /// 创建对象存储，确保目录存在
+    pub fn new(objects_dir: PathBuf) -> Result<Self> {
+        if !objects_dir.exists() {
+            fs::create_dir_all(&objects_dir)
+                .context("Failed to create objects directory")?;
+        }
+
+        Ok(Self {
+            objects_dir,
+            max_chain_length: DEFAULT_MAX_CHAIN_LENGTH,
+        })
+    }
+
+    /// 设置增量链最大长度
+    #[cfg(test)]
+    pub fn with_max_chain_length(mut self, max_chain_length: usize) -> Self {
+        self.max_chain_length = max_chain_length.max(1);
+        self
+    }
+
+    /// 计算内容哈希
+    pub fn hash(content: &str) -> String {
+        format!("{:x}", Sha256::digest(content.as_bytes()))
+    }
+
+    /// 写入内容并返回其哈希，尽量保存为相对 `base` 的增量
+    pub fn put_with_base(&self, content: &str, base: Option<&str>) -> Result<String> {
+        let hash = Self::hash(content);
+
+        if self.contains(&hash) {
+            return Ok(hash);
+        }
+
+        if let Some(base) = base {
+            if let Some(delta) = self.build_delta(&hash, content, base)? {
+                self.write_delta(&hash, &delta)?;
+                return Ok(hash);
+            }
+        }
+
+        self.write_full(&hash, content)?;
+        Ok(hash)
+    }
+
+    /// 按哈希读取内容
+    pub fn get(&self, hash: &str) -> Result<String> {
+        // 沿增量链找到完整快照
+        let mut deltas = Vec::new();
+        let mut current = hash.to_string();
+
+        let mut content = loop {
+            if deltas.len() > MAX_RESOLVE_DEPTH {
+                return Err(anyhow::anyhow!("Delta chain too deep for object: {}", hash));
+            }
+
+            let full_path = self.object_path(&current)?;
+            if full_path.exists() {
+                break fs::read_to_string(&full_path)
+                    .context("Failed to read object")?;
+            }
+
+            let delta = self.read_delta(&current)?
+                .ok_or_else(|| anyhow::anyhow!("Object not found: {}", current))?;
+            current = delta.base.clone();
+            deltas.push(delta);
+        };
+
+        for delta in deltas.iter().rev() {
+            content = apply_delta(&content, &delta.ops)?;
+        }
+
+        if Self::hash(&content) != hash.to_ascii_lowercase() {
+            return Err(anyhow::anyhow!("Object is corrupted: {}", hash));
+        }
+
+        Ok(content)
+    }
+
+    /// 检查对象是否存在
+    pub fn contains(&self, hash: &str) -> bool {
+        self.object_path(hash)
+            .map(|path| path.exists() || Self::delta_path(&path).exists())
+            .unwrap_or(false)
+    }
+
+    /// 按版本顺序（从旧到新）重建增量链
+    ///
+    /// 每 `max_chain_length` 个版本保存一次完整快照，其余版本保存为相对前一版本的增量。
+    pub fn repack(&self, hashes: &[String]) -> Result<RepackStats> {
+        let mut stats = RepackStats::default();
+        let mut seen = HashSet::new();
+        let mut previous: Option<(String, String)> = None;
+
+        for hash in hashes {
+            if !seen.insert(hash.to_ascii_lowercase()) || !self.contains(hash) {
+                continue;
+            }
+
+            let content = self.get(hash)?;
+            stats.bytes_before += self.stored_size(hash)?;
+
+            let position = stats.objects;
+            stats.objects += 1;
+
+            let delta = match &previous {
+                Some((base_hash, base_content)) if position % self.max_chain_length != 0 => {
+                    let ops = compute_delta(base_content, &content);
+                    let delta = DeltaObject { base: base_hash.clone(), ops };
+                    if Self::is_worth_storing(&delta, &content)? {
+                        Some(delta)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            match delta {
+                Some(delta) => {
+                    self.write_delta(hash, &delta)?;
+                    let full_path = self.object_path(hash)?;
+                    if full_path.exists() {
+                        fs::remove_file(&full_path)
+                            .context("Failed to remove repacked object")?;
+                    }
+                    stats.delta_objects += 1;
+                }
+                None => {
+                    self.write_full(hash, &content)?;
+                    let delta_path = Self::delta_path(&self.object_path(hash)?);
+                    if delta_path.exists() {
+                        fs::remove_file(&delta_path)
+                            .context("Failed to remove repacked delta")?;
+                    }
+                    stats.full_objects += 1;
+                }
+            }
+
+            stats.bytes_after += self.stored_size(hash)?;
+            previous = Some((hash.clone(), content));
+        }
+
+        Ok(stats)
+    }
+
+    /// 尝试为新对象构建增量，链过长或增量不划算时返回 None
+    fn build_delta(&self, hash: &str, content: &str, base: &str) -> Result<Option<DeltaObject>> {
+        if !self.contains(base) || base.eq_ignore_ascii_case(hash) {
+            return Ok(None);
+        }
+
+        match self.chain_depth(base, hash)? {
+            Some(depth) if depth + 1 < self.max_chain_length => {}
+            _ => return Ok(None),
+        }
+
+        let base_content = self.get(base)?;
+        let delta = DeltaObject {
+            base: base.to_string(),
+            ops: compute_delta(&base_content, content),
+        };
+
+        if Self::is_worth_storing(&delta, content)? {
+            Ok(Some(delta))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 计算对象所在增量链的深度，链中出现 `exclude` 时返回 None 以避免形成环
+    fn chain_depth(&self, hash: &str, exclude: &str) -> Result<Option<usize>> {
+        let mut depth = 0;
+        let mut current = hash.to_string();
+
+        loop {
+            if current.eq_ignore_ascii_case(exclude) || depth > MAX_RESOLVE_DEPTH {
+                return Ok(None);
+            }
+
+            if self.object_path(&current)?.exists() {
+                return Ok(Some(depth));
+            }
+
+            match self.read_delta(&current)? {
+                Some(delta) => {
+                    current = delta.base;
+                    depth += 1;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// 增量明显小于完整内容时才值得保存
+    fn is_worth_storing(delta: &DeltaObject, content: &str) -> Result<bool> {
+        let encoded = serde_json::to_string(delta)
+            .context("Failed to serialize delta")?;
+        Ok(encoded.len() < content.len())
+    }
+
+    /// 对象当前占用的字节数
+    fn stored_size(&self, hash: &str) -> Result<u64> {
+        let full_path = self.object_path(hash)?;
+        let path = if full_path.exists() { full_path } else { Self::delta_path(&full_path) };
+
+        Ok(fs::metadata(&path)
+            .context("Failed to read object metadata")?
+            .len())
+    }
+
+    /// 写入完整快照
+    fn write_full(&self, hash: &str, content: &str) -> Result<()> {
+        let object_path = self.object_path(hash)?;
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create object directory")?;
+        }
+
+        atomic::write_file(&object_path, content)
+            .context("Failed to write object")
+    }
+
+    /// 写入增量快照
+    fn write_delta(&self, hash: &str, delta: &DeltaObject) -> Result<()> {
+        let delta_path = Self::delta_path(&self.object_path(hash)?);
+        if let Some(parent) = delta_path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create object directory")?;
+        }
+
+        let delta_json = serde_json::to_string(delta)
+            .context("Failed to serialize delta")?;
+        atomic::write_file(&delta_path, delta_json)
+            .context("Failed to write delta")
+    }
+
+    /// 读取增量快照，不存在时返回 None
+    fn read_delta(&self, hash: &str) -> Result<Option<DeltaObject>> {
+        let delta_path = Self::delta_path(&self.object_path(hash)?);
+        if !delta_path.exists() {
+            return Ok(None);
+        }
+
+        let delta_json = fs::read_to_string(&delta_path)
+            .context("Failed to read delta")?;
+        let delta = serde_json::from_str(&delta_json)
+            .context("Failed to parse delta")?;
+
+        Ok(Some(delta))
+    }
+
+    /// 获取对象文件路径
+    fn object_path(&self, hash: &str) -> Result<PathBuf> {
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow::anyhow!("Invalid object hash: {}", hash));
+        }
+
+        let hash = hash.to_ascii_lowercase();
+        Ok(self.objects_dir.join(&hash[..2]).join(&hash[2..]))
+    }
+
+    /// 获取增量文件路径
+    fn delta_path(object_path: &std::path::Path) -> PathBuf {
+        object_path.with_extension("delta")
+    }
+}
+
+/// 计算从 `base` 到 `target` 的行级增量
+fn compute_delta(base: &str, target: &str) -> Vec<DeltaOp> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let target_lines: Vec<&str> = target.split_inclusive('\n').collect();
+
+    let mut index: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, line) in base_lines.iter().enumerate() {
+        index.entry(line).or_default().push(i);
+    }
+
+    let match_len = |b: usize, t: usize| {
+        base_lines[b..]
+            .iter()
+            .zip(&target_lines[t..])
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+
+    let mut ops = Vec::new();
+    let mut pending = String::new();
+    let mut next_base = 0;
+    let mut t = 0;
+
+    while t < target_lines.len() {
+        // 优先延续上一段复制，其次在候选位置中找最长匹配
+        let mut best = (0, 0);
+        if next_base < base_lines.len() {
+            best = (next_base, match_len(next_base, t));
+        }
+        if let Some(candidates) = index.get(target_lines[t]) {
+            for &b in candidates.iter().take(MAX_MATCH_CANDIDATES) {
+                let len = match_len(b, t);
+                if len > best.1 {
+                    best = (b, len);
+                }
+            }
+        }
+
+        let (start, len) = best;
+        if len == 0 {
+            pending.push_str(target_lines[t]);
+            t += 1;
+            continue;
+        }
+
+        if !pending.is_empty() {
+            ops.push(DeltaOp::Insert { text: std::mem::take(&mut pending) });
+        }
+
+        match ops.last_mut() {
+            Some(DeltaOp::Copy { start: prev_start, len: prev_len })
+                if *prev_start + *prev_len == start =>
+            {
+                *prev_len += len;
+            }
+            _ => ops.push(DeltaOp::Copy { start, len }),
+        }
+
+        next_base = start + len;
+        t += len;
+    }
+
+    if !pending.is_empty() {
+        ops.push(DeltaOp::Insert { text: pending });
+    }
+
+    ops
+}
+
+/// 将增量应用到基础内容上
+fn apply_delta(base: &str, ops: &[DeltaOp]) -> Result<String> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let mut content = String::with_capacity(base.len());
+
+    for op in ops {
+        match op {
+            DeltaOp::Copy { start, len } => {
+                let end = start
+                    .checked_add(*len)
+                    .filter(|end| *end <= base_lines.len())
+                    .ok_or_else(|| anyhow::anyhow!("Delta copy out of range"))?;
+                for line in &base_lines[*start..end] {
+                    content.push_str(line);
+                }
+            }
+            DeltaOp::Insert { text } => content.push_str(text),
+        }
+    }
+
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// 简单的确定性伪随机数生成器
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound.max(1)
+        }
+    }
+
+    /// 生成一段较长的中英文混合文稿
+    fn manuscript(paragraphs: usize) -> String {
+        (0..paragraphs)
+            .map(|i| format!("第{}段。The rain kept falling on paragraph {} while 她在窗边等待。\n", i, i))
+            .collect()
+    }
+
+    /// 对文稿做一次随机编辑
+    fn edit(content: &str, rng: &mut Lcg, step: usize) -> String {
+        let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+        match rng.next(4) {
+            0 => {
+                let at = rng.next(lines.len() + 1);
+                lines.insert(at, format!("新增的一行 {} inserted.\n", step));
+            }
+            1 if lines.len() > 1 => {
+                let at = rng.next(lines.len());
+                lines.remove(at);
+            }
+            _ if !lines.is_empty() => {
+                let at = rng.next(lines.len());
+                lines[at] = format!("改写 {} —— {}", step, lines[at]);
+            }
+            _ => lines.push(format!("追加 {}\n", step)),
+        }
+        lines.concat()
+    }
+
+    fn build_history(versions: usize) -> Vec<String> {
+        let mut rng = Lcg(42);
+        let mut current = manuscript(2000);
+        let mut history = vec![current.clone()];
+        for step in 1..versions {
+            current = edit(&current, &mut rng, step);
+            history.push(current.clone());
+        }
+        history
+    }
+
+    fn dir_size(path: &std::path::Path) -> u64 {
+        fs::read_dir(path)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let metadata = entry.metadata().unwrap();
+                if metadata.is_dir() { dir_size(&entry.path()) } else { metadata.len() }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn delta_round_trips_edits() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let targets = [
+            "one\ntwo\nthree\nfour\n",
+            "zero\none\ntwo\nthree\nfour\n",
+            "one\nthree\nfour",
+            "four\nthree\ntwo\none\n",
+            "",
+            "完全不同的内容",
+        ];
+
+        for target in targets {
+            let ops = compute_delta(base, target);
+            assert_eq!(apply_delta(base, &ops).unwrap(), target);
+        }
+        assert_eq!(apply_delta("", &compute_delta("", "new\n")).unwrap(), "new\n");
+    }
+
+    #[test]
+    fn large_history_round_trips_with_deltas() {
+        let dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(dir.path().join("objects")).unwrap().with_max_chain_length(16);
+        let history = build_history(400);
+
+        let mut parent: Option<String> = None;
+        let mut hashes = Vec::new();
+        for content in &history {
+            let hash = store.put_with_base(content, parent.as_deref()).unwrap();
+            parent = Some(hash.clone());
+            hashes.push(hash);
+        }
+
+        for (hash, content) in hashes.iter().zip(&history) {
+            assert_eq!(&store.get(hash).unwrap(), content);
+            let depth = store.chain_depth(hash, "").unwrap().unwrap();
+            assert!(depth < 16);
+        }
+
+        let raw_size: u64 = history.iter().map(|c| c.len() as u64).sum();
+        assert!(dir_size(dir.path()) * 5 < raw_size);
+    }
+
+    #[test]
+    fn repack_rebuilds_chains_without_changing_content() {
+        let dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(dir.path().join("objects")).unwrap().with_max_chain_length(8);
+        let history = build_history(120);
+
+        let hashes: Vec<String> = history.iter().map(|c| store.put_with_base(c, None).unwrap()).collect();
+        let size_before = dir_size(dir.path());
+
+        let stats = store.repack(&hashes).unwrap();
+        assert_eq!(stats.objects, 120);
+        assert_eq!(stats.full_objects, 15);
+        assert_eq!(stats.delta_objects, 105);
+        assert!(stats.bytes_after < stats.bytes_before);
+        assert!(dir_size(dir.path()) < size_before);
+
+        for (hash, content) in hashes.iter().zip(&history) {
+            assert_eq!(&store.get(hash).unwrap(), content);
+        }
+
+        // 重复打包结果不变
+        let again = store.repack(&hashes).unwrap();
+        assert_eq!(again.bytes_after, stats.bytes_after);
+
+        // 反向打包同样不会形成环
+        let reversed: Vec<String> = hashes.iter().rev().cloned().collect();
+        store.repack(&reversed).unwrap();
+        for (hash, content) in hashes.iter().zip(&history) {
+            assert_eq!(&store.get(hash).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn duplicate_content_is_stored_once() {
+        let dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(dir.path().join("objects")).unwrap();
+
+        let first = store.put_with_base("same text", None).unwrap();
+        let second = store.put_with_base("same text", Some(&first)).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(store.get(&first).unwrap(), "same text");
+        assert!(store.get(&"0".repeat(64)).is_err());
+        assert!(store.get("../escape").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::file_system::{DocumentConfig, DocumentType};
+
+/// 目录树中的一个节点
+///
+/// 合计字数包含节点自身和所有下级文档，笔记不计入。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentNode {
+    #[serde(flatten)]
+    pub document: DocumentConfig,
+    pub total_word_count: u32,
+    pub total_character_count: u32,
+    pub children: Vec<DocumentNode>,
+}
+
+/// 文档列表的树形视图，按父文档分组，组内按 `order` 排序
+///
+/// 父文档不存在或父子关系成环时，文档按顶层文档处理，
+/// 这样损坏的 documents.json 也不会让文档从目录中消失。
+struct Outline {
+    parents: Vec<Option<usize>>,
+    children: HashMap<Option<usize>, Vec<usize>>,
+}
+
+impl Outline {
+    fn new(documents: &[DocumentConfig]) -> Self {
+        let positions: HashMap<&str, usize> = documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| (document.id.as_str(), index))
+            .collect();
+        let declared: Vec<Option<usize>> = documents
+            .iter()
+            .map(|document| document.parent_id.as_deref().and_then(|id| positions.get(id).copied()))
+            .collect();
+
+        // 沿父文档向上最多走 n 步，走不到顶层说明处在环中或环下
+        let is_rooted = |index: usize| {
+            let mut current = declared[index];
+            for _ in 0..documents.len() {
+                match current {
+                    None => return true,
+                    Some(parent) => current = declared[parent],
+                }
+            }
+            false
+        };
+        let parents: Vec<Option<usize>> = (0..documents.len())
+            .map(|index| if is_rooted(index) { declared[index] } else { None })
+            .collect();
+
+        let mut children: HashMap<Option<usize>, Vec<usize>> = HashMap::new();
+        for (index, parent) in parents.iter().enumerate() {
+            children.entry(*parent).or_default().push(index);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(|&index| documents[index].order);
+        }
+
+        Self { parents, children }
+    }
+
+    fn children_of(&self, parent: Option<usize>) -> &[usize] {
+        self.children.get(&parent).map_or(&[], Vec::as_slice)
+    }
+
+    /// 按阅读顺序（先序）排列的文档下标
+    fn reading_order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut stack: Vec<usize> = self.children_of(None).iter().rev().copied().collect();
+        while let Some(index) = stack.pop() {
+            order.push(index);
+            stack.extend(self.children_of(Some(index)).iter().rev());
+        }
+        order
+    }
+
+    fn is_ancestor(&self, ancestor: usize, index: usize) -> bool {
+        let mut current = self.parents[index];
+        while let Some(parent) = current {
+            if parent == ancestor {
+                return true;
+            }
+            current = self.parents[parent];
+        }
+        false
+    }
+}
+
+fn position(documents: &[DocumentConfig], document_id: &str) -> Result<usize> {
+    documents
+        .iter()
+        .position(|document| document.id == document_id)
+        .ok_or_else(|| anyhow::anyhow!("Document not found: {}", document_id))
+}
+
+/// 构建目录树，并汇总每个节点的字数
+pub fn tree(documents: &[DocumentConfig]) -> Vec<DocumentNode> {
+    fn build(documents: &[DocumentConfig], outline: &Outline, index: usize) -> DocumentNode {
+        let document = documents[index].clone();
+        let children: Vec<DocumentNode> = outline
+            .children_of(Some(index))
+            .iter()
+            .map(|&child| build(documents, outline, child))
+            .collect();
+
+        let (mut total_word_count, mut total_character_count) = if document.doc_type == DocumentType::Note {
+            (0, 0)
+        } else {
+            (document.word_count, document.character_count)
+        };
+        for child in &children {
+            total_word_count += child.total_word_count;
+            total_character_count += child.total_character_count;
+        }
+
+        DocumentNode { document, total_word_count, total_character_count, children }
+    }
+
+    let outline = Outline::new(documents);
+    outline
+        .children_of(None)
+        .iter()
+        .map(|&index| build(documents, &outline, index))
+        .collect()
+}
+
+/// 按阅读顺序排列所有文档：先父文档，再依次展开子文档
+pub fn reading_order(documents: &[DocumentConfig]) -> Vec<&DocumentConfig> {
+    Outline::new(documents)
+        .reading_order()
+        .into_iter()
+        .map(|index| &documents[index])
+        .collect()
+}
+
+/// `ancestor_id` 是否是 `document_id` 的上级文档
+pub fn is_ancestor(documents: &[DocumentConfig], ancestor_id: &str, document_id: &str) -> bool {
+    match (position(documents, ancestor_id), position(documents, document_id)) {
+        (Ok(ancestor), Ok(index)) => Outline::new(documents).is_ancestor(ancestor, index),
+        _ => false,
+    }
+}
+
+/// 新文档放在同级文档末尾时的顺序号
+pub fn next_order(documents: &[DocumentConfig], parent_id: Option<&str>) -> u32 {
+    documents
+        .iter()
+        .filter(|document| document.parent_id.as_deref() == parent_id)
+        .map(|document| document.order)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// 把文档连同下级文档移到 `parent_id` 之下的第 `index` 个位置，不指定位置时放在末尾
+pub fn move_document(documents: &mut [DocumentConfig], document_id: &str, parent_id: Option<&str>, index: Option<usize>) -> Result<()> {
+    let moved = position(documents, document_id)?;
+    let outline = Outline::new(documents);
+    let parent = match parent_id {
+        Some(parent_id) => {
+            let parent = position(documents, parent_id)?;
+            if parent == moved || outline.is_ancestor(moved, parent) {
+                return Err(anyhow::anyhow!("Cannot move a document under itself or one of its descendants"));
+            }
+            Some(parent)
+        }
+        None => None,
+    };
+
+    let mut siblings: Vec<usize> = outline
+        .children_of(parent)
+        .iter()
+        .copied()
+        .filter(|&sibling| sibling != moved)
+        .collect();
+    siblings.insert(index.unwrap_or(siblings.len()).min(siblings.len()), moved);
+
+    documents[moved].parent_id = parent_id.map(str::to_string);
+    for (order, &sibling) in siblings.iter().enumerate() {
+        documents[sibling].order = order as u32 + 1;
+    }
+    renumber(documents);
+    Ok(())
+}
+
+/// 按给出的顺序重排同一父文档下的子文档，必须恰好列出全部子文档
+pub fn reorder(documents: &mut [DocumentConfig], parent_id: Option<&str>, document_ids: &[String]) -> Result<()> {
+    let outline = Outline::new(documents);
+    let parent = parent_id.map(|parent_id| position(documents, parent_id)).transpose()?;
+
+    let mut expected: Vec<&str> = outline
+        .children_of(parent)
+        .iter()
+        .map(|&index| documents[index].id.as_str())
+        .collect();
+    let mut given: Vec<&str> = document_ids.iter().map(String::as_str).collect();
+    expected.sort_unstable();
+    given.sort_unstable();
+    if expected != given {
+        return Err(anyhow::anyhow!("The new order must list every child document exactly once"));
+    }
+
+    for (order, document_id) in document_ids.iter().enumerate() {
+        let index = position(documents, document_id)?;
+        documents[index].order = order as u32 + 1;
+    }
+    Ok(())
+}
+
+/// 每组同级文档重新从 1 开始连续编号，并清除指向不存在或成环的父文档引用
+pub fn renumber(documents: &mut [DocumentConfig]) {
+    let outline = Outline::new(documents);
+    for (index, parent) in outline.parents.iter().enumerate() {
+        if parent.is_none() {
+            documents[index].parent_id = None;
+        }
+    }
+    for siblings in outline.children.values() {
+        for (order, &index) in siblings.iter().enumerate() {
+            documents[index].order = order as u32 + 1;
+        }
+    }
+}
+
+/// 从列表中移除文档，它的子文档上移一级，占据它原来的位置
+pub fn remove_document(documents: &mut Vec<DocumentConfig>, document_id: &str) -> Option<DocumentConfig> {
+    let removed = position(documents, document_id).ok()?;
+    let outline = Outline::new(documents);
+    let parent = outline.parents[removed];
+
+    let mut siblings = Vec::new();
+    for &sibling in outline.children_of(parent) {
+        if sibling == removed {
+            siblings.extend_from_slice(outline.children_of(Some(removed)));
+        } else {
+            siblings.push(sibling);
+        }
+    }
+
+    let parent_id = parent.map(|parent| documents[parent].id.clone());
+    for (order, &sibling) in siblings.iter().enumerate() {
+        documents[sibling].parent_id = parent_id.clone();
+        documents[sibling].order = order as u32 + 1;
+    }
+    Some(documents.remove(removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn document(id: &str, parent_id: Option<&str>, order: u32, doc_type: DocumentType, word_count: u32) -> DocumentConfig {
+        DocumentConfig {
+            id: id.to_string(),
+            book_id: "book".to_string(),
+            parent_id: parent_id.map(str::to_string),
+            title: id.to_string(),
+            order,
+            doc_type,
+            created_at: Utc::now(),
+            last_modified: Utc::now(),
+            word_count,
+            character_count: word_count,
+            status: "draft".to_string(),
+        }
+    }
+
+    fn book() -> Vec<DocumentConfig> {
+        vec![
+            document("scene-2", Some("chapter-1"), 2, DocumentType::Scene, 30),
+            document("part-1", None, 1, DocumentType::Part, 5),
+            document("chapter-2", Some("part-1"), 2, DocumentType::Chapter, 100),
+            document("chapter-1", Some("part-1"), 1, DocumentType::Chapter, 10),
+            document("scene-1", Some("chapter-1"), 1, DocumentType::Scene, 20),
+            document("notes", None, 2, DocumentType::Note, 1000),
+        ]
+    }
+
+    fn ids(documents: &[&DocumentConfig]) -> Vec<String> {
+        documents.iter().map(|document| document.id.clone()).collect()
+    }
+
+    #[test]
+    fn builds_tree_in_reading_order_with_rolled_up_counts() {
+        let documents = book();
+        assert_eq!(
+            ids(&reading_order(&documents)),
+            vec!["part-1", "chapter-1", "scene-1", "scene-2", "chapter-2", "notes"]
+        );
+
+        let tree = tree(&documents);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].total_word_count, 5 + 10 + 20 + 30 + 100);
+        assert_eq!(tree[0].children[0].total_word_count, 60);
+        assert_eq!(tree[1].total_word_count, 0); // 笔记不计入
+
+        assert!(is_ancestor(&documents, "part-1", "scene-2"));
+        assert!(!is_ancestor(&documents, "chapter-2", "scene-2"));
+    }
+
+    #[test]
+    fn moves_reorders_and_rejects_cycles() {
+        let mut documents = book();
+
+        move_document(&mut documents, "scene-2", Some("chapter-2"), None).unwrap();
+        move_document(&mut documents, "chapter-2", Some("part-1"), Some(0)).unwrap();
+        assert_eq!(
+            ids(&reading_order(&documents)),
+            vec!["part-1", "chapter-2", "scene-2", "chapter-1", "scene-1", "notes"]
+        );
+
+        assert!(move_document(&mut documents, "part-1", Some("scene-2"), None).is_err());
+        assert!(move_document(&mut documents, "part-1", Some("part-1"), None).is_err());
+
+        reorder(&mut documents, None, &["notes".to_string(), "part-1".to_string()]).unwrap();
+        assert_eq!(reading_order(&documents)[0].id, "notes");
+        assert!(reorder(&mut documents, None, &["notes".to_string()]).is_err());
+        assert!(reorder(&mut documents, None, &["notes".to_string(), "notes".to_string()]).is_err());
+    }
+
+    #[test]
+    fn removal_promotes_children_and_renumber_repairs_broken_parents() {
+        let mut documents = book();
+        remove_document(&mut documents, "chapter-1").unwrap();
+        assert_eq!(
+            ids(&reading_order(&documents)),
+            vec!["part-1", "scene-1", "scene-2", "chapter-2", "notes"]
+        );
+        let orders: Vec<u32> = reading_order(&documents).iter().map(|document| document.order).collect();
+        assert_eq!(orders, vec![1, 1, 2, 3, 2]);
+
+        // 指向不存在的父文档、互为父子的文档都回到顶层
+        let mut broken = vec![
+            document("orphan", Some("missing"), 5, DocumentType::Chapter, 0),
+            document("a", Some("b"), 1, DocumentType::Chapter, 0),
+            document("b", Some("a"), 1, DocumentType::Chapter, 0),
+        ];
+        assert_eq!(reading_order(&broken).len(), 3);
+        renumber(&mut broken);
+        assert!(broken.iter().all(|document| document.parent_id.is_none()));
+        let mut orders: Vec<u32> = broken.iter().map(|document| document.order).collect();
+        orders.sort_unstable();
+        assert_eq!(orders, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,946 @@
+use anyhow::{Context, Result};
+use printpdf::{IndirectFontRef, Mm, PdfDocument};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::export::{self, Chapter};
+use crate::file_system::{BookConfig, BookSettings};
+use crate::fonts::{self, Font, FontLibrary, FontStyle};
+use crate::text;
+
+/// 每毫米的磅数
+const PT_PER_MM: f32 = 72.0 / 25.4;
+/// 编辑器像素换算为磅（96 dpi）
+const PT_PER_PX: f32 = 0.75;
+
+/// 不能出现在行首的标点
+const NO_BREAK_BEFORE: &str = "，。、；：？！）》」』】〕〉’”…—～·,.;:?!)]}%\"'-";
+/// 不能出现在行尾的标点
+const NO_BREAK_AFTER: &str = "（《「『【〔〈‘“([{";
+
+/// 成书尺寸
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrimSize {
+    A4,
+    A5,
+    Letter,
+    UsTrade, // 6 × 9 英寸
+    Digest,  // 5.5 × 8.5 英寸
+    Pocket,  // 4.25 × 6.87 英寸
+    Custom { width_mm: f32, height_mm: f32 },
+}
+
+impl TrimSize {
+    /// 页面宽高（毫米）
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            Self::A4 => (210.0, 297.0),
+            Self::A5 => (148.0, 210.0),
+            Self::Letter => (215.9, 279.4),
+            Self::UsTrade => (152.4, 228.6),
+            Self::Digest => (139.7, 215.9),
+            Self::Pocket => (108.0, 174.5),
+            Self::Custom { width_mm, height_mm } => (width_mm.max(50.0), height_mm.max(50.0)),
+        }
+    }
+}
+
+/// 页边距（毫米），内侧为装订侧，左右页镜像
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfMargins {
+    pub top: f32,
+    pub bottom: f32,
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl Default for PdfMargins {
+    fn default() -> Self {
+        Self { top: 20.0, bottom: 22.0, inner: 22.0, outer: 18.0 }
+    }
+}
+
+/// PDF 导出选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub trim_size: TrimSize,
+    pub margins: PdfMargins,
+    pub font_family: Option<String>, // 默认使用书籍设置中的字体
+    pub font_path: Option<String>,   // 直接指定 TrueType 字体文件
+    pub font_size: Option<f32>,      // 磅，默认按书籍设置换算
+    pub line_height: Option<f32>,    // 磅，默认按书籍设置换算
+    pub running_headers: bool,       // 左页页眉为书名，右页为章节名
+    pub page_numbers: bool,
+    pub chapter_page_break: bool, // 每章另起一页
+    pub title_page: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            trim_size: TrimSize::A5,
+            margins: PdfMargins::default(),
+            font_family: None,
+            font_path: None,
+            font_size: None,
+            line_height: None,
+            running_headers: true,
+            page_numbers: true,
+            chapter_page_break: true,
+            title_page: true,
+        }
+    }
+}
+
+/// 生成 PDF 文件内容
+///
+/// 字体以 TrueType 轮廓（FontFile2）嵌入，只保留用到的字形，因此不支持 CFF 轮廓的字体。
+pub fn build(config: &BookConfig, chapters: &[Chapter], options: &PdfOptions) -> Result<Vec<u8>> {
+    let samples: Vec<&str> = std::iter::once(config.name.as_str())
+        .chain(chapters.iter().flat_map(|chapter| [chapter.title.as_str(), chapter.content.as_str()]))
+        .collect();
+    let needs_cjk = samples.iter().any(|sample| fonts::needs_cjk(sample));
+    let language = export::detect_language(&samples);
+
+    let mut font_set = FontSet::load(options, &config.settings, needs_cjk)?;
+    let size = options
+        .font_size
+        .unwrap_or(config.settings.font_size as f32 * PT_PER_PX)
+        .clamp(6.0, 36.0);
+    let leading = options
+        .line_height
+        .unwrap_or(config.settings.line_height as f32 * PT_PER_PX)
+        .max(size);
+
+    let (page_width, page_height) = options.trim_size.dimensions();
+    let margins = options.margins;
+    let text_width = (page_width - margins.inner - margins.outer).max(20.0) * PT_PER_MM;
+    let text_height = (page_height - margins.top - margins.bottom).max(20.0) * PT_PER_MM;
+
+    let mut layout = Layout::new(&mut font_set, text_width, text_height, size, leading);
+    // 中日韩文本每段都首行缩进，西文在标题和分隔符之后的段落不缩进
+    layout.indent_all = language != "en";
+
+    if options.title_page {
+        layout.title_page(config);
+    }
+
+    let mut bookmarks = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        let page_break = index == 0 || options.chapter_page_break;
+        let page = layout.chapter(index, chapter, page_break);
+        bookmarks.push((chapter.title.clone(), page));
+    }
+
+    let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+    layout.decorate(&config.name, &titles, options);
+    let pages = layout.finish();
+
+    render(config, &pages, &font_set, options, &bookmarks)
+}
+
+/// 把排好的页面写成 PDF
+fn render(
+    config: &BookConfig,
+    pages: &[Page],
+    font_set: &FontSet,
+    options: &PdfOptions,
+    bookmarks: &[(String, usize)],
+) -> Result<Vec<u8>> {
+    let (width, height) = options.trim_size.dimensions();
+    let margins = options.margins;
+
+    let (document, first_page, first_layer) = PdfDocument::new(config.name.as_str(), Mm(width), Mm(height), "Text");
+    let document = document.with_author(config.author.as_str()).with_creator("BranchWrite");
+
+    // 只嵌入实际用到的字体，且只保留用到的字形
+    let mut used_chars: Vec<BTreeSet<char>> = vec![BTreeSet::new(); font_set.fonts.len()];
+    for item in pages.iter().flat_map(|page| &page.items) {
+        used_chars[item.font].extend(item.text.chars());
+    }
+    let mut font_refs: Vec<Option<IndirectFontRef>> = vec![None; font_set.fonts.len()];
+    for (index, chars) in used_chars.iter().enumerate().filter(|(_, chars)| !chars.is_empty()) {
+        let font = &font_set.fonts[index];
+        let data = font.subset(chars.iter().copied()).unwrap_or_else(|e| {
+            log::warn!("Failed to subset font, embedding it whole: {}", e);
+            font.data.clone()
+        });
+        let font_ref = document
+            .add_external_font(Cursor::new(data))
+            .map_err(|e| anyhow::anyhow!("Failed to embed font: {}", e))?;
+        font_refs[index] = Some(font_ref);
+    }
+
+    let mut page_indices = Vec::with_capacity(pages.len());
+    for (index, page) in pages.iter().enumerate() {
+        let (page_index, layer_index) = if index == 0 {
+            (first_page, first_layer)
+        } else {
+            document.add_page(Mm(width), Mm(height), "Text")
+        };
+        page_indices.push(page_index);
+        let layer = document.get_page(page_index).get_layer(layer_index);
+
+        // 第一页是右页，装订侧在左
+        let left = if index % 2 == 0 { margins.inner } else { margins.outer };
+        for item in &page.items {
+            if let Some(font_ref) = &font_refs[item.font] {
+                let x = left + item.x / PT_PER_MM;
+                let y = height - margins.top - item.y / PT_PER_MM;
+                layer.use_text(item.text.as_str(), item.size, Mm(x), Mm(y), font_ref);
+            }
+        }
+    }
+
+    for (title, page) in bookmarks {
+        document.add_bookmark(title.as_str(), page_indices[*page]);
+    }
+
+    document
+        .save_to_bytes()
+        .map_err(|e| anyhow::anyhow!("Failed to write PDF: {}", e))
+}
+
+/// 排版用到的字体：四种样式和中日韩后备字体
+struct FontSet {
+    fonts: Vec<Font>,
+    styles: [usize; 4], // 按 FontStyle 顺序排列的字体序号
+    fallback: Option<usize>,
+}
+
+impl FontSet {
+    fn load(options: &PdfOptions, settings: &BookSettings, needs_cjk: bool) -> Result<Self> {
+        let library = FontLibrary::scan();
+        let family = options
+            .font_family
+            .as_deref()
+            .filter(|family| !family.trim().is_empty())
+            .unwrap_or(&settings.font_family);
+
+        let mut fonts = Vec::new();
+        let mut styles = [0; 4];
+        match options.font_path.as_deref().filter(|path| !path.trim().is_empty()) {
+            Some(path) => fonts.push(Font::load(Path::new(path))?),
+            None => {
+                let regular = library
+                    .load_stack(family, FontStyle::Regular)
+                    .or_else(|| library.load_any())
+                    .or_else(|| library.load_cjk_fallback())
+                    .context("No TrueType font found for PDF export; choose a font file")?;
+                fonts.push(regular);
+
+                for style in [FontStyle::Bold, FontStyle::Italic, FontStyle::BoldItalic] {
+                    if let Some(font) = library.load_stack(family, style) {
+                        styles[style as usize] = fonts.len();
+                        fonts.push(font);
+                    }
+                }
+            }
+        }
+
+        let mut fallback = None;
+        if needs_cjk && fonts[0].advance('中').is_none() {
+            if let Some(font) = library.load_cjk_fallback() {
+                fallback = Some(fonts.len());
+                fonts.push(font);
+            } else {
+                log::warn!("No CJK font found; CJK text will be missing from the PDF");
+            }
+        }
+
+        Ok(Self { fonts, styles, fallback })
+    }
+
+    /// 选择能显示该字符的字体，返回字体序号和字宽（em）
+    fn glyph(&mut self, c: char, style: FontStyle) -> (usize, f32) {
+        let styled = self.styles[style as usize];
+        for index in [Some(styled), Some(self.styles[0]), self.fallback].into_iter().flatten() {
+            if let Some(advance) = self.fonts[index].advance(c) {
+                return (index, advance);
+            }
+        }
+        (styled, 0.5)
+    }
+}
+
+/// 行内文字片段
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    text: String,
+    bold: bool,
+    italic: bool,
+}
+
+impl Piece {
+    fn plain(text: &str) -> Self {
+        Self { text: text.to_string(), bold: false, italic: false }
+    }
+
+    fn style(&self) -> FontStyle {
+        FontStyle::from_flags(self.bold, self.italic)
+    }
+}
+
+/// 排好的一行中使用同一字体的一段文字
+#[derive(Debug, Clone, PartialEq)]
+struct Run {
+    x: f32, // 相对行首，磅
+    text: String,
+    font: usize,
+}
+
+/// 排好的一行
+#[derive(Debug, Clone, Default)]
+struct Line {
+    runs: Vec<Run>,
+    width: f32, // 含首行缩进
+}
+
+impl Line {
+    fn push(&mut self, text: &str, style: FontStyle, size: f32, measure: &mut impl FnMut(char, FontStyle) -> (usize, f32)) {
+        for c in text.chars() {
+            let (font, advance) = measure(c, style);
+            match self.runs.last_mut() {
+                Some(run) if run.font == font => run.text.push(c),
+                _ => self.runs.push(Run { x: self.width, text: c.to_string(), font }),
+            }
+            self.width += advance * size;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnitKind {
+    Word,
+    Space,
+    Newline,
+}
+
+/// 折行时不可拆开的最小单位
+struct Unit<'a> {
+    parts: Vec<(&'a str, FontStyle)>,
+    kind: UnitKind,
+}
+
+/// 把片段切成折行单位：中日韩文字逐字可断，标点按避头尾规则粘在相邻文字上
+fn units(pieces: &[Piece]) -> Vec<Unit<'_>> {
+    let mut units: Vec<Unit> = Vec::new();
+    let mut glue_next = false;
+
+    for piece in pieces {
+        for (index, token) in text::split_words(&piece.text).into_iter().enumerate() {
+            let first = token.chars().next().unwrap_or(' ');
+            let kind = if token == "\n" {
+                UnitKind::Newline
+            } else if first.is_whitespace() {
+                UnitKind::Space
+            } else {
+                UnitKind::Word
+            };
+
+            let previous = units.last().filter(|unit| unit.kind == UnitKind::Word);
+            let after_word = previous.is_some();
+            // 加粗等样式切换处的同一个西文单词不拆开
+            let continues_word = index == 0
+                && previous
+                    .and_then(|unit| unit.parts.last())
+                    .and_then(|(text, _)| text.chars().last())
+                    .is_some_and(|last| is_latin(last) && is_latin(first));
+            let joins = kind == UnitKind::Word
+                && after_word
+                && (glue_next || continues_word || NO_BREAK_BEFORE.contains(first));
+
+            match units.last_mut() {
+                Some(unit) if joins => unit.parts.push((token, piece.style())),
+                _ => units.push(Unit { parts: vec![(token, piece.style())], kind }),
+            }
+
+            let last = token.chars().last().unwrap_or(' ');
+            let opening_quote = matches!(token, "\"" | "'") && !after_word;
+            glue_next = kind == UnitKind::Word && (NO_BREAK_AFTER.contains(last) || opening_quote);
+        }
+    }
+
+    units
+}
+
+fn is_latin(c: char) -> bool {
+    c.is_alphanumeric() && !text::is_cjk(c)
+}
+
+/// 把片段折成不超过 `width` 的行，首行缩进 `indent`（均为磅）
+fn break_lines(
+    pieces: &[Piece],
+    width: f32,
+    indent: f32,
+    size: f32,
+    measure: &mut impl FnMut(char, FontStyle) -> (usize, f32),
+) -> Vec<Line> {
+    let mut text_width = |parts: &[(&str, FontStyle)]| -> f32 {
+        parts
+            .iter()
+            .flat_map(|(text, style)| text.chars().map(move |c| (c, *style)))
+            .map(|(c, style)| measure(c, style).1 * size)
+            .sum()
+    };
+
+    let units = units(pieces);
+    let widths: Vec<f32> = units.iter().map(|unit| text_width(&unit.parts)).collect();
+
+    let mut lines = Vec::new();
+    let mut line = Line { width: indent, ..Line::default() };
+    let mut empty = true;
+    let mut space: Option<(&Unit, f32)> = None; // 行中待定的空白
+
+    for (unit, unit_width) in units.iter().zip(widths) {
+        match unit.kind {
+            UnitKind::Newline => {
+                lines.push(std::mem::take(&mut line));
+                empty = true;
+                space = None;
+                continue;
+            }
+            UnitKind::Space => {
+                if !empty {
+                    space = Some((unit, unit_width));
+                }
+                continue;
+            }
+            UnitKind::Word => {}
+        }
+
+        let space_width = space.map_or(0.0, |(_, width)| width);
+        if !empty && line.width + space_width + unit_width > width {
+            lines.push(std::mem::take(&mut line));
+            empty = true;
+            space = None;
+        }
+        if let Some((space, _)) = space.take() {
+            for (text, style) in &space.parts {
+                line.push(text, *style, size, measure);
+            }
+        }
+
+        // 比整行还宽的单位（如长网址）逐字折行
+        let overflows = line.width + unit_width > width;
+        for (text, style) in &unit.parts {
+            for c in text::split_chars(text) {
+                if overflows && !empty {
+                    let advance = c.chars().map(|c| measure(c, *style).1 * size).sum::<f32>();
+                    if line.width + advance > width {
+                        lines.push(std::mem::take(&mut line));
+                    }
+                }
+                line.push(c, *style, size, measure);
+                empty = false;
+            }
+        }
+    }
+
+    if !empty {
+        lines.push(line);
+    }
+    lines
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BlockKind {
+    Heading(u8),
+    Body,
+    Quote,
+    Item { marker: String, depth: usize }, // 同一列表项的后续段落没有编号
+    Code,
+    SceneBreak,
+}
+
+/// 块级元素
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    kind: BlockKind,
+    pieces: Vec<Piece>,
+}
+
+/// 把 Markdown 转为块级元素
+struct BlockWriter {
+    blocks: Vec<Block>,
+    current: Option<Block>,
+    italic: usize,
+    bold: usize,
+    quote_depth: usize,
+    lists: Vec<Option<u64>>, // 有序列表的下一个编号
+    in_code_block: bool,
+}
+
+impl BlockWriter {
+    fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            current: None,
+            italic: 0,
+            bold: 0,
+            quote_depth: 0,
+            lists: Vec::new(),
+            in_code_block: false,
+        }
+    }
+
+    /// 当前块级结构对应的正文类型
+    fn block_kind(&self) -> BlockKind {
+        if self.in_code_block {
+            BlockKind::Code
+        } else if !self.lists.is_empty() {
+            BlockKind::Item { marker: String::new(), depth: self.lists.len() }
+        } else if self.quote_depth > 0 {
+            BlockKind::Quote
+        } else {
+            BlockKind::Body
+        }
+    }
+
+    fn open(&mut self, kind: BlockKind) {
+        if self.current.is_none() {
+            self.current = Some(Block { kind, pieces: Vec::new() });
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(block) = self.current.take() {
+            self.blocks.push(block);
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let kind = self.block_kind();
+        self.open(kind);
+        let (bold, italic) = (self.bold > 0, self.italic > 0);
+        if let Some(block) = self.current.as_mut() {
+            block.pieces.push(Piece { text: text.to_string(), bold, italic });
+        }
+    }
+
+    fn markdown(mut self, markdown: &str) -> Vec<Block> {
+        for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH) {
+            match event {
+                Event::Start(Tag::Heading { level, .. }) => {
+                    self.close();
+                    self.open(BlockKind::Heading(match level {
+                        HeadingLevel::H1 => 1,
+                        HeadingLevel::H2 => 2,
+                        _ => 3,
+                    }));
+                }
+                Event::End(TagEnd::Heading(_)) | Event::End(TagEnd::Paragraph) => self.close(),
+                Event::Start(Tag::Paragraph) => {
+                    let kind = self.block_kind();
+                    self.open(kind);
+                }
+                Event::Start(Tag::BlockQuote(_)) => {
+                    self.close();
+                    self.quote_depth += 1;
+                }
+                Event::End(TagEnd::BlockQuote(_)) => {
+                    self.close();
+                    self.quote_depth = self.quote_depth.saturating_sub(1);
+                }
+                Event::Start(Tag::List(start)) => {
+                    self.close();
+                    self.lists.push(start);
+                }
+                Event::End(TagEnd::List(_)) => {
+                    self.close();
+                    self.lists.pop();
+                }
+                Event::Start(Tag::Item) => {
+                    self.close();
+                    let marker = match self.lists.last_mut() {
+                        Some(Some(number)) => {
+                            *number += 1;
+                            format!("{}.", *number - 1)
+                        }
+                        _ => "•".to_string(),
+                    };
+                    self.open(BlockKind::Item { marker, depth: self.lists.len() });
+                }
+                Event::End(TagEnd::Item) => self.close(),
+                Event::Start(Tag::CodeBlock(_)) => {
+                    self.close();
+                    self.in_code_block = true;
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    // 代码块末尾的换行不需要保留
+                    if let Some(piece) = self.current.as_mut().and_then(|block| block.pieces.last_mut()) {
+                        piece.text.truncate(piece.text.trim_end_matches('\n').len());
+                    }
+                    self.close();
+                    self.in_code_block = false;
+                }
+                Event::Start(Tag::Emphasis) => self.italic += 1,
+                Event::End(TagEnd::Emphasis) => self.italic = self.italic.saturating_sub(1),
+                Event::Start(Tag::Strong) => self.bold += 1,
+                Event::End(TagEnd::Strong) => self.bold = self.bold.saturating_sub(1),
+                Event::Text(text) if self.in_code_block => self.text(&text.replace('\t', "    ")),
+                Event::Text(text) | Event::Code(text) | Event::Html(text) | Event::InlineHtml(text) => self.text(&text),
+                Event::SoftBreak => self.text(" "),
+                Event::HardBreak => self.text("\n"),
+                // 分隔线作为场景分隔符
+                Event::Rule => {
+                    self.close();
+                    self.blocks.push(Block { kind: BlockKind::SceneBreak, pieces: Vec::new() });
+                }
+                _ => {}
+            }
+        }
+
+        self.close();
+        self.blocks
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+}
+
+/// 页面上的一段文字（磅，x 相对版心左边，y 为基线到版心顶边的距离）
+struct TextItem {
+    x: f32,
+    y: f32,
+    text: String,
+    font: usize,
+    size: f32,
+}
+
+/// 排好的一页
+struct Page {
+    items: Vec<TextItem>,
+    chapter: Option<usize>,
+    opening: bool,     // 书名页或章首页，不印页眉
+    page_number: bool, // 书名页不印页码
+}
+
+/// 按版心尺寸把内容排成页面
+struct Layout<'a> {
+    fonts: &'a mut FontSet,
+    width: f32,  // 版心宽（磅）
+    height: f32, // 版心高（磅）
+    size: f32,
+    leading: f32,
+    indent_all: bool, // 每段都首行缩进
+    indent_next: bool,
+    pages: Vec<Page>,
+    y: f32, // 下一行顶边到版心顶边的距离
+    chapter: Option<usize>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(fonts: &'a mut FontSet, width: f32, height: f32, size: f32, leading: f32) -> Self {
+        Self {
+            fonts,
+            width,
+            height,
+            size,
+            leading,
+            indent_all: false,
+            indent_next: false,
+            pages: Vec::new(),
+            y: 0.0,
+            chapter: None,
+        }
+    }
+
+    fn finish(mut self) -> Vec<Page> {
+        if self.pages.is_empty() {
+            self.new_page(true);
+        }
+        self.pages
+    }
+
+    fn new_page(&mut self, opening: bool) {
+        self.pages.push(Page { items: Vec::new(), chapter: self.chapter, opening, page_number: true });
+        self.y = 0.0;
+    }
+
+    /// 当前页放不下 `height` 时换页
+    fn ensure(&mut self, height: f32) {
+        if self.pages.is_empty() || (self.y > 0.0 && self.y + height > self.height) {
+            self.new_page(false);
+        }
+    }
+
+    /// 留出空白，页首不留
+    fn skip(&mut self, amount: f32) {
+        if self.y > 0.0 {
+            self.y += amount;
+        }
+    }
+
+    fn break_lines(&mut self, pieces: &[Piece], width: f32, indent: f32, size: f32) -> Vec<Line> {
+        let fonts = &mut *self.fonts;
+        break_lines(pieces, width, indent, size, &mut |c, style| fonts.glyph(c, style))
+    }
+
+    fn place(&mut self, lines: Vec<Line>, x: f32, size: f32, leading: f32, align: Align) {
+        for line in lines {
+            self.ensure(leading);
+            let baseline = self.y + leading / 2.0 + size * 0.3;
+            let offset = match align {
+                Align::Left => x,
+                Align::Center => (self.width - line.width) / 2.0,
+            };
+
+            if let Some(page) = self.pages.last_mut() {
+                for run in line.runs.into_iter().filter(|run| !run.text.trim().is_empty()) {
+                    page.items.push(TextItem { x: offset + run.x, y: baseline, text: run.text, font: run.font, size });
+                }
+            }
+            self.y += leading;
+        }
+    }
+
+    /// 书名页：书名和作者居中
+    fn title_page(&mut self, config: &BookConfig) {
+        self.new_page(true);
+        if let Some(page) = self.pages.last_mut() {
+            page.page_number = false;
+        }
+        self.y = self.height / 3.0;
+
+        let size = self.size * 2.2;
+        let title = Piece { bold: true, ..Piece::plain(&config.name) };
+        let lines = self.break_lines(&[title], self.width, 0.0, size);
+        self.place(lines, 0.0, size, size * 1.3, Align::Center);
+
+        if !config.author.trim().is_empty() {
+            self.skip(self.leading * 2.0);
+            let size = self.size * 1.2;
+            let lines = self.break_lines(&[Piece::plain(&config.author)], self.width, 0.0, size);
+            self.place(lines, 0.0, size, self.leading * 1.2, Align::Center);
+        }
+    }
+
+    /// 排一章，返回章首所在页
+    fn chapter(&mut self, index: usize, chapter: &Chapter, page_break: bool) -> usize {
+        self.chapter = Some(index);
+        if page_break || self.pages.is_empty() {
+            // 章首页下沉
+            self.new_page(true);
+            self.y = self.height / 6.0;
+        } else {
+            self.skip(self.leading * 2.0);
+            self.ensure(self.leading * 4.0);
+        }
+        let page = self.pages.len() - 1;
+
+        let mut blocks = BlockWriter::new().markdown(&chapter.content);
+        if !chapter.starts_with_heading() {
+            blocks.insert(0, Block { kind: BlockKind::Heading(1), pieces: vec![Piece::plain(&chapter.title)] });
+        }
+
+        self.indent_next = false;
+        for block in blocks {
+            self.block(block);
+        }
+        page
+    }
+
+    fn block(&mut self, block: Block) {
+        let (size, leading, width) = (self.size, self.leading, self.width);
+        match block.kind {
+            BlockKind::Heading(level) => {
+                let scale = match level {
+                    1 => 1.6,
+                    2 => 1.3,
+                    _ => 1.1,
+                };
+                let pieces: Vec<Piece> = block.pieces.into_iter().map(|piece| Piece { bold: true, ..piece }).collect();
+                let lines = self.break_lines(&pieces, width, 0.0, size * scale);
+
+                self.skip(leading);
+                // 标题后至少跟两行正文，不单独留在页尾
+                self.ensure(lines.len() as f32 * leading * scale + leading * 2.0);
+                let align = if level == 1 { Align::Center } else { Align::Left };
+                self.place(lines, 0.0, size * scale, leading * scale, align);
+                self.skip(leading * if level == 1 { 1.0 } else { 0.5 });
+                self.indent_next = false;
+            }
+            BlockKind::Body => {
+                let indent = if self.indent_next || self.indent_all { size * 2.0 } else { 0.0 };
+                let lines = self.break_lines(&block.pieces, width, indent, size);
+                if lines.len() > 1 {
+                    self.ensure(leading * 2.0);
+                }
+                self.place(lines, 0.0, size, leading, Align::Left);
+                self.indent_next = true;
+            }
+            BlockKind::Quote => {
+                let inset = size * 2.0;
+                let pieces: Vec<Piece> = block.pieces.into_iter().map(|piece| Piece { italic: !piece.italic, ..piece }).collect();
+                let lines = self.break_lines(&pieces, width - inset * 2.0, 0.0, size);
+                self.skip(leading * 0.5);
+                self.place(lines, inset, size, leading, Align::Left);
+                self.skip(leading * 0.5);
+                self.indent_next = false;
+            }
+            BlockKind::Item { marker, depth } => {
+                let hang = size * 1.5 * depth as f32;
+                let mut lines = self.break_lines(&block.pieces, width - hang, 0.0, size);
+                if !marker.is_empty() {
+                    let mut marker_lines = self.break_lines(&[Piece::plain(&marker)], f32::INFINITY, 0.0, size);
+                    if lines.is_empty() {
+                        lines.push(Line::default());
+                    }
+                    for mut run in marker_lines.drain(..).flat_map(|line| line.runs) {
+                        run.x -= size * 1.5;
+                        lines[0].runs.insert(0, run);
+                    }
+                }
+                self.place(lines, hang, size, leading, Align::Left);
+                self.indent_next = false;
+            }
+            BlockKind::Code => {
+                let inset = size * 2.0;
+                let lines = self.break_lines(&block.pieces, width - inset, 0.0, size * 0.9);
+                self.skip(leading * 0.5);
+                self.place(lines, inset, size * 0.9, leading * 0.9, Align::Left);
+                self.skip(leading * 0.5);
+                self.indent_next = false;
+            }
+            BlockKind::SceneBreak => {
+                let lines = self.break_lines(&[Piece::plain("* * *")], width, 0.0, size);
+                self.skip(leading * 0.5);
+                self.place(lines, 0.0, size, leading, Align::Center);
+                self.skip(leading * 0.5);
+                self.indent_next = false;
+            }
+        }
+    }
+
+    /// 加上页眉和页码
+    fn decorate(&mut self, book_title: &str, chapter_titles: &[&str], options: &PdfOptions) {
+        let size = self.size * 0.8;
+        let header_y = -options.margins.top * PT_PER_MM * 0.45;
+        let footer_y = self.height + options.margins.bottom * PT_PER_MM * 0.55;
+
+        for index in 0..self.pages.len() {
+            let page = &self.pages[index];
+            let mut marks = Vec::new();
+            if options.running_headers && !page.opening {
+                // 页码从 1 开始，偶数页是左页
+                let header = match page.chapter {
+                    Some(chapter) if index % 2 == 0 => chapter_titles[chapter],
+                    _ => book_title,
+                };
+                marks.push((header.to_string(), header_y));
+            }
+            if options.page_numbers && page.page_number {
+                marks.push(((index + 1).to_string(), footer_y));
+            }
+
+            for (mark, y) in marks {
+                let lines = self.break_lines(&[Piece::plain(&mark)], f32::INFINITY, 0.0, size);
+                let page = &mut self.pages[index];
+                for line in lines {
+                    let offset = (self.width - line.width) / 2.0;
+                    for run in line.runs {
+                        page.items.push(TextItem { x: offset + run.x, y, text: run.text, font: run.font, size });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 等宽测量：中日韩文字 1em，其余 0.5em
+    fn measure(c: char, _style: FontStyle) -> (usize, f32) {
+        (0, if text::is_cjk(c) || "，。“”".contains(c) { 1.0 } else { 0.5 })
+    }
+
+    fn line_texts(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.runs.iter().map(|run| run.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn breaks_latin_at_spaces_and_keeps_punctuation_attached() {
+        let pieces = [Piece::plain("The quick brown fox, jumps")];
+        // 每个字符 5 磅，一行最多 12 个字符
+        let lines = break_lines(&pieces, 60.0, 0.0, 10.0, &mut measure);
+        assert_eq!(line_texts(&lines), vec!["The quick", "brown fox,", "jumps"]);
+    }
+
+    #[test]
+    fn cjk_lines_do_not_start_with_closing_punctuation() {
+        let pieces = [Piece::plain("他说：“走吧。”我们走了。")];
+        let lines = break_lines(&pieces, 40.0, 0.0, 10.0, &mut measure);
+        for text in line_texts(&lines) {
+            let first = text.chars().next().unwrap();
+            assert!(!NO_BREAK_BEFORE.contains(first), "line starts with {first}: {text}");
+        }
+        assert_eq!(line_texts(&lines).concat(), "他说：“走吧。”我们走了。");
+    }
+
+    #[test]
+    fn indent_applies_to_first_line_and_words_span_style_changes() {
+        let pieces = [
+            Piece { bold: true, ..Piece::plain("bold") },
+            Piece::plain("face words"),
+        ];
+        let lines = break_lines(&pieces, 60.0, 20.0, 10.0, &mut measure);
+        assert_eq!(line_texts(&lines), vec!["boldface", "words"]);
+        assert_eq!(lines[0].runs[0].x, 20.0);
+        assert_eq!(lines[1].runs[0].x, 0.0);
+    }
+
+    #[test]
+    fn markdown_maps_to_blocks() {
+        let blocks = BlockWriter::new().markdown("# 第一章\n\n正文 *强调*\n\n---\n\n> 引文\n\n1. 一\n2. 二\n");
+        let kinds: Vec<&BlockKind> = blocks.iter().map(|block| &block.kind).collect();
+        assert_eq!(kinds, vec![
+            &BlockKind::Heading(1),
+            &BlockKind::Body,
+            &BlockKind::SceneBreak,
+            &BlockKind::Quote,
+            &BlockKind::Item { marker: "1.".into(), depth: 1 },
+            &BlockKind::Item { marker: "2.".into(), depth: 1 },
+        ]);
+        assert!(blocks[1].pieces[1].italic);
+    }
+
+    #[test]
+    fn builds_pdf_with_system_font() {
+        if FontLibrary::scan().load_any().is_none() {
+            return; // 测试环境没有可用字体
+        }
+
+        let mut config = BookConfig::sample("Night Rain", "A. Writer");
+        config.settings.font_family = "serif".into();
+        let paragraph = "It was raining. ".repeat(40);
+        let chapters: Vec<Chapter> = (1..=3)
+            .map(|n| Chapter { title: format!("Chapter {}", n), content: format!("{0}\n\n{0}", paragraph) })
+            .collect();
+
+        let pdf = build(&config, &chapters, &PdfOptions::default()).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        // 书名页加上每章至少一页
+        assert!(document.get_pages().len() >= 4);
+
+        // 字体只嵌入用到的字形，整个文件比字体本身还小
+        let font = FontLibrary::scan().load_stack("serif", FontStyle::Regular).unwrap();
+        assert!(pdf.len() < font.data.len(), "{} >= {}", pdf.len(), font.data.len());
+    }
+}
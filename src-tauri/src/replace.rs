@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use crate::text;
+
+/// 预览中匹配前后保留的上下文长度（字符）
+const PREVIEW_CONTEXT: usize = 40;
+
+/// 查找替换选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplaceOptions {
+    pub find: String,
+    pub replace: String,
+    pub regex: bool, // 按正则表达式查找，替换文本可用 $1、${name} 引用分组
+    pub whole_word: bool,
+    pub case_sensitive: bool,
+    pub document_ids: Vec<String>, // 只替换这些文档，为空时替换全部
+    pub statuses: Vec<String>,     // 只替换这些状态的文档，为空时不限
+    pub dry_run: bool,             // 只返回匹配，不修改文档
+}
+
+/// 一处替换的预览
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplaceMatch {
+    pub document_id: String,
+    pub document_title: String,
+    pub line: u32, // 从 1 开始
+    pub before: String,
+    pub matched: String,
+    pub replacement: String,
+    pub after: String,
+}
+
+/// 查找替换结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplaceResult {
+    pub group_id: Option<String>, // 本次替换的提交分组，预览或没有匹配时为空
+    pub matches: Vec<ReplaceMatch>,
+    pub documents: Vec<String>, // 有匹配的文档
+    pub replacement_count: usize,
+    pub failed: Vec<String>, // 读取或替换失败、保持原样的文档
+}
+
+/// 撤销一组提交的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RevertResult {
+    pub group_id: Option<String>, // 撤销产生的提交分组，没有文档被修改时为空
+    pub documents: Vec<String>,   // 已恢复的文档
+    pub conflicts: Vec<String>,   // 之后的修改与撤销冲突、未能恢复的文档
+}
+
+/// 文本中的一处替换
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    pub range: Range<usize>, // 原文中的字节范围
+    pub text: String,
+}
+
+/// 按选项编译好的查找替换规则
+pub struct Replacer {
+    regex: Regex,
+    replacement: String,
+    expand: bool, // 替换文本中展开分组引用，普通查找时原样替换
+    whole_word: bool,
+}
+
+impl Replacer {
+    pub fn new(options: &ReplaceOptions) -> Result<Self> {
+        if options.find.is_empty() {
+            return Err(anyhow::anyhow!("Search text is empty"));
+        }
+
+        let pattern = if options.regex { options.find.clone() } else { regex::escape(&options.find) };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .build()
+            .context("Invalid regular expression")?;
+
+        Ok(Self {
+            regex,
+            replacement: options.replace.clone(),
+            expand: options.regex,
+            whole_word: options.whole_word,
+        })
+    }
+
+    /// 找出所有替换，同时返回替换后的文本
+    ///
+    /// 空匹配会被跳过，否则 `a*` 之类的表达式会在每个字符之间插入替换文本。
+    pub fn replace(&self, content: &str) -> (String, Vec<Replacement>) {
+        let mut replaced = String::with_capacity(content.len());
+        let mut replacements = Vec::new();
+        let mut last = 0;
+
+        for captures in self.regex.captures_iter(content) {
+            let found = captures.get(0).expect("group 0 always participates");
+            if found.is_empty() || (self.whole_word && !is_whole_word(content, found.range())) {
+                continue;
+            }
+
+            let mut text = String::new();
+            if self.expand {
+                captures.expand(&self.replacement, &mut text);
+            } else {
+                text.push_str(&self.replacement);
+            }
+
+            replaced.push_str(&content[last..found.start()]);
+            replaced.push_str(&text);
+            last = found.end();
+            replacements.push(Replacement { range: found.range(), text });
+        }
+
+        replaced.push_str(&content[last..]);
+        (replaced, replacements)
+    }
+}
+
+/// 中日韩文字之间没有空格，不把它们当作单词字符，否则整词匹配永远找不到中文
+fn is_word_char(c: char) -> bool {
+    (c.is_alphanumeric() || c == '_') && !text::is_cjk(c)
+}
+
+/// 匹配的两端不与相邻的单词字符连在一起
+fn is_whole_word(content: &str, range: Range<usize>) -> bool {
+    let matched = &content[range.clone()];
+    let starts_word = matched.chars().next().is_some_and(is_word_char);
+    let ends_word = matched.chars().next_back().is_some_and(is_word_char);
+    let before = content[..range.start].chars().next_back();
+    let after = content[range.end..].chars().next();
+
+    let joined_before = starts_word && before.is_some_and(is_word_char);
+    let joined_after = ends_word && after.is_some_and(is_word_char);
+    !joined_before && !joined_after
+}
+
+/// 生成每处替换的预览，上下文只取匹配所在的行
+pub fn preview(document_id: &str, document_title: &str, content: &str, replacements: &[Replacement]) -> Vec<ReplaceMatch> {
+    let mut line = 1;
+    let mut counted = 0;
+
+    replacements
+        .iter()
+        .map(|replacement| {
+            let Range { start, end } = replacement.range;
+            line += content[counted..start].matches('\n').count() as u32;
+            counted = start;
+
+            let line_start = content[..start].rfind('\n').map_or(0, |index| index + 1);
+            let line_end = content[end..].find('\n').map_or(content.len(), |index| end + index);
+
+            ReplaceMatch {
+                document_id: document_id.to_string(),
+                document_title: document_title.to_string(),
+                line,
+                before: context_before(&content[line_start..start]),
+                matched: content[start..end].to_string(),
+                replacement: replacement.text.clone(),
+                after: context_after(content[end..line_end].trim_end_matches('\r')),
+            }
+        })
+        .collect()
+}
+
+fn context_before(text: &str) -> String {
+    let char_count = text.chars().count();
+    if char_count <= PREVIEW_CONTEXT {
+        return text.to_string();
+    }
+    let kept: String = text.chars().skip(char_count - PREVIEW_CONTEXT).collect();
+    format!("…{}", kept)
+}
+
+fn context_after(text: &str) -> String {
+    if text.chars().count() <= PREVIEW_CONTEXT {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(PREVIEW_CONTEXT).collect();
+    format!("{}…", kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(find: &str, replace: &str) -> ReplaceOptions {
+        ReplaceOptions { find: find.to_string(), replace: replace.to_string(), ..Default::default() }
+    }
+
+    fn replace(options: &ReplaceOptions, content: &str) -> String {
+        Replacer::new(options).unwrap().replace(content).0
+    }
+
+    #[test]
+    fn replaces_literals_with_case_and_word_options() {
+        let content = "Cat and cat, concatenate. 猫cat";
+
+        assert_eq!(replace(&options("cat", "dog"), content), "dog and dog, condogenate. 猫dog");
+
+        let whole_word = ReplaceOptions { whole_word: true, ..options("cat", "dog") };
+        assert_eq!(replace(&whole_word, content), "dog and dog, concatenate. 猫dog");
+
+        let case_sensitive = ReplaceOptions { whole_word: true, case_sensitive: true, ..options("cat", "dog") };
+        assert_eq!(replace(&case_sensitive, content), "Cat and dog, concatenate. 猫dog");
+
+        // 普通查找不解释正则语法，替换文本中的 $ 原样保留
+        assert_eq!(replace(&options("a.b", "$1"), "a.b axb"), "$1 axb");
+
+        // 中文没有词边界，整词匹配不影响中文
+        let chinese = ReplaceOptions { whole_word: true, ..options("韩立", "厉飞雨") };
+        assert_eq!(replace(&chinese, "韩立走了。"), "厉飞雨走了。");
+
+        assert!(Replacer::new(&options("", "x")).is_err());
+    }
+
+    #[test]
+    fn expands_regex_groups_and_skips_empty_matches() {
+        let swap = ReplaceOptions { regex: true, ..options(r"(\w+)@(\w+)", "$2@$1") };
+        assert_eq!(replace(&swap, "a@b c@d"), "b@a d@c");
+
+        let headings = ReplaceOptions { regex: true, ..options(r"^#\s*", "## ") };
+        assert_eq!(replace(&headings, "#一\n正文 # 号\n#二"), "## 一\n正文 # 号\n## 二");
+
+        let empty = ReplaceOptions { regex: true, ..options("x*", "-") };
+        assert_eq!(replace(&empty, "axxb"), "a-b");
+
+        let invalid = ReplaceOptions { regex: true, ..options("(", "") };
+        assert!(Replacer::new(&invalid).is_err());
+    }
+
+    #[test]
+    fn previews_matches_with_line_context() {
+        let content = "第一行没有\n他说：“师兄。”\r\n又一位师兄";
+        let (_, replacements) = Replacer::new(&options("师兄", "师姐")).unwrap().replace(content);
+        let matches = preview("doc-1", "第一章", content, &replacements);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].before, "他说：“");
+        assert_eq!(matches[0].matched, "师兄");
+        assert_eq!(matches[0].replacement, "师姐");
+        assert_eq!(matches[0].after, "。”");
+        assert_eq!(matches[1].line, 3);
+        assert_eq!(matches[1].before, "又一位");
+        assert_eq!(matches[1].after, "");
+
+        let long_line = format!("{}目标{}", "前".repeat(50), "后".repeat(50));
+        let (_, replacements) = Replacer::new(&options("目标", "")).unwrap().replace(&long_line);
+        let matches = preview("doc-1", "第一章", &long_line, &replacements);
+        assert_eq!(matches[0].before, format!("…{}", "前".repeat(PREVIEW_CONTEXT)));
+        assert_eq!(matches[0].after, format!("{}…", "后".repeat(PREVIEW_CONTEXT)));
+    }
+}
@@ -0,0 +1,293 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+
+/// 通用文件命令的错误，前端可以按 `kind` 区分被拒绝的访问和普通的读写失败
+#[derive(Debug)]
+pub enum SandboxError {
+    NotAbsolute(String),          // 不是绝对路径
+    ParentTraversal(String),      // 路径中含有 `..`
+    OutsideAllowedRoots(String),  // 不在数据目录或用户选择过的位置中
+    SymlinkEscape(String),        // 经过符号链接指向允许范围之外，或是悬空链接
+    Io(String, io::Error),
+}
+
+impl SandboxError {
+    pub fn io(path: &Path, error: io::Error) -> Self {
+        SandboxError::Io(path.display().to_string(), error)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            SandboxError::NotAbsolute(_) => "not_absolute",
+            SandboxError::ParentTraversal(_) => "parent_traversal",
+            SandboxError::OutsideAllowedRoots(_) => "outside_allowed_roots",
+            SandboxError::SymlinkEscape(_) => "symlink_escape",
+            SandboxError::Io(..) => "io",
+        }
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            SandboxError::NotAbsolute(path)
+            | SandboxError::ParentTraversal(path)
+            | SandboxError::OutsideAllowedRoots(path)
+            | SandboxError::SymlinkEscape(path)
+            | SandboxError::Io(path, _) => path,
+        }
+    }
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxError::NotAbsolute(path) => write!(f, "Path must be absolute: {}", path),
+            SandboxError::ParentTraversal(path) => write!(f, "Path must not contain '..': {}", path),
+            SandboxError::OutsideAllowedRoots(path) => write!(f, "Access denied outside allowed folders: {}", path),
+            SandboxError::SymlinkEscape(path) => write!(f, "Access denied through symbolic link: {}", path),
+            SandboxError::Io(path, error) => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for SandboxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SandboxError::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// 序列化为 `{ kind, path, message }`，命令可以直接把它返回给前端
+impl Serialize for SandboxError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SandboxError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("path", self.path())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// 通用文件命令允许访问的位置
+///
+/// 只包含数据目录和用户通过对话框选择过的文件夹或文件。所有位置都保存为解析过符号链接的
+/// 规范路径，检查时请求的路径也先解析，这样指向外部的链接无法绕过限制。
+pub struct PathSandbox {
+    roots: RwLock<Vec<PathBuf>>, // 文件夹，其下的内容都可以访问
+    files: RwLock<Vec<PathBuf>>, // 单个文件，只能访问它本身
+}
+
+impl PathSandbox {
+    pub fn new() -> Self {
+        Self { roots: RwLock::new(Vec::new()), files: RwLock::new(Vec::new()) }
+    }
+
+    /// 允许访问 `root` 及其下的所有内容，`root` 必须已经存在
+    pub fn allow(&self, root: &Path) -> Result<(), SandboxError> {
+        let root = fs::canonicalize(root).map_err(|e| SandboxError::io(root, e))?;
+        let mut roots = self.roots.write().unwrap_or_else(PoisonError::into_inner);
+        if !roots.contains(&root) {
+            log::info!("Allowing file access to {}", root.display());
+            roots.push(root);
+        }
+        Ok(())
+    }
+
+    /// 只允许访问 `file` 这一个文件，文件可以还不存在（保存对话框选择的新文件）
+    pub fn allow_file(&self, file: &Path) -> Result<(), SandboxError> {
+        let file_string = file.to_string_lossy();
+        let file = Self::canonical_request(&file_string)?;
+        let mut files = self.files.write().unwrap_or_else(PoisonError::into_inner);
+        if !files.contains(&file) {
+            log::info!("Allowing file access to {}", file.display());
+            files.push(file);
+        }
+        Ok(())
+    }
+
+    /// 检查路径并返回解析后的规范路径，之后的读写都应使用返回的路径
+    ///
+    /// 路径可以还不存在（写入新文件、创建目录），此时解析最近的已存在上级目录，再接上其余部分。
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, SandboxError> {
+        let lexical = Self::lexical_path(path)?;
+        let resolved = Self::canonicalize_existing_prefix(&lexical, path)?;
+
+        let files = self.files.read().unwrap_or_else(PoisonError::into_inner);
+        if files.contains(&resolved) {
+            return Ok(resolved);
+        }
+
+        let roots = self.roots.read().unwrap_or_else(PoisonError::into_inner);
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            return Ok(resolved);
+        }
+
+        // 字面上在允许范围内、解析后却在外面，说明经过了指向外部的符号链接
+        if roots.iter().any(|root| lexical.starts_with(root)) || files.contains(&lexical) {
+            log::warn!("Rejected symlink escape: {} -> {}", path, resolved.display());
+            Err(SandboxError::SymlinkEscape(path.to_string()))
+        } else {
+            log::warn!("Rejected file access outside allowed folders: {}", path);
+            Err(SandboxError::OutsideAllowedRoots(path.to_string()))
+        }
+    }
+
+    /// 解析路径但不检查是否允许访问
+    fn canonical_request(path: &str) -> Result<PathBuf, SandboxError> {
+        Self::canonicalize_existing_prefix(&Self::lexical_path(path)?, path)
+    }
+
+    /// 要求绝对路径，去掉 `.`，拒绝 `..`
+    fn lexical_path(path: &str) -> Result<PathBuf, SandboxError> {
+        let requested = Path::new(path);
+        if !requested.is_absolute() {
+            return Err(SandboxError::NotAbsolute(path.to_string()));
+        }
+
+        let mut lexical = PathBuf::new();
+        for component in requested.components() {
+            match component {
+                Component::ParentDir => return Err(SandboxError::ParentTraversal(path.to_string())),
+                Component::CurDir => {}
+                other => lexical.push(other),
+            }
+        }
+        Ok(lexical)
+    }
+
+    fn canonicalize_existing_prefix(lexical: &Path, path: &str) -> Result<PathBuf, SandboxError> {
+        let mut existing = lexical;
+        let mut missing = Vec::new();
+
+        let canonical = loop {
+            match fs::canonicalize(existing) {
+                Ok(canonical) => break canonical,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    // 悬空的符号链接也会报不存在，写入时会跟随链接创建目标，不能放过
+                    if fs::symlink_metadata(existing).is_ok() {
+                        return Err(SandboxError::SymlinkEscape(path.to_string()));
+                    }
+                    match (existing.parent(), existing.file_name()) {
+                        (Some(parent), Some(name)) => {
+                            missing.push(name);
+                            existing = parent;
+                        }
+                        _ => return Err(SandboxError::io(lexical, e)),
+                    }
+                }
+                Err(e) => return Err(SandboxError::io(lexical, e)),
+            }
+        };
+
+        Ok(missing.iter().rev().fold(canonical, |resolved, name| resolved.join(name)))
+    }
+}
+
+impl Default for PathSandbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn path_string(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn allows_only_paths_under_registered_roots() {
+        let dir = TempDir::new().unwrap();
+        let data_dir = dir.path().join("data");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(data_dir.join("notes.md"), "笔记").unwrap();
+        fs::write(outside.join("secret.txt"), "secret").unwrap();
+
+        let sandbox = PathSandbox::new();
+        sandbox.allow(&data_dir).unwrap();
+
+        let notes = sandbox.resolve(&path_string(&data_dir.join("notes.md"))).unwrap();
+        assert_eq!(fs::read_to_string(notes).unwrap(), "笔记");
+
+        // 还不存在的文件和目录可以写入，但只能在允许的位置下
+        let new_file = sandbox.resolve(&path_string(&data_dir.join("new").join("draft.md"))).unwrap();
+        assert!(new_file.starts_with(fs::canonicalize(&data_dir).unwrap()));
+
+        assert!(matches!(
+            sandbox.resolve(&path_string(&outside.join("secret.txt"))),
+            Err(SandboxError::OutsideAllowedRoots(_))
+        ));
+        assert!(matches!(sandbox.resolve("notes.md"), Err(SandboxError::NotAbsolute(_))));
+
+        let traversal = format!("{}/../outside/secret.txt", path_string(&data_dir));
+        let error = sandbox.resolve(&traversal).unwrap_err();
+        assert!(matches!(error, SandboxError::ParentTraversal(_)));
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["kind"], "parent_traversal");
+        assert_eq!(json["path"], traversal.as_str());
+
+        // 保存对话框选择的新文件只允许它本身，不包括所在的文件夹
+        let export = outside.join("book.epub");
+        sandbox.allow_file(&export).unwrap();
+        assert!(sandbox.resolve(&path_string(&export)).is_ok());
+        assert!(matches!(
+            sandbox.resolve(&path_string(&outside.join("secret.txt"))),
+            Err(SandboxError::OutsideAllowedRoots(_))
+        ));
+        assert!(sandbox.resolve(&path_string(&export.join("inner.txt"))).is_err());
+
+        // 用户选择了外部文件夹之后才能访问
+        sandbox.allow(&outside).unwrap();
+        assert!(sandbox.resolve(&path_string(&outside.join("secret.txt"))).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_that_leave_allowed_roots() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new().unwrap();
+        let data_dir = dir.path().join("data");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("secret.txt"), "secret").unwrap();
+
+        symlink(&outside, data_dir.join("escape")).unwrap();
+        symlink(outside.join("missing.txt"), data_dir.join("dangling")).unwrap();
+        fs::create_dir_all(data_dir.join("inner")).unwrap();
+        symlink(data_dir.join("inner"), data_dir.join("alias")).unwrap();
+
+        let sandbox = PathSandbox::new();
+        sandbox.allow(&data_dir).unwrap();
+
+        assert!(matches!(
+            sandbox.resolve(&path_string(&data_dir.join("escape").join("secret.txt"))),
+            Err(SandboxError::SymlinkEscape(_))
+        ));
+        assert!(matches!(
+            sandbox.resolve(&path_string(&data_dir.join("escape").join("new.txt"))),
+            Err(SandboxError::SymlinkEscape(_))
+        ));
+        assert!(matches!(
+            sandbox.resolve(&path_string(&data_dir.join("dangling"))),
+            Err(SandboxError::SymlinkEscape(_))
+        ));
+
+        // 指向允许范围内部的链接不受影响
+        let aliased = sandbox.resolve(&path_string(&data_dir.join("alias").join("a.md"))).unwrap();
+        assert_eq!(aliased, fs::canonicalize(data_dir.join("inner")).unwrap().join("a.md"));
+    }
+}
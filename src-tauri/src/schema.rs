@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::Transaction;
+
+/// 当前的书籍目录格式版本
+pub const CURRENT_VERSION: u32 = 2;
+
+/// 记录格式版本的文件，没有此文件的书籍视为版本 0
+const SCHEMA_FILE: &str = "schema.json";
+
+/// 书籍目录的格式信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SchemaInfo {
+    version: u32,
+    app_version: String, // 最后一次写入格式版本的应用版本，仅供排查问题
+}
+
+/// 一次格式升级，把 `from` 版本的书籍目录升级到 `from + 1`
+///
+/// 升级直接读写原始 JSON，不依赖当前的结构体定义，写入都放进事务中，
+/// 与新的版本号一起提交。
+struct Migration {
+    from: u32,
+    description: &'static str,
+    apply: fn(&Path, &mut Transaction) -> Result<()>,
+}
+
+/// 按版本顺序排列的升级步骤
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "fill in fields missing from early book and document files",
+        apply: fill_missing_fields,
+    },
+    Migration {
+        from: 1,
+        description: "normalize free-form document types and statuses",
+        apply: normalize_types_and_statuses,
+    },
+];
+
+/// 读取书籍目录的格式版本
+pub fn read_version(book_dir: &Path) -> Result<u32> {
+    let path = book_dir.join(SCHEMA_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let json = fs::read_to_string(&path)
+        .context("Failed to read book schema version")?;
+    let info: SchemaInfo = serde_json::from_str(&json)
+        .context("Failed to parse book schema version")?;
+    Ok(info.version)
+}
+
+/// 把当前格式版本加入写入事务，用于新建的书籍
+pub fn stage_current_version(transaction: &mut Transaction, book_dir: &Path) -> Result<()> {
+    stage_version(transaction, book_dir, CURRENT_VERSION)
+}
+
+fn stage_version(transaction: &mut Transaction, book_dir: &Path, version: u32) -> Result<()> {
+    let info = SchemaInfo { version, app_version: env!("CARGO_PKG_VERSION").to_string() };
+    let json = serde_json::to_string_pretty(&info)
+        .context("Failed to serialize book schema version")?;
+    transaction.write(&book_dir.join(SCHEMA_FILE), json);
+    Ok(())
+}
+
+/// 把书籍目录升级到当前版本，升级前先完整备份到 `backups_dir`
+///
+/// 返回升级前的版本，已是当前版本时返回 None。
+/// 书籍由更新版本的应用写入时返回错误，避免旧版本误改数据。
+pub fn upgrade(book_dir: &Path, backups_dir: &Path, transactions_dir: &Path) -> Result<Option<u32>> {
+    let version = read_version(book_dir)?;
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+    if version > CURRENT_VERSION {
+        return Err(anyhow::anyhow!(
+            "This book was saved by a newer version of BranchWrite (format version {}, this version supports up to {}). Please update the app to open it.",
+            version,
+            CURRENT_VERSION
+        ));
+    }
+
+    let backup_dir = backup(book_dir, backups_dir, version)?;
+    log::info!("Backed up {} to {} before upgrading", book_dir.display(), backup_dir.display());
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from >= version) {
+        let mut transaction = Transaction::new(transactions_dir);
+        (migration.apply)(book_dir, &mut transaction)
+            .with_context(|| format!("Failed to upgrade book format from version {}: {}", migration.from, migration.description))?;
+        stage_version(&mut transaction, book_dir, migration.from + 1)?;
+        transaction.commit()
+            .with_context(|| format!("Failed to write book format version {}", migration.from + 1))?;
+        log::info!("Upgraded {} to format version {}", book_dir.display(), migration.from + 1);
+    }
+
+    Ok(Some(version))
+}
+
+/// 复制整个书籍目录，返回备份位置
+fn backup(book_dir: &Path, backups_dir: &Path, version: u32) -> Result<PathBuf> {
+    let book_id = book_dir
+        .file_name()
+        .context("Invalid book directory")?;
+    let backup_dir = backups_dir
+        .join(book_id)
+        .join(format!("v{}-{}", version, Utc::now().format("%Y%m%d%H%M%S%3f")));
+
+    copy_dir(book_dir, &backup_dir)
+        .with_context(|| format!("Failed to back up {}", book_dir.display()))?;
+    Ok(backup_dir)
+}
+
+fn copy_dir(source: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &target.join(entry.file_name()))?;
+        } else {
+            fs::copy(&path, target.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// 读取 JSON 文件，不存在时返回 None
+fn read_json(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(value))
+}
+
+fn stage_json(transaction: &mut Transaction, path: &Path, value: &Value) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .with_context(|| format!("Failed to serialize {}", path.display()))?;
+    transaction.write(path, json);
+    Ok(())
+}
+
+/// 补上对象中缺少的键，已有的值保持不变
+fn fill_defaults(object: &mut Map<String, Value>, defaults: Value) {
+    if let Value::Object(defaults) = defaults {
+        for (key, value) in defaults {
+            object.entry(key).or_insert(value);
+        }
+    }
+}
+
+// ===== 版本 0 → 1 =====
+
+/// 早期的书籍文件可能缺少后来加入的字段，补上当时的默认值
+fn fill_missing_fields(book_dir: &Path, transaction: &mut Transaction) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+    let book_id = book_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let config_path = book_dir.join("config.json");
+    if let Some(Value::Object(mut config)) = read_json(&config_path)? {
+        fill_defaults(&mut config, json!({
+            "id": book_id,
+            "name": "",
+            "description": "",
+            "author": "",
+            "genre": "",
+            "created_at": now,
+            "last_modified": now,
+            "cover_image": null,
+            "tags": [],
+            "settings": {},
+        }));
+        if let Some(Value::Object(settings)) = config.get_mut("settings") {
+            fill_defaults(settings, json!({
+                "outline_enabled": true,
+                "timeline_enabled": true,
+                "auto_save_interval": 5,
+                "target_word_count": null,
+                "deadline": null,
+                "editor_theme": "focus-writing",
+                "font_size": 14,
+                "line_height": 24,
+                "font_family": "'JetBrains Mono', 'Fira Code', 'Monaco', 'Consolas', monospace",
+                "count_markdown_syntax": false,
+            }));
+        }
+        stage_json(transaction, &config_path, &Value::Object(config))?;
+    }
+
+    let document_defaults = |index: usize| json!({
+        "book_id": book_id,
+        "title": "",
+        "order": index + 1,
+        "doc_type": "chapter",
+        "created_at": now,
+        "last_modified": now,
+        "word_count": 0,
+        "character_count": 0,
+        "status": "draft",
+    });
+
+    let documents_path = book_dir.join("documents.json");
+    if let Some(Value::Array(mut documents)) = read_json(&documents_path)? {
+        for (index, document) in documents.iter_mut().enumerate() {
+            let Value::Object(document) = document else { continue };
+            fill_defaults(document, document_defaults(index));
+
+            let Some(document_id) = document.get("id").and_then(Value::as_str) else { continue };
+            let metadata_path = book_dir.join("documents").join(document_id).join("metadata.json");
+            if let Some(Value::Object(mut metadata)) = read_json(&metadata_path)? {
+                fill_defaults(&mut metadata, Value::Object(document.clone()));
+                stage_json(transaction, &metadata_path, &Value::Object(metadata))?;
+            }
+        }
+        stage_json(transaction, &documents_path, &Value::Array(documents))?;
+    }
+
+    Ok(())
+}
+
+// ===== 版本 1 → 2 =====
+
+/// 把旧版本写入的文档类型换成枚举值
+///
+/// 早期界面直接保存中文类型名（章节、笔记、大纲……），无法识别的类型按章节处理，
+/// 与之前导出时的行为一致。
+fn normalize_doc_type(doc_type: &str) -> &'static str {
+    match doc_type.trim().to_lowercase().as_str() {
+        "part" | "卷" => "part",
+        "scene" | "场景" => "scene",
+        "section" | "小节" => "section",
+        "note" | "notes" | "笔记" | "大纲" | "设定" => "note",
+        _ => "chapter",
+    }
+}
+
+/// 不在默认状态流程中的状态改为初稿
+fn normalize_status(status: &str) -> &'static str {
+    match status.trim().to_lowercase().as_str() {
+        "review" => "review",
+        "final" => "final",
+        _ => "draft",
+    }
+}
+
+fn normalize_document(document: &mut Map<String, Value>) {
+    let doc_type = document.get("doc_type").and_then(Value::as_str).unwrap_or_default();
+    let doc_type = normalize_doc_type(doc_type);
+    let status = document.get("status").and_then(Value::as_str).unwrap_or_default();
+    let status = normalize_status(status);
+    document.insert("doc_type".to_string(), json!(doc_type));
+    document.insert("status".to_string(), json!(status));
+}
+
+/// 文档类型改为枚举、状态改由状态流程校验，规范化已有的值
+fn normalize_types_and_statuses(book_dir: &Path, transaction: &mut Transaction) -> Result<()> {
+    let documents_path = book_dir.join("documents.json");
+    let Some(Value::Array(mut documents)) = read_json(&documents_path)? else { return Ok(()) };
+
+    for document in &mut documents {
+        let Value::Object(document) = document else { continue };
+        normalize_document(document);
+
+        let Some(document_id) = document.get("id").and_then(Value::as_str) else { continue };
+        let metadata_path = book_dir.join("documents").join(document_id).join("metadata.json");
+        if let Some(Value::Object(mut metadata)) = read_json(&metadata_path)? {
+            normalize_document(&mut metadata);
+            stage_json(transaction, &metadata_path, &Value::Object(metadata))?;
+        }
+    }
+    stage_json(transaction, &documents_path, &Value::Array(documents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn upgrades_unversioned_book_after_backup() {
+        let dir = TempDir::new().unwrap();
+        let book_dir = dir.path().join("books").join("book-1");
+        fs::create_dir_all(book_dir.join("documents").join("doc-1")).unwrap();
+        fs::write(book_dir.join("config.json"), r#"{"id":"book-1","name":"旧书","created_at":"2024-01-01T00:00:00Z","last_modified":"2024-01-01T00:00:00Z","settings":{"font_size":18}}"#).unwrap();
+        fs::write(book_dir.join("documents.json"), r#"[{"id":"doc-1","title":"第一章"}]"#).unwrap();
+        fs::write(book_dir.join("documents").join("doc-1").join("metadata.json"), r#"{"id":"doc-1","title":"第一章"}"#).unwrap();
+
+        let backups_dir = dir.path().join("backups");
+        let transactions_dir = dir.path().join("transactions");
+        assert_eq!(upgrade(&book_dir, &backups_dir, &transactions_dir).unwrap(), Some(0));
+        assert_eq!(read_version(&book_dir).unwrap(), CURRENT_VERSION);
+
+        let config = read_json(&book_dir.join("config.json")).unwrap().unwrap();
+        assert_eq!(config["settings"]["font_size"], 18);
+        assert_eq!(config["settings"]["line_height"], 24);
+        assert_eq!(config["tags"], json!([]));
+        let documents = read_json(&book_dir.join("documents.json")).unwrap().unwrap();
+        assert_eq!(documents[0]["status"], "draft");
+        assert_eq!(documents[0]["book_id"], "book-1");
+        let metadata = read_json(&book_dir.join("documents").join("doc-1").join("metadata.json")).unwrap().unwrap();
+        assert_eq!(metadata["order"], 1);
+        assert_eq!(metadata["doc_type"], "chapter");
+
+        // 备份保留了升级前的文件
+        let backups: Vec<_> = fs::read_dir(backups_dir.join("book-1")).unwrap().flatten().collect();
+        assert_eq!(backups.len(), 1);
+        let original = fs::read_to_string(backups[0].path().join("documents.json")).unwrap();
+        assert_eq!(original, r#"[{"id":"doc-1","title":"第一章"}]"#);
+
+        // 已是当前版本时不再升级
+        assert_eq!(upgrade(&book_dir, &backups_dir, &transactions_dir).unwrap(), None);
+    }
+
+    #[test]
+    fn normalizes_legacy_types_and_statuses() {
+        let dir = TempDir::new().unwrap();
+        let book_dir = dir.path().join("books").join("book-1");
+        fs::create_dir_all(book_dir.join("documents").join("doc-1")).unwrap();
+        fs::write(book_dir.join(SCHEMA_FILE), r#"{"version":1,"app_version":"0.1.0"}"#).unwrap();
+        fs::write(book_dir.join("documents.json"), r#"[
+            {"id":"doc-1","doc_type":"笔记","status":"final"},
+            {"id":"doc-2","doc_type":"章节","status":"drfat"},
+            {"id":"doc-3","doc_type":"Scene","status":"review"}
+        ]"#).unwrap();
+        fs::write(book_dir.join("documents").join("doc-1").join("metadata.json"), r#"{"id":"doc-1","doc_type":"笔记","status":"final"}"#).unwrap();
+
+        assert_eq!(upgrade(&book_dir, &dir.path().join("backups"), &dir.path().join("transactions")).unwrap(), Some(1));
+
+        let documents = read_json(&book_dir.join("documents.json")).unwrap().unwrap();
+        let normalized: Vec<(&str, &str)> = documents
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|document| (document["doc_type"].as_str().unwrap(), document["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(normalized, vec![("note", "final"), ("chapter", "draft"), ("scene", "review")]);
+        let metadata = read_json(&book_dir.join("documents").join("doc-1").join("metadata.json")).unwrap().unwrap();
+        assert_eq!(metadata["doc_type"], "note");
+    }
+
+    #[test]
+    fn refuses_books_from_newer_versions() {
+        let dir = TempDir::new().unwrap();
+        let book_dir = dir.path().join("book-1");
+        fs::create_dir_all(&book_dir).unwrap();
+        fs::write(book_dir.join(SCHEMA_FILE), format!(r#"{{"version":{},"app_version":"9.0.0"}}"#, CURRENT_VERSION + 1)).unwrap();
+
+        let error = upgrade(&book_dir, &dir.path().join("backups"), &dir.path().join("transactions")).unwrap_err();
+        assert!(error.to_string().contains("newer version"));
+        assert!(!dir.path().join("backups").exists());
+    }
+}
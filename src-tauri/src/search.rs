@@ -0,0 +1,538 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::atomic;
+use crate::file_system::CommitInfo;
+use crate::text;
+
+/// 索引文件格式版本，分词规则改变时递增，旧索引会被重建
+const INDEX_VERSION: u32 = 1;
+
+/// 每条结果片段的最大长度（字符）
+const SNIPPET_CHARS: usize = 160;
+
+/// 片段中关键词前保留的上下文长度（字符）
+const SNIPPET_CONTEXT: usize = 50;
+
+/// 默认最多返回的结果数
+const DEFAULT_LIMIT: usize = 200;
+
+/// 搜索选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub book_ids: Vec<String>, // 只搜索这些书籍，为空时搜索全部
+    pub limit: Option<usize>,
+}
+
+impl SearchOptions {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+}
+
+/// 片段中的高亮范围，使用 UTF-16 偏移，可直接用于 JavaScript 字符串
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 一行中的匹配
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMatch {
+    pub line: u32, // 从 1 开始
+    pub snippet: String,
+    pub highlights: Vec<Highlight>,
+}
+
+/// 一条搜索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub book_id: String,
+    pub book_name: String,
+    pub document_id: String,
+    pub document_title: String,
+    pub line: u32,
+    pub snippet: String,
+    pub highlights: Vec<Highlight>,
+}
+
+/// 搜索结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    pub total: usize, // 匹配的总行数，可能多于返回的结果
+}
+
+/// 版本历史中的变化
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryChange {
+    Appeared,    // 所有父版本中都没有，此版本中出现
+    Disappeared, // 所有父版本中都有，此版本中消失
+}
+
+/// 版本历史搜索的一条结果
+///
+/// 片段取自出现时的版本，或消失前的父版本。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryMatch {
+    pub book_id: String,
+    pub document_id: String,
+    pub document_title: String,
+    pub change: HistoryChange,
+    pub commit: CommitInfo,
+    pub line: u32,
+    pub snippet: String,
+    pub highlights: Vec<Highlight>,
+}
+
+/// 查询中的一个条件，多个词元时按短语匹配
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    tokens: Vec<String>,
+    prefix: bool, // 最后一个词元按前缀匹配
+}
+
+/// 文档文件的指纹，用于发现索引之外的修改
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Fingerprint {
+    len: u64,
+    modified: u64, // 纳秒
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self { len: metadata.len(), modified: modified.as_nanos() as u64 })
+    }
+}
+
+/// 单篇文档的索引文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedDocument {
+    version: u32,
+    fingerprint: Option<Fingerprint>,
+    terms: Vec<String>,
+}
+
+type DocumentKey = (String, String); // (书籍 ID, 文档 ID)
+
+/// 全文索引
+///
+/// 中日韩文字逐字索引，拉丁文字按词（小写）索引。倒排表只用于找出候选文档，
+/// 短语、前缀和行号都在读取候选文档内容后确认，因此索引只需记录每篇文档包含哪些词。
+/// 每篇文档的索引单独保存在 `index/<书籍 ID>/<文档 ID>.json`，保存文档时只需重写一个小文件。
+pub struct SearchIndex {
+    dir: PathBuf,
+    loaded: bool,
+    documents: Vec<Option<(DocumentKey, IndexedDocument)>>,
+    slots: HashMap<DocumentKey, usize>,
+    free: Vec<usize>, // 已删除文档空出的位置，插入时优先复用
+    postings: BTreeMap<String, BTreeSet<usize>>,
+}
+
+impl SearchIndex {
+    /// 创建索引，首次搜索时才从 `dir` 读取
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            loaded: false,
+            documents: Vec::new(),
+            slots: HashMap::new(),
+            free: Vec::new(),
+            postings: BTreeMap::new(),
+        }
+    }
+
+    fn document_path(&self, book_id: &str, document_id: &str) -> PathBuf {
+        self.dir.join(book_id).join(format!("{}.json", document_id))
+    }
+
+    /// 保存文档后更新其索引
+    ///
+    /// 词集合没有变化时不重写索引文件，只更新内存中的指纹。文件里留下的旧指纹
+    /// 最多让下次启动后的首次搜索重新读一遍这篇文档。
+    pub fn update_document(&mut self, content_path: &Path, book_id: &str, document_id: &str, content: &str) -> Result<()> {
+        let key = (book_id.to_string(), document_id.to_string());
+        let fingerprint = Fingerprint::of(content_path);
+        let terms = terms(content);
+        let path = self.document_path(book_id, document_id);
+
+        if self.loaded {
+            if let Some((_, indexed)) = self.slots.get(&key).and_then(|slot| self.documents[*slot].as_mut()) {
+                if indexed.terms == terms {
+                    indexed.fingerprint = fingerprint;
+                    return Ok(());
+                }
+            }
+        } else if read_indexed(&path).is_some_and(|indexed| indexed.terms == terms) {
+            return Ok(());
+        }
+
+        let document = IndexedDocument { version: INDEX_VERSION, fingerprint, terms };
+        fs::create_dir_all(path.parent().unwrap_or(&self.dir))
+            .context("Failed to create search index directory")?;
+        let json = serde_json::to_vec(&document)
+            .context("Failed to serialize search index")?;
+        atomic::write_file(&path, json)
+            .context("Failed to write search index")?;
+
+        // 尚未加载时，首次搜索会读到刚写入的文件
+        if self.loaded {
+            self.insert(key, document);
+        }
+        Ok(())
+    }
+
+    /// 加载索引，并重新索引在索引之外新增、修改或删除的文档
+    pub fn refresh(&mut self, books_dir: &Path) -> Result<()> {
+        if !self.loaded {
+            self.load();
+            self.loaded = true;
+        }
+
+        let mut seen = HashSet::new();
+        for book_entry in fs::read_dir(books_dir).context("Failed to read books directory")?.flatten() {
+            let book_id = book_entry.file_name().to_string_lossy().into_owned();
+            let Ok(document_entries) = fs::read_dir(book_entry.path().join("documents")) else { continue };
+
+            for document_entry in document_entries.flatten() {
+                let document_id = document_entry.file_name().to_string_lossy().into_owned();
+                let content_path = document_entry.path().join("content.md");
+                let Some(fingerprint) = Fingerprint::of(&content_path) else { continue };
+
+                let key = (book_id.clone(), document_id);
+                let indexed = self.slots
+                    .get(&key)
+                    .and_then(|slot| self.documents[*slot].as_ref())
+                    .and_then(|(_, document)| document.fingerprint);
+                if indexed != Some(fingerprint) {
+                    let content = fs::read_to_string(&content_path)
+                        .with_context(|| format!("Failed to read {}", content_path.display()))?;
+                    self.update_document(&content_path, &key.0, &key.1, &content)?;
+                }
+                seen.insert(key);
+            }
+        }
+
+        let removed: Vec<DocumentKey> = self.slots.keys().filter(|key| !seen.contains(*key)).cloned().collect();
+        for (book_id, document_id) in removed {
+            self.remove(&(book_id.clone(), document_id.clone()));
+            let _ = fs::remove_file(self.document_path(&book_id, &document_id));
+        }
+        Ok(())
+    }
+
+    /// 读取所有索引文件，损坏或版本不符的文件会在 `refresh` 中重建
+    fn load(&mut self) {
+        let Ok(book_entries) = fs::read_dir(&self.dir) else { return };
+        for book_entry in book_entries.flatten() {
+            let book_id = book_entry.file_name().to_string_lossy().into_owned();
+            let Ok(document_entries) = fs::read_dir(book_entry.path()) else { continue };
+
+            for document_entry in document_entries.flatten() {
+                let path = document_entry.path();
+                let Some(document_id) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else { continue };
+                if let Some(document) = read_indexed(&path) {
+                    self.insert((book_id.clone(), document_id), document);
+                }
+            }
+        }
+    }
+
+    /// 插入或替换文档的索引，已有的文档沿用原来的位置
+    fn insert(&mut self, key: DocumentKey, document: IndexedDocument) {
+        let slot = match self.slots.get(&key) {
+            Some(&slot) => {
+                self.clear_slot(slot);
+                slot
+            }
+            None => self.free.pop().unwrap_or_else(|| {
+                self.documents.push(None);
+                self.documents.len() - 1
+            }),
+        };
+        for term in &document.terms {
+            self.postings.entry(term.clone()).or_default().insert(slot);
+        }
+        self.slots.insert(key.clone(), slot);
+        self.documents[slot] = Some((key, document));
+    }
+
+    fn remove(&mut self, key: &DocumentKey) {
+        let Some(slot) = self.slots.remove(key) else { return };
+        self.clear_slot(slot);
+        self.free.push(slot);
+    }
+
+    fn clear_slot(&mut self, slot: usize) {
+        if let Some((_, document)) = self.documents[slot].take() {
+            for term in &document.terms {
+                if let Some(slots) = self.postings.get_mut(term) {
+                    slots.remove(&slot);
+                    if slots.is_empty() {
+                        self.postings.remove(term);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 包含所有条件中全部词元的文档
+    pub fn candidates(&self, clauses: &[Clause], book_ids: &[String]) -> Vec<DocumentKey> {
+        let mut result: Option<BTreeSet<usize>> = None;
+        for clause in clauses {
+            for (index, token) in clause.tokens.iter().enumerate() {
+                let slots: BTreeSet<usize> = if clause.prefix && index + 1 == clause.tokens.len() {
+                    self.postings
+                        .range(token.clone()..)
+                        .take_while(|(term, _)| term.starts_with(token.as_str()))
+                        .flat_map(|(_, slots)| slots.iter().copied())
+                        .collect()
+                } else {
+                    self.postings.get(token).cloned().unwrap_or_default()
+                };
+                result = Some(match result {
+                    Some(result) => result.intersection(&slots).copied().collect(),
+                    None => slots,
+                });
+            }
+        }
+
+        result
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|slot| self.documents[slot].as_ref().map(|(key, _)| key.clone()))
+            .filter(|(book_id, _)| book_ids.is_empty() || book_ids.contains(book_id))
+            .collect()
+    }
+}
+
+/// 切分出可搜索的词元（小写）及其字节范围，标点和空白被跳过
+fn tokenize(text: &str) -> Vec<(String, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    for word in text::split_words(text) {
+        let range = offset..offset + word.len();
+        offset = range.end;
+        if word.chars().next().is_some_and(|c| c.is_alphanumeric()) {
+            tokens.push((word.to_lowercase(), range));
+        }
+    }
+    tokens
+}
+
+/// 读取单篇文档的索引文件，损坏或版本不符时返回 `None`
+fn read_indexed(path: &Path) -> Option<IndexedDocument> {
+    fs::read(path)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<IndexedDocument>(&json).ok())
+        .filter(|document| document.version == INDEX_VERSION)
+}
+
+/// 文档中出现的所有词
+fn terms(content: &str) -> Vec<String> {
+    let terms: BTreeSet<String> = tokenize(content).into_iter().map(|(term, _)| term).collect();
+    terms.into_iter().collect()
+}
+
+/// 解析查询：空格分隔的条件需全部满足，引号内为短语，末尾的 `*` 表示前缀匹配。
+/// 连续的中日韩文字本身按短语匹配。
+pub fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    let mut push = |text: &str| {
+        let prefix = text.trim_end().ends_with('*');
+        let tokens: Vec<String> = tokenize(text).into_iter().map(|(token, _)| token).collect();
+        if !tokens.is_empty() {
+            clauses.push(Clause { tokens, prefix });
+        }
+    };
+
+    for (index, part) in query.split(['"', '“', '”']).enumerate() {
+        if index % 2 == 1 {
+            push(part);
+        } else {
+            part.split_whitespace().for_each(&mut push);
+        }
+    }
+    clauses
+}
+
+/// 在文档中查找所有条件，全部满足时按行返回匹配
+pub fn find_matches(content: &str, clauses: &[Clause]) -> Vec<LineMatch> {
+    let tokens = tokenize(content);
+    let mut ranges = Vec::new();
+
+    for clause in clauses {
+        let count = clause.tokens.len();
+        let matches_at = |start: usize| {
+            clause.tokens.iter().enumerate().all(|(offset, expected)| {
+                let token = &tokens[start + offset].0;
+                if clause.prefix && offset + 1 == count { token.starts_with(expected.as_str()) } else { token == expected }
+            })
+        };
+
+        let before = ranges.len();
+        for start in 0..tokens.len().saturating_sub(count - 1) {
+            if matches_at(start) {
+                ranges.push(tokens[start].1.start..tokens[start + count - 1].1.end);
+            }
+        }
+        if ranges.len() == before {
+            return Vec::new();
+        }
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    line_matches(content, &ranges)
+}
+
+/// 按行汇总匹配范围并生成片段
+fn line_matches(content: &str, ranges: &[Range<usize>]) -> Vec<LineMatch> {
+    let mut matches = Vec::new();
+    let mut line_start = 0;
+    let mut ranges = ranges.iter().peekable();
+
+    for (line_index, line) in content.split('\n').enumerate() {
+        let line_end = line_start + line.len();
+        let mut in_line = Vec::new();
+        while let Some(range) = ranges.next_if(|range| range.start <= line_end) {
+            // 跨行的短语只高亮到行尾
+            in_line.push(range.start - line_start..range.end.min(line_end) - line_start);
+        }
+        if !in_line.is_empty() {
+            matches.push(snippet(line.trim_end_matches('\r'), line_index as u32 + 1, &in_line));
+        }
+        line_start = line_end + 1;
+    }
+    matches
+}
+
+/// 截取关键词附近的文字，高亮范围转为片段内的 UTF-16 偏移
+fn snippet(line: &str, line_number: u32, ranges: &[Range<usize>]) -> LineMatch {
+    let char_count = line.chars().count();
+    let first_char = line[..ranges[0].start.min(line.len())].chars().count();
+    let start_char = if char_count <= SNIPPET_CHARS { 0 } else { first_char.saturating_sub(SNIPPET_CONTEXT).min(char_count - SNIPPET_CHARS) };
+    let end_char = (start_char + SNIPPET_CHARS).min(char_count);
+
+    let byte_at = |char_index: usize| line.char_indices().nth(char_index).map_or(line.len(), |(index, _)| index);
+    let (start, end) = (byte_at(start_char), byte_at(end_char));
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < line.len() { "…" } else { "" };
+    let snippet = format!("{}{}{}", prefix, &line[start..end], suffix);
+
+    let utf16 = |byte: usize| prefix.encode_utf16().count() + line[start..byte.clamp(start, end)].encode_utf16().count();
+    let highlights = ranges
+        .iter()
+        .filter(|range| range.end > start && range.start < end)
+        .map(|range| Highlight { start: utf16(range.start), end: utf16(range.end) })
+        .collect();
+
+    LineMatch { line: line_number, snippet, highlights }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_document(books_dir: &Path, book_id: &str, document_id: &str, content: &str) -> PathBuf {
+        let dir = books_dir.join(book_id).join("documents").join(document_id);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("content.md");
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn search(index: &SearchIndex, query: &str, book_ids: &[String]) -> Vec<DocumentKey> {
+        let mut keys = index.candidates(&parse_query(query), book_ids);
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn finds_cjk_phrases_prefixes_and_filters_books() {
+        let dir = TempDir::new().unwrap();
+        let books_dir = dir.path().join("books");
+        write_document(&books_dir, "a", "1", "第一行\n韩立走进了**七玄门**。");
+        write_document(&books_dir, "a", "2", "立韩并不是人名。Running fast.");
+        write_document(&books_dir, "b", "3", "韩立又回来了。");
+
+        let mut index = SearchIndex::new(dir.path().join("index"));
+        index.refresh(&books_dir).unwrap();
+
+        // 候选文档只要求包含所有字，短语在读取内容后确认
+        assert_eq!(search(&index, "韩立", &[]).len(), 3);
+        assert_eq!(search(&index, "韩立", &["b".to_string()]), vec![("b".to_string(), "3".to_string())]);
+        assert_eq!(search(&index, "run*", &[]), vec![("a".to_string(), "2".to_string())]);
+        assert!(search(&index, "run", &[]).is_empty());
+
+        let matches = find_matches("第一行\n韩立走进了**七玄门**。", &parse_query("韩立 七玄门"));
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].highlights, vec![Highlight { start: 0, end: 2 }, Highlight { start: 7, end: 10 }]);
+        assert!(find_matches("立韩并不是人名。", &parse_query("韩立")).is_empty());
+        assert_eq!(find_matches("Don't stop", &parse_query("\"don't stop\"")).len(), 1);
+        assert!(find_matches("stop, don't", &parse_query("\"don't stop\"")).is_empty());
+    }
+
+    #[test]
+    fn updates_incrementally_and_notices_outside_changes() {
+        let dir = TempDir::new().unwrap();
+        let books_dir = dir.path().join("books");
+        let path = write_document(&books_dir, "a", "1", "雨夜");
+        write_document(&books_dir, "a", "2", "晴天");
+
+        let mut index = SearchIndex::new(dir.path().join("index"));
+        index.refresh(&books_dir).unwrap();
+        assert_eq!(search(&index, "雨夜", &[]).len(), 1);
+
+        fs::write(&path, "风起").unwrap();
+        index.update_document(&path, "a", "1", "风起").unwrap();
+        assert!(search(&index, "雨夜", &[]).is_empty());
+        assert_eq!(search(&index, "风起", &[]).len(), 1);
+
+        // 反复保存同一篇文档沿用原来的位置；词集合不变时不重写索引文件
+        let index_file = dir.path().join("index").join("a").join("1.json");
+        for content in ["风起云涌", "云涌风起", "风起"] {
+            fs::write(&path, content).unwrap();
+            index.update_document(&path, "a", "1", content).unwrap();
+        }
+        assert_eq!(index.documents.len(), 2);
+        let written = fs::read(&index_file).unwrap();
+        fs::write(&path, "起风").unwrap();
+        index.update_document(&path, "a", "1", "起风").unwrap();
+        assert_eq!(fs::read(&index_file).unwrap(), written);
+        index.refresh(&books_dir).unwrap();
+        assert_eq!(index.documents.len(), 2);
+
+        // 重新打开时从索引文件恢复，并发现被删除的文档
+        fs::remove_dir_all(books_dir.join("a").join("documents").join("2")).unwrap();
+        let mut reopened = SearchIndex::new(dir.path().join("index"));
+        reopened.refresh(&books_dir).unwrap();
+        assert_eq!(search(&reopened, "风起", &[]).len(), 1);
+        assert!(search(&reopened, "晴天", &[]).is_empty());
+        assert!(!dir.path().join("index").join("a").join("2.json").exists());
+    }
+
+    #[test]
+    fn long_lines_are_cut_around_the_match() {
+        let line = format!("{}目标{}", "前".repeat(300), "后".repeat(300));
+        let matches = find_matches(&line, &parse_query("目标"));
+        let found = &matches[0];
+        assert_eq!(found.snippet.chars().count(), SNIPPET_CHARS + 2);
+        assert!(found.snippet.starts_with('…') && found.snippet.ends_with('…'));
+        let highlight = &found.highlights[0];
+        let highlighted: String = found.snippet.chars().skip(highlight.start).take(highlight.end - highlight.start).collect();
+        assert_eq!(highlighted, "目标");
+    }
+}
@@ -0,0 +1,453 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::export::{self, Chapter, Cover};
+use crate::file_system::BookConfig;
+
+/// 站点样式表
+const STYLESHEET: &str = r#"body { max-width: 42em; margin: 0 auto; padding: 0 1.2em 3em; font-family: Georgia, "Noto Serif", "Songti SC", serif; line-height: 1.8; color: #222; background: #fdfcf8; }
+a { color: #2a5d8f; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.site-header { padding: 1em 0; border-bottom: 1px solid #ddd; font-size: 0.9em; }
+h1, h2, h3 { line-height: 1.3; }
+h1 { text-align: center; margin: 1.5em 0 1em; }
+article p { margin: 0; text-indent: 2em; }
+blockquote { margin: 1em 2em; font-style: italic; }
+hr { border: none; text-align: center; margin: 1.5em 0; }
+hr::after { content: "* * *"; }
+table { border-collapse: collapse; margin: 1em auto; }
+th, td { border: 1px solid #999; padding: 0.2em 0.5em; }
+.cover { display: block; max-width: 60%; margin: 2em auto 0; }
+.author { text-align: center; color: #666; }
+.toc li { margin: 0.3em 0; }
+.toc time { color: #888; font-size: 0.85em; margin-left: 0.6em; }
+.chapter-nav { display: flex; justify-content: space-between; margin-top: 3em; padding-top: 1em; border-top: 1px solid #ddd; }
+"#;
+
+/// 订阅源格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+    None,
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    /// 订阅源文件名和 MIME 类型
+    fn file(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::None => None,
+            Self::Atom => Some(("feed.xml", "application/atom+xml")),
+            Self::Rss => Some(("rss.xml", "application/rss+xml")),
+        }
+    }
+}
+
+/// 静态站点导出选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteOptions {
+    pub feed: Option<FeedFormat>, // 未指定时，设置了站点地址才生成 Atom 订阅源
+    pub site_url: Option<String>, // 站点的公开地址，订阅源中的链接据此生成绝对地址
+}
+
+impl SiteOptions {
+    /// 实际使用的订阅源格式：没有站点地址时订阅源里只能写相对链接，阅读器无法使用
+    fn feed(&self) -> FeedFormat {
+        self.feed.unwrap_or(if self.base_url().is_empty() { FeedFormat::None } else { FeedFormat::Atom })
+    }
+
+    /// 以 `/` 结尾的站点地址，未设置时为空
+    fn base_url(&self) -> String {
+        self.site_url
+            .as_deref()
+            .map(|url| url.trim().trim_end_matches('/'))
+            .filter(|url| !url.is_empty())
+            .map(|url| format!("{}/", url))
+            .unwrap_or_default()
+    }
+}
+
+/// 站点中的一章及其发布时间
+#[derive(Debug, Clone)]
+pub struct SiteChapter {
+    pub id: String,
+    pub chapter: Chapter,
+    pub published: DateTime<Utc>,
+}
+
+/// 界面文字
+struct Labels {
+    contents: &'static str,
+    previous: &'static str,
+    next: &'static str,
+    subscribe: &'static str,
+}
+
+impl Labels {
+    fn for_language(language: &str) -> Self {
+        match language {
+            "zh" | "ja" => Self { contents: "目录", previous: "上一章", next: "下一章", subscribe: "订阅" },
+            _ => Self { contents: "Contents", previous: "Previous", next: "Next", subscribe: "Subscribe" },
+        }
+    }
+}
+
+/// 记录上次导出生成了哪些文件，再次导出时只清理其中这次不再生成的文件
+pub const MANIFEST_FILE: &str = ".branchwrite-site.json";
+
+/// 生成静态站点，返回文件名和内容
+pub fn build(config: &BookConfig, chapters: &[SiteChapter], cover: Option<&Cover>, options: &SiteOptions) -> Vec<(String, Vec<u8>)> {
+    let samples: Vec<&str> = std::iter::once(config.name.as_str())
+        .chain(chapters.iter().map(|entry| entry.chapter.content.as_str()))
+        .collect();
+    let language = export::detect_language(&samples);
+    let labels = Labels::for_language(language);
+    let feed = options.feed();
+
+    let mut files = vec![
+        ("style.css".to_string(), STYLESHEET.as_bytes().to_vec()),
+        ("index.html".to_string(), index_html(config, chapters, cover, feed, language, &labels).into_bytes()),
+    ];
+
+    if let Some(cover) = cover {
+        files.push((format!("cover.{}", cover.extension), cover.data.clone()));
+    }
+
+    for index in 0..chapters.len() {
+        let html = chapter_html(config, chapters, index, feed, language, &labels);
+        files.push((chapter_file(index), html.into_bytes()));
+    }
+
+    let base_url = options.base_url();
+    match feed {
+        FeedFormat::None => {}
+        FeedFormat::Atom => files.push(("feed.xml".to_string(), atom_feed(config, chapters, &base_url, language).into_bytes())),
+        FeedFormat::Rss => files.push(("rss.xml".to_string(), rss_feed(config, chapters, &base_url, language).into_bytes())),
+    }
+
+    files
+}
+
+/// 章节页面文件名
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{:03}.html", index + 1)
+}
+
+/// HTML 页面外壳
+fn html_page(title: &str, language: &str, feed: FeedFormat, body: &str) -> String {
+    let feed_link = match feed.file() {
+        Some((file, media_type)) => format!("<link rel=\"alternate\" type=\"{}\" href=\"{}\">\n", media_type, file),
+        None => String::new(),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{language}">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="style.css">
+{feed_link}</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        language = language,
+        title = export::escape_xml(title),
+        feed_link = feed_link,
+        body = body,
+    )
+}
+
+/// 书籍首页：封面、简介和目录
+fn index_html(
+    config: &BookConfig,
+    chapters: &[SiteChapter],
+    cover: Option<&Cover>,
+    feed: FeedFormat,
+    language: &str,
+    labels: &Labels,
+) -> String {
+    let mut body = String::from("<main>\n");
+    if let Some(cover) = cover {
+        body.push_str(&format!(
+            "<img class=\"cover\" src=\"cover.{}\" alt=\"{}\">\n",
+            cover.extension,
+            export::escape_xml(&config.name),
+        ));
+    }
+    body.push_str(&format!("<h1>{}</h1>\n", export::escape_xml(&config.name)));
+    if !config.author.trim().is_empty() {
+        body.push_str(&format!("<p class=\"author\">{}</p>\n", export::escape_xml(&config.author)));
+    }
+
+    // 简介是纯文本，按空行分段
+    for paragraph in config.description.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+        body.push_str(&format!("<p>{}</p>\n", export::escape_xml(paragraph).replace('\n', "<br>")));
+    }
+
+    body.push_str(&format!("<h2>{}</h2>\n<ol class=\"toc\">\n", labels.contents));
+    for (index, entry) in chapters.iter().enumerate() {
+        body.push_str(&format!(
+            "<li><a href=\"{}\">{}</a><time datetime=\"{}\">{}</time></li>\n",
+            chapter_file(index),
+            export::escape_xml(&entry.chapter.title),
+            entry.published.to_rfc3339_opts(SecondsFormat::Secs, true),
+            entry.published.format("%Y-%m-%d"),
+        ));
+    }
+    body.push_str("</ol>\n");
+
+    if let Some((file, _)) = feed.file() {
+        body.push_str(&format!("<p><a href=\"{}\">{}</a></p>\n", file, labels.subscribe));
+    }
+    body.push_str("</main>");
+
+    html_page(&config.name, language, feed, &body)
+}
+
+/// 章节页面，带前后章导航
+fn chapter_html(
+    config: &BookConfig,
+    chapters: &[SiteChapter],
+    index: usize,
+    feed: FeedFormat,
+    language: &str,
+    labels: &Labels,
+) -> String {
+    let chapter = &chapters[index].chapter;
+    let mut body = format!(
+        "<header class=\"site-header\"><a href=\"index.html\">{}</a></header>\n<article>\n",
+        export::escape_xml(&config.name),
+    );
+    if !chapter.starts_with_heading() {
+        body.push_str(&format!("<h1>{}</h1>\n", export::escape_xml(&chapter.title)));
+    }
+    body.push_str(&export::markdown_to_xhtml(&chapter.content));
+    body.push_str("</article>\n<nav class=\"chapter-nav\">\n");
+
+    let link = |target: usize, label: &str| {
+        format!(
+            "<a href=\"{}\" title=\"{}\">{}</a>\n",
+            chapter_file(target),
+            export::escape_xml(&chapters[target].chapter.title),
+            label,
+        )
+    };
+    body.push_str(&match index.checked_sub(1) {
+        Some(previous) => link(previous, &format!("« {}", labels.previous)),
+        None => "<span></span>\n".to_string(),
+    });
+    body.push_str(&format!("<a href=\"index.html\">{}</a>\n", labels.contents));
+    body.push_str(&if index + 1 < chapters.len() {
+        link(index + 1, &format!("{} »", labels.next))
+    } else {
+        "<span></span>\n".to_string()
+    });
+    body.push_str("</nav>");
+
+    html_page(&chapter.title, language, feed, &body)
+}
+
+/// 订阅源中的章节正文（HTML 片段）
+fn feed_content(chapter: &Chapter) -> String {
+    let mut html = String::new();
+    if !chapter.starts_with_heading() {
+        html.push_str(&format!("<h1>{}</h1>\n", export::escape_xml(&chapter.title)));
+    }
+    html.push_str(&export::markdown_to_xhtml(&chapter.content));
+    html
+}
+
+/// 按发布时间从新到旧排列的章节序号
+fn newest_first(chapters: &[SiteChapter]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..chapters.len()).collect();
+    order.sort_by_key(|index| std::cmp::Reverse(chapters[*index].published));
+    order
+}
+
+fn atom_feed(config: &BookConfig, chapters: &[SiteChapter], base_url: &str, language: &str) -> String {
+    let updated = chapters
+        .iter()
+        .map(|entry| entry.published)
+        .max()
+        .unwrap_or(config.last_modified);
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="{language}">
+  <title>{title}</title>
+  <subtitle>{subtitle}</subtitle>
+  <id>urn:uuid:{id}</id>
+  <updated>{updated}</updated>
+  <author><name>{author}</name></author>
+  <link rel="alternate" type="text/html" href="{base_url}index.html"/>
+  <link rel="self" type="application/atom+xml" href="{base_url}feed.xml"/>
+  <generator>BranchWrite</generator>
+"#,
+        language = language,
+        title = export::escape_xml(&config.name),
+        subtitle = export::escape_xml(&config.description),
+        id = export::escape_xml(&config.id),
+        updated = updated.to_rfc3339_opts(SecondsFormat::Secs, true),
+        author = export::escape_xml(&config.author),
+        base_url = export::escape_xml(base_url),
+    );
+
+    for index in newest_first(chapters) {
+        let entry = &chapters[index];
+        let published = entry.published.to_rfc3339_opts(SecondsFormat::Secs, true);
+        xml.push_str(&format!(
+            r#"  <entry>
+    <title>{title}</title>
+    <id>urn:uuid:{id}</id>
+    <link rel="alternate" type="text/html" href="{base_url}{file}"/>
+    <published>{published}</published>
+    <updated>{published}</updated>
+    <content type="html">{content}</content>
+  </entry>
+"#,
+            title = export::escape_xml(&entry.chapter.title),
+            id = export::escape_xml(&entry.id),
+            base_url = export::escape_xml(base_url),
+            file = chapter_file(index),
+            published = published,
+            content = export::escape_xml(&feed_content(&entry.chapter)),
+        ));
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss_feed(config: &BookConfig, chapters: &[SiteChapter], base_url: &str, language: &str) -> String {
+    let updated = chapters
+        .iter()
+        .map(|entry| entry.published)
+        .max()
+        .unwrap_or(config.last_modified);
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0">
+<channel>
+  <title>{title}</title>
+  <link>{base_url}index.html</link>
+  <description>{description}</description>
+  <language>{language}</language>
+  <lastBuildDate>{updated}</lastBuildDate>
+  <generator>BranchWrite</generator>
+"#,
+        title = export::escape_xml(&config.name),
+        base_url = export::escape_xml(base_url),
+        description = export::escape_xml(&config.description),
+        language = language,
+        updated = updated.to_rfc2822(),
+    );
+
+    for index in newest_first(chapters) {
+        let entry = &chapters[index];
+        xml.push_str(&format!(
+            r#"  <item>
+    <title>{title}</title>
+    <link>{base_url}{file}</link>
+    <guid isPermaLink="false">urn:uuid:{id}</guid>
+    <pubDate>{published}</pubDate>
+    <description>{content}</description>
+  </item>
+"#,
+            title = export::escape_xml(&entry.chapter.title),
+            base_url = export::escape_xml(base_url),
+            file = chapter_file(index),
+            id = export::escape_xml(&entry.id),
+            published = entry.published.to_rfc2822(),
+            content = export::escape_xml(&feed_content(&entry.chapter)),
+        ));
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config() -> BookConfig {
+        BookConfig { description: "连载小说。\n\n每周更新 <两章>。".to_string(), ..BookConfig::sample("雨夜", "作者") }
+    }
+
+    fn chapters() -> Vec<SiteChapter> {
+        (1..=3)
+            .map(|n| SiteChapter {
+                id: format!("doc-{}", n),
+                chapter: Chapter { title: format!("第{}章", n), content: format!("第{}章的正文。", n) },
+                published: Utc.with_ymd_and_hms(2024, 1, n, 8, 0, 0).unwrap(),
+            })
+            .collect()
+    }
+
+    fn file<'a>(files: &'a [(String, Vec<u8>)], name: &str) -> &'a str {
+        let (_, data) = files.iter().find(|(file, _)| file == name).unwrap();
+        std::str::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn builds_index_chapters_and_navigation() {
+        let files = build(&config(), &chapters(), None, &SiteOptions::default());
+
+        let index = file(&files, "index.html");
+        assert!(index.contains("<html lang=\"zh\">"));
+        assert!(index.contains("<p>每周更新 &lt;两章&gt;。</p>"));
+        assert!(index.contains("<a href=\"chapter-002.html\">第2章</a>"));
+
+        let first = file(&files, "chapter-001.html");
+        assert!(first.contains("<h1>第1章</h1>"));
+        assert!(!first.contains("上一章"));
+        assert!(first.contains("<a href=\"chapter-002.html\" title=\"第2章\">下一章 »</a>"));
+        let middle = file(&files, "chapter-002.html");
+        assert!(middle.contains("href=\"chapter-001.html\""));
+        assert!(middle.contains("href=\"chapter-003.html\""));
+        let last = file(&files, "chapter-003.html");
+        assert!(!last.contains("下一章"));
+
+    }
+
+    #[test]
+    fn feed_defaults_to_atom_only_with_a_site_url() {
+        let files = build(&config(), &chapters(), None, &SiteOptions::default());
+        assert!(!files.iter().any(|(name, _)| name == "feed.xml"));
+        assert!(!file(&files, "index.html").contains("application/atom+xml"));
+
+        let options = SiteOptions { site_url: Some("https://example.com".to_string()), ..SiteOptions::default() };
+        let files = build(&config(), &chapters(), None, &options);
+        assert!(file(&files, "feed.xml").contains("href=\"https://example.com/chapter-001.html\""));
+    }
+
+    #[test]
+    fn feeds_use_publish_dates_and_absolute_links() {
+        let options = SiteOptions { feed: Some(FeedFormat::Atom), site_url: Some("https://example.com/novel/".to_string()) };
+        let files = build(&config(), &chapters(), None, &options);
+        let atom = file(&files, "feed.xml");
+        assert!(atom.contains("<updated>2024-01-03T08:00:00Z</updated>"));
+        assert!(atom.contains("href=\"https://example.com/novel/chapter-001.html\""));
+        assert!(atom.contains("&lt;p&gt;第1章的正文。&lt;/p&gt;"));
+        // 最新的一章排在最前
+        assert!(atom.find("<id>urn:uuid:doc-3</id>").unwrap() < atom.find("<id>urn:uuid:doc-1</id>").unwrap());
+
+        let options = SiteOptions { feed: Some(FeedFormat::Rss), site_url: None };
+        let files = build(&config(), &chapters(), None, &options);
+        let rss = file(&files, "rss.xml");
+        assert!(rss.contains("<pubDate>Mon, 1 Jan 2024 08:00:00 +0000</pubDate>"));
+        assert!(!files.iter().any(|(name, _)| name == "feed.xml"));
+
+        let options = SiteOptions { feed: Some(FeedFormat::None), site_url: None };
+        let files = build(&config(), &chapters(), None, &options);
+        assert!(!file(&files, "index.html").contains("alternate"));
+    }
+}
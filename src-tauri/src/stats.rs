@@ -0,0 +1,126 @@
+use pulldown_cmark::{Event, Parser, TagEnd};
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::text;
+
+/// 文本统计结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextStats {
+    pub word_count: u32,      // 中日韩文字逐字计数，拉丁文字按词计数
+    pub character_count: u32, // 按字素簇计数，不含换行
+    pub character_count_no_spaces: u32,
+    pub cjk_character_count: u32,
+    pub latin_word_count: u32,
+    pub line_count: u32,
+    pub paragraph_count: u32,
+}
+
+/// 计算文本统计
+///
+/// `count_markdown_syntax` 为 false 时先去掉 Markdown 标记，只统计正文。
+pub fn compute(content: &str, count_markdown_syntax: bool) -> TextStats {
+    let line_count = content.split('\n').count() as u32;
+
+    let plain;
+    let text = if count_markdown_syntax {
+        content
+    } else {
+        plain = strip_markdown(content);
+        plain.as_str()
+    };
+
+    let mut stats = TextStats {
+        line_count,
+        ..TextStats::default()
+    };
+
+    for grapheme in text.graphemes(true) {
+        if grapheme == "\n" || grapheme == "\r\n" || grapheme == "\r" {
+            continue;
+        }
+        stats.character_count += 1;
+        if !grapheme.chars().all(char::is_whitespace) {
+            stats.character_count_no_spaces += 1;
+        }
+    }
+
+    for word in text.unicode_words() {
+        let cjk = word.chars().filter(|c| text::is_cjk(*c)).count() as u32;
+        if cjk == 0 {
+            stats.latin_word_count += 1;
+            continue;
+        }
+
+        stats.cjk_character_count += cjk;
+        // 混排的词（如“第3章”）中非中日韩的部分按一个词计
+        let rest: String = word.chars().map(|c| if text::is_cjk(c) { ' ' } else { c }).collect();
+        stats.latin_word_count += rest.split_whitespace().count() as u32;
+    }
+
+    stats.word_count = stats.cjk_character_count + stats.latin_word_count;
+    stats.paragraph_count = text
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .count() as u32;
+
+    stats
+}
+
+/// 去掉 Markdown 标记，保留正文文本
+pub fn strip_markdown(content: &str) -> String {
+    let mut plain = String::with_capacity(content.len());
+
+    for event in Parser::new(content) {
+        match event {
+            Event::Text(text) | Event::Code(text) => plain.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => plain.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock | TagEnd::TableCell) => {
+                plain.push('\n');
+            }
+            _ => {}
+        }
+    }
+
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_chinese_characters_individually() {
+        let stats = compute("春眠不觉晓，处处闻啼鸟。", true);
+        assert_eq!(stats.word_count, 10);
+        assert_eq!(stats.cjk_character_count, 10);
+        assert_eq!(stats.character_count, 12);
+    }
+
+    #[test]
+    fn counts_latin_words_and_mixed_text() {
+        let stats = compute("He didn't know 她的名字 in 2024.", true);
+        assert_eq!(stats.latin_word_count, 5);
+        assert_eq!(stats.cjk_character_count, 4);
+        assert_eq!(stats.word_count, 9);
+        assert_eq!(stats.character_count_no_spaces, 23);
+    }
+
+    #[test]
+    fn counts_graphemes_not_bytes() {
+        let stats = compute("e\u{301}👨‍👩‍👧\n", true);
+        assert_eq!(stats.character_count, 2);
+        assert_eq!(stats.line_count, 2);
+    }
+
+    #[test]
+    fn markdown_syntax_is_optional() {
+        let content = "# 第一章\n\n**她**走进了[房间](http://example.com)。\n";
+        let plain = compute(content, false);
+        let raw = compute(content, true);
+
+        assert_eq!(plain.word_count, 9);
+        assert!(raw.character_count > plain.character_count);
+        assert_eq!(plain.paragraph_count, 2);
+    }
+}
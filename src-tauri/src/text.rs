@@ -0,0 +1,71 @@
+/// 是否为中日韩文字（每个字单独计为一个词）
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3400..=0x4DBF      // CJK 扩展 A
+        | 0x4E00..=0x9FFF    // CJK 基本区
+        | 0xF900..=0xFAFF    // CJK 兼容汉字
+        | 0x20000..=0x2EBEF  // CJK 扩展 B-F
+        | 0x30000..=0x3134F  // CJK 扩展 G
+        | 0x3040..=0x30FF    // 平假名、片假名
+        | 0x31F0..=0x31FF    // 片假名扩展
+        | 0xAC00..=0xD7AF    // 韩文音节
+    )
+}
+
+/// 按词切分文本：中日韩文字逐字切分，拉丁文字按连续的字母数字切分，
+/// 空白和标点各自成为单独的词元，拼接后与原文完全一致
+pub fn split_words(text: &str) -> Vec<&str> {
+    #[derive(PartialEq, Clone, Copy)]
+    enum Kind {
+        Word,
+        Space,
+        Single,
+    }
+
+    let kind_of = |c: char| {
+        if is_cjk(c) || c == '\n' {
+            Kind::Single
+        } else if c.is_alphanumeric() || c == '_' {
+            Kind::Word
+        } else if c.is_whitespace() {
+            Kind::Space
+        } else {
+            Kind::Single
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut current: Option<Kind> = None;
+
+    for (i, c) in text.char_indices() {
+        let kind = kind_of(c);
+
+        // 单词内部的撇号（如 don't）不拆开
+        let joins_word = current == Some(Kind::Word)
+            && (c == '\'' || c == '’')
+            && text[i + c.len_utf8()..].chars().next().is_some_and(|next| kind_of(next) == Kind::Word);
+
+        let continues = joins_word || (current == Some(kind) && kind != Kind::Single);
+        if !continues {
+            if i > start {
+                tokens.push(&text[start..i]);
+            }
+            start = i;
+        }
+        current = Some(if joins_word { Kind::Word } else { kind });
+    }
+
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+
+    tokens
+}
+
+/// 按字符切分文本
+pub fn split_chars(text: &str) -> Vec<&str> {
+    text.char_indices()
+        .map(|(i, c)| &text[i..i + c.len_utf8()])
+        .collect()
+}
@@ -0,0 +1,241 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic;
+use crate::file_system::DocumentConfig;
+
+/// 条目信息的文件名
+const ENTRY_FILE: &str = "entry.json";
+
+/// 条目中保存被删除目录的子目录名
+const ITEM_DIR: &str = "item";
+
+/// 回收站设置的文件名
+const SETTINGS_FILE: &str = "settings.json";
+
+/// 回收站中的内容类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Book,
+    Document,
+}
+
+/// 回收站中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub kind: TrashKind,
+    pub book_id: String,
+    pub title: String, // 书名或文档标题
+    pub deleted_at: DateTime<Utc>,
+    #[serde(default)]
+    pub document: Option<DocumentConfig>, // 删除前的文档配置，恢复时据此放回原来的位置
+    #[serde(default)]
+    pub children: Vec<String>, // 删除文档时上移一级的子文档，恢复时重新归到它下面
+}
+
+/// 回收站设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashSettings {
+    pub retention_days: Option<u32>, // 超过天数自动清除，为空时一直保留
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self { retention_days: Some(30) }
+    }
+}
+
+/// 回收站，每项占一个目录：条目信息和移进来的书籍或文档目录
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn entry_dir(&self, entry_id: &str) -> PathBuf {
+        self.dir.join(entry_id)
+    }
+
+    /// 把目录移进回收站
+    ///
+    /// 先写条目信息再移动目录，移动失败时撤销条目，不会留下没有内容的条目。
+    pub fn put(&self, source: &Path, entry: &TrashEntry) -> Result<()> {
+        let entry_dir = self.entry_dir(&entry.id);
+        fs::create_dir_all(&entry_dir)
+            .context("Failed to create trash entry")?;
+
+        let entry_json = serde_json::to_string_pretty(entry)
+            .context("Failed to serialize trash entry")?;
+        atomic::write_file(&entry_dir.join(ENTRY_FILE), entry_json)
+            .context("Failed to write trash entry")?;
+
+        if let Err(e) = fs::rename(source, entry_dir.join(ITEM_DIR)) {
+            let _ = fs::remove_dir_all(&entry_dir);
+            return Err(e).context("Failed to move item to trash");
+        }
+        Ok(())
+    }
+
+    /// 列出回收站中的所有条目，最近删除的在前
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir).context("Failed to read trash directory")?.flatten() {
+            if !dir_entry.path().is_dir() {
+                continue;
+            }
+            match Self::read_entry(&dir_entry.path()) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping unreadable trash entry {}: {}", dir_entry.path().display(), e),
+            }
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+        Ok(entries)
+    }
+
+    fn read_entry(entry_dir: &Path) -> Result<TrashEntry> {
+        let entry_json = fs::read_to_string(entry_dir.join(ENTRY_FILE))
+            .context("Failed to read trash entry")?;
+        serde_json::from_str(&entry_json)
+            .context("Failed to parse trash entry")
+    }
+
+    /// 读取一个条目
+    pub fn get(&self, entry_id: &str) -> Result<TrashEntry> {
+        let entry_dir = self.entry_dir(entry_id);
+        if !entry_dir.join(ENTRY_FILE).exists() {
+            return Err(anyhow::anyhow!("Trash entry not found: {}", entry_id));
+        }
+        Self::read_entry(&entry_dir)
+    }
+
+    /// 把条目中的目录移回 `target`，然后删除条目
+    pub fn take(&self, entry_id: &str, target: &Path) -> Result<()> {
+        if target.exists() {
+            return Err(anyhow::anyhow!("Cannot restore: {} already exists", target.display()));
+        }
+
+        let entry_dir = self.entry_dir(entry_id);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create restore directory")?;
+        }
+        fs::rename(entry_dir.join(ITEM_DIR), target)
+            .context("Failed to restore item from trash")?;
+        fs::remove_dir_all(&entry_dir)
+            .context("Failed to remove restored trash entry")
+    }
+
+    /// 永久删除所有条目，返回删除的数量
+    pub fn empty(&self) -> Result<usize> {
+        let entries = self.list()?;
+        for entry in &entries {
+            fs::remove_dir_all(self.entry_dir(&entry.id))
+                .with_context(|| format!("Failed to delete trash entry {}", entry.title))?;
+        }
+        Ok(entries.len())
+    }
+
+    /// 永久删除超过保留天数的条目，返回删除的数量
+    pub fn purge_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        let Some(retention_days) = self.load_settings()?.retention_days else { return Ok(0) };
+        let cutoff = now - Duration::days(i64::from(retention_days));
+
+        let mut purged = 0;
+        for entry in self.list()? {
+            if entry.deleted_at < cutoff {
+                fs::remove_dir_all(self.entry_dir(&entry.id))
+                    .with_context(|| format!("Failed to purge trash entry {}", entry.title))?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// 读取回收站设置，没有设置文件时使用默认值
+    pub fn load_settings(&self) -> Result<TrashSettings> {
+        let settings_path = self.dir.join(SETTINGS_FILE);
+        if !settings_path.exists() {
+            return Ok(TrashSettings::default());
+        }
+
+        let settings_json = fs::read_to_string(&settings_path)
+            .context("Failed to read trash settings")?;
+        serde_json::from_str(&settings_json)
+            .context("Failed to parse trash settings")
+    }
+
+    pub fn save_settings(&self, settings: &TrashSettings) -> Result<()> {
+        let settings_json = serde_json::to_string_pretty(settings)
+            .context("Failed to serialize trash settings")?;
+        atomic::write_file(&self.dir.join(SETTINGS_FILE), settings_json)
+            .context("Failed to write trash settings")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(id: &str, deleted_at: DateTime<Utc>) -> TrashEntry {
+        TrashEntry {
+            id: id.to_string(),
+            kind: TrashKind::Book,
+            book_id: id.to_string(),
+            title: id.to_string(),
+            deleted_at,
+            document: None,
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn moves_items_in_and_out_and_purges_old_entries() {
+        let dir = TempDir::new().unwrap();
+        let trash = Trash::new(dir.path().join("trash"));
+        let source = dir.path().join("books").join("book-1");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("config.json"), "{}").unwrap();
+
+        let now = Utc::now();
+        trash.put(&source, &entry("new", now)).unwrap();
+        assert!(!source.exists());
+
+        let old_source = dir.path().join("books").join("book-2");
+        fs::create_dir_all(&old_source).unwrap();
+        trash.put(&old_source, &entry("old", now - Duration::days(40))).unwrap();
+
+        let ids: Vec<String> = trash.list().unwrap().into_iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec!["new", "old"]);
+
+        // 原位置已被占用时不覆盖
+        fs::create_dir_all(&source).unwrap();
+        assert!(trash.take("new", &source).is_err());
+        fs::remove_dir(&source).unwrap();
+        trash.take("new", &source).unwrap();
+        assert_eq!(fs::read_to_string(source.join("config.json")).unwrap(), "{}");
+        assert!(trash.get("new").is_err());
+
+        // 默认保留 30 天
+        assert_eq!(trash.purge_expired(now).unwrap(), 1);
+        assert!(trash.list().unwrap().is_empty());
+
+        trash.save_settings(&TrashSettings { retention_days: None }).unwrap();
+        trash.put(&source, &entry("kept", now - Duration::days(400))).unwrap();
+        assert_eq!(trash.purge_expired(now).unwrap(), 0);
+        assert_eq!(trash.empty().unwrap(), 1);
+    }
+}
@@ -114,7 +114,7 @@ describe('存储适配器测试', () => {
       expect(doc).toBeDefined()
       expect(doc.title).toBe('第一章')
       expect(doc.book_id).toBe(bookId)
-      expect(doc.type).toBe('chapter')
+      expect(doc.doc_type).toBe('chapter')
     })
 
     it('应该列出书籍的所有文档', async () => {
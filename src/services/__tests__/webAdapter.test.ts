@@ -75,12 +75,12 @@ describe('WebFileSystemAdapter', () => {
       const doc = await WebFileSystemAdapter.createDocument(
         project.id,
         '第一章',
-        '章节'
+        'chapter'
       )
 
       expect(doc).toBeDefined()
       expect(doc.title).toBe('第一章')
-      expect(doc.type).toBe('章节')
+      expect(doc.doc_type).toBe('chapter')
       expect(doc.book_id).toBe(project.id)
       expect(doc.word_count).toBe(0)
       expect(doc.character_count).toBe(0)
@@ -89,8 +89,8 @@ describe('WebFileSystemAdapter', () => {
     it('应该将文档添加到文档列表中', async () => {
       const project = await WebFileSystemAdapter.createProject('测试项目', '描述', '作者')
       
-      await WebFileSystemAdapter.createDocument(project.id, '第一章', '章节')
-      await WebFileSystemAdapter.createDocument(project.id, '第二章', '章节')
+      await WebFileSystemAdapter.createDocument(project.id, '第一章', 'chapter')
+      await WebFileSystemAdapter.createDocument(project.id, '第二章', 'chapter')
 
       const docs = await WebFileSystemAdapter.listDocuments(project.id)
       expect(docs).toHaveLength(2)
@@ -100,7 +100,7 @@ describe('WebFileSystemAdapter', () => {
   describe('saveDocument and loadDocument', () => {
     it('应该保存和加载文档内容', async () => {
       const project = await WebFileSystemAdapter.createProject('测试项目', '描述', '作者')
-      const doc = await WebFileSystemAdapter.createDocument(project.id, '第一章', '章节')
+      const doc = await WebFileSystemAdapter.createDocument(project.id, '第一章', 'chapter')
       
       const content = '这是测试内容，包含一些文字。'
       await WebFileSystemAdapter.saveDocument(project.id, doc.id, content)
@@ -111,7 +111,7 @@ describe('WebFileSystemAdapter', () => {
 
     it('保存文档时应该更新字数统计', async () => {
       const project = await WebFileSystemAdapter.createProject('测试项目', '描述', '作者')
-      const doc = await WebFileSystemAdapter.createDocument(project.id, '第一章', '章节')
+      const doc = await WebFileSystemAdapter.createDocument(project.id, '第一章', 'chapter')
       
       const content = '这是 测试 内容'
       await WebFileSystemAdapter.saveDocument(project.id, doc.id, content)
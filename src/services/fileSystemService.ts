@@ -1,4 +1,5 @@
 import { WebFileSystemAdapter } from './webAdapter';
+import type { DiffChange, DiffResult } from '../types/index';
 
 // 环境检测
 const isTauriEnvironment = () => {
@@ -36,6 +37,17 @@ class TauriError extends Error {
   }
 }
 
+// 通用文件命令返回的错误，kind 为 io 以外的值表示路径不在允许访问的位置
+export interface FileAccessError {
+  kind: 'not_absolute' | 'parent_traversal' | 'outside_allowed_roots' | 'symlink_escape' | 'io';
+  path: string;
+  message: string;
+}
+
+function isFileAccessError(error: unknown): error is FileAccessError {
+  return typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
+}
+
 // 统一的错误处理函数
 async function handleTauriCall<T>(
   command: string,
@@ -58,6 +70,8 @@ async function handleTauriCall<T>(
       friendlyMessage = error;
     } else if (error instanceof Error) {
       friendlyMessage = error.message;
+    } else if (isFileAccessError(error)) {
+      friendlyMessage = error.message;
     }
 
     throw new TauriError(friendlyMessage, error);
@@ -85,6 +99,7 @@ export interface ProjectSettings {
   font_size: number;
   line_height: number;
   font_family: string;
+  count_markdown_syntax?: boolean; // 统计字数时是否计入 Markdown 标记
 }
 
 export interface DocumentMetadata {
@@ -106,6 +121,63 @@ export interface CommitInfo {
   document_hash: string;
   word_count: number;
   character_count: number;
+  parent_ids?: string[];
+  branch_id?: string;
+  group_id?: string; // 同一次批量操作（如查找替换）产生的提交共用一个分组
+}
+
+export interface BranchInfo {
+  id: string;
+  name: string;
+  head_commit_id?: string;
+  created_at: string;
+  last_modified: string;
+}
+
+export interface DocumentRefs {
+  head: string; // 当前分支 ID
+  branches: BranchInfo[];
+}
+
+export type MergeRegion =
+  | { type: 'resolved'; text: string }
+  | { type: 'conflict'; base: string; ours: string; theirs: string };
+
+export interface MergeResult {
+  regions: MergeRegion[];
+  conflict_count: number;
+  content?: string; // 无冲突时的合并文本
+}
+
+export interface DocumentMergeOutcome {
+  base_commit_id?: string;
+  ours_commit_id?: string;
+  theirs_commit_id: string;
+  result: MergeResult;
+  commit?: CommitInfo; // 无冲突时自动生成的合并提交
+}
+
+export type DiffGranularity = 'line' | 'word' | 'char';
+
+export interface DiffHunk {
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  changes: DiffChange[];
+}
+
+export interface DiffStats {
+  additions: number;
+  deletions: number;
+  addedCharacters: number;
+  removedCharacters: number;
+}
+
+export interface VersionDiffResult extends DiffResult {
+  granularity: DiffGranularity;
+  hunks: DiffHunk[];
+  stats: DiffStats;
 }
 
 export interface ProjectData {
@@ -116,6 +188,176 @@ export interface ProjectData {
   commit_data: Record<string, string>;
 }
 
+export interface TextStats {
+  word_count: number; // 中日韩文字逐字计数，拉丁文字按词计数
+  character_count: number; // 按字素簇计数，不含换行
+  character_count_no_spaces: number;
+  cjk_character_count: number;
+  latin_word_count: number;
+  line_count: number;
+  paragraph_count: number;
+}
+
+export interface RecoverableDocument {
+  book_id: string;
+  book_name: string;
+  document_id: string;
+  document_title: string;
+  saved_at?: string; // content.md 的最后写入时间
+  journaled_at: string; // 日志中最后一次编辑的时间
+  content: string; // 重放日志得到的内容
+}
+
+export interface ManuscriptOptions {
+  font_family?: string; // 默认 Times New Roman
+  font_size?: number; // 磅，默认 12
+  double_spacing?: boolean;
+  page_number_header?: boolean; // 页眉显示“作者 / 书名 / 页码”
+  title_page?: boolean;
+  chapter_page_break?: boolean; // 每章另起一页
+}
+
+export type TrimSize =
+  | 'a4'
+  | 'a5'
+  | 'letter'
+  | 'us_trade' // 6 × 9 英寸
+  | 'digest' // 5.5 × 8.5 英寸
+  | 'pocket' // 4.25 × 6.87 英寸
+  | { custom: { width_mm: number; height_mm: number } };
+
+export interface PdfOptions {
+  trim_size?: TrimSize; // 默认 A5
+  margins?: { top?: number; bottom?: number; inner?: number; outer?: number }; // 毫米
+  font_family?: string; // 默认使用书籍设置中的字体
+  font_path?: string; // 直接指定 TrueType 字体文件（.ttf/.ttc，须通过 selectFontFile 选择），CFF 轮廓的 .otf 无法嵌入
+  font_size?: number; // 磅，默认按书籍设置换算
+  line_height?: number; // 磅，默认按书籍设置换算
+  running_headers?: boolean; // 左页书名，右页章节名
+  page_numbers?: boolean;
+  chapter_page_break?: boolean;
+  title_page?: boolean;
+}
+
+export interface SiteOptions {
+  feed?: 'none' | 'atom' | 'rss'; // 默认设置了 site_url 时生成 Atom，否则不生成
+  site_url?: string; // 站点的公开地址，订阅源中的链接据此生成绝对地址
+}
+
+export type SplitRule =
+  | { type: 'auto' } // 按重复出现的最高级标题，其次按“第N章”“Chapter N”等格式
+  | { type: 'heading'; max_level: number }
+  | { type: 'pattern'; pattern: string } // 正则表达式，匹配的行作为章节标题
+  | { type: 'none' };
+
+export interface ImportOptions {
+  name?: string; // 默认取文件中的书名标题或文件名
+  description?: string;
+  author?: string;
+  genre?: string;
+  split?: SplitRule; // EPUB 总是按书脊拆分
+}
+
+export interface SearchOptions {
+  book_ids?: string[]; // 只搜索这些书籍，默认搜索全部
+  limit?: number; // 默认 200
+}
+
+export interface SearchHit {
+  book_id: string;
+  book_name: string;
+  document_id: string;
+  document_title: string;
+  line: number; // 从 1 开始
+  snippet: string;
+  highlights: { start: number; end: number }[]; // 片段内的高亮范围
+}
+
+export interface SearchResults {
+  hits: SearchHit[];
+  total: number; // 匹配的总行数，可能多于返回的结果
+}
+
+export interface HistoryMatch {
+  book_id: string;
+  document_id: string;
+  document_title: string;
+  change: 'appeared' | 'disappeared';
+  commit: CommitInfo;
+  line: number;
+  snippet: string; // 出现时的版本，或消失前的父版本中的片段
+  highlights: { start: number; end: number }[];
+}
+
+export interface ReplaceOptions {
+  find: string;
+  replace: string;
+  regex?: boolean; // 按正则表达式查找，替换文本可用 $1 引用分组
+  whole_word?: boolean;
+  case_sensitive?: boolean;
+  document_ids?: string[]; // 为空时替换全部文档
+  statuses?: string[]; // 为空时不限状态
+  dry_run?: boolean; // 只预览，不修改文档
+}
+
+export interface ReplaceMatch {
+  document_id: string;
+  document_title: string;
+  line: number;
+  before: string;
+  matched: string;
+  replacement: string;
+  after: string;
+}
+
+export interface ReplaceResult {
+  group_id: string | null; // 预览或没有匹配时为空
+  matches: ReplaceMatch[];
+  documents: string[];
+  replacement_count: number;
+  failed: string[]; // 读取或替换失败、保持原样的文档
+}
+
+export interface RevertResult {
+  group_id: string | null;
+  documents: string[];
+  conflicts: string[]; // 与之后的编辑冲突、未能恢复的文档
+}
+
+export interface TrashEntry {
+  id: string;
+  kind: 'book' | 'document';
+  book_id: string;
+  title: string;
+  deleted_at: string;
+  document: DocumentConfig | null; // 删除前的文档配置
+  children: string[]; // 删除时上移一级的子文档
+}
+
+export interface TrashSettings {
+  retention_days: number | null; // 超过天数自动清除，为空时一直保留
+}
+
+export interface ProjectMigrationReport {
+  project_id: string;
+  project_name: string;
+  status: 'migrated' | 'already_migrated' | 'failed';
+  book_id: string; // 沿用项目 ID
+  document_id: string | null;
+  commits_migrated: number;
+  missing_commits: string[]; // 找不到内容、未能迁移的提交
+  dropped_settings: string[]; // 书籍设置中没有对应项的项目设置
+  error: string | null;
+}
+
+export interface RepackStats {
+  objects: number;
+  full_objects: number;
+  delta_objects: number;
+  bytes_before: number;
+  bytes_after: number;
+}
+
 export interface FileInfo {
   exists: boolean;
   is_file: boolean;
@@ -144,6 +386,7 @@ export interface BookSettings {
   font_size: number;
   line_height: number;
   font_family: string;
+  count_markdown_syntax?: boolean; // 统计字数时是否计入 Markdown 标记
 }
 
 export interface BookConfig {
@@ -162,14 +405,34 @@ export interface BookConfig {
 export interface DocumentConfig {
   id: string;
   book_id: string;
+  parent_id?: string | null; // 上级文档（卷或章），顶层文档为空
   title: string;
-  order: number;
-  type: string; // 'chapter' | 'section' | 'note'
+  order: number; // 同一上级文档下的顺序
+  doc_type: DocumentType;
   created_at: string;
   last_modified: string;
   word_count: number;
   character_count: number;
-  status: string; // 'draft' | 'review' | 'final'
+  status: string; // 书籍状态流程中的状态，默认为 'draft' | 'review' | 'final'
+}
+
+export type DocumentType = 'part' | 'chapter' | 'scene' | 'section' | 'note';
+
+export interface StatusWorkflow {
+  statuses: string[]; // 新文档使用第一个状态
+  transitions: { from: string; to: string }[];
+}
+
+export interface StatusTransition {
+  from: string;
+  to: string;
+  timestamp: string;
+}
+
+export interface DocumentNode extends DocumentConfig {
+  total_word_count: number; // 包含所有下级文档，笔记不计入
+  total_character_count: number;
+  children: DocumentNode[];
 }
 
 export interface BookData {
@@ -241,16 +504,13 @@ export class FileSystemService {
       return await WebFileSystemAdapter.exportDocument(filename, content, format);
     }
 
-    // Tauri 环境使用原生文件保存对话框
+    // Tauri 环境使用原生文件保存对话框，选择的位置才允许写入
     try {
-      const { save } = await import('@tauri-apps/plugin-dialog');
-      
       // 设置文件过滤器
       const ext = format === 'markdown' ? 'md' : 'txt';
-      const filters = [{
-        name: format === 'markdown' ? 'Markdown' : 'Text',
-        extensions: [ext]
-      }];
+      const filters: Array<[string, string[]]> = [
+        [format === 'markdown' ? 'Markdown' : 'Text', [ext]]
+      ];
 
       // 获取默认保存路径（用户文档目录）
       let defaultPath = filename;
@@ -262,10 +522,7 @@ export class FileSystemService {
       }
 
       // 显示保存对话框
-      const filePath = await save({
-        defaultPath,
-        filters
-      });
+      const filePath = await FileSystemService.selectSaveFile(defaultPath, filters);
 
       if (filePath) {
         // 保存文件
@@ -285,6 +542,27 @@ export class FileSystemService {
     return await handleTauriCall<Record<string, any>>('get_project_stats', { project_id: projectId });
   }
 
+  /**
+   * 重建项目历史的增量链
+   */
+  static async repackProject(projectId: string): Promise<RepackStats> {
+    return await handleTauriCall<RepackStats>('repack_project', { projectId });
+  }
+
+  /**
+   * 把旧项目迁移为书籍，已迁移的项目不会重复处理
+   */
+  static async migrateProject(projectId: string): Promise<ProjectMigrationReport> {
+    return await handleTauriCall<ProjectMigrationReport>('migrate_project', { projectId });
+  }
+
+  /**
+   * 迁移所有旧项目
+   */
+  static async migrateAllProjects(): Promise<ProjectMigrationReport[]> {
+    return await handleTauriCall<ProjectMigrationReport[]>('migrate_all_projects');
+  }
+
   /**
    * 选择文件夹
    */
@@ -299,6 +577,27 @@ export class FileSystemService {
     return await handleTauriCall<string | null>('select_file', { filters });
   }
 
+  /**
+   * 选择 PDF 导出用的字体文件
+   *
+   * 只支持 TrueType 轮廓的字体，CFF 轮廓的 OpenType 字体（多数 .otf）无法嵌入 PDF
+   */
+  static async selectFontFile(): Promise<string | null> {
+    return await FileSystemService.selectFile([['TrueType 字体', ['ttf', 'ttc']]]);
+  }
+
+  /**
+   * 选择保存位置
+   *
+   * 导入、导出和通用文件命令只接受数据目录或通过 selectFolder/selectFile/selectSaveFile 选择过的路径
+   */
+  static async selectSaveFile(
+    defaultPath: string | null,
+    filters: Array<[string, string[]]>
+  ): Promise<string | null> {
+    return await handleTauriCall<string | null>('select_save_file', { defaultPath, filters });
+  }
+
   /**
    * 显示消息对话框
    */
@@ -423,6 +722,13 @@ export class FileSystemService {
     return await handleTauriCall<BookData>('create_book', { name, description, author, genre });
   }
 
+  /**
+   * 从稿件文件（.md/.txt/.docx/.epub）或文件夹导入新书籍；DOCX/EPUB 的作者和简介会在未填写时沿用
+   */
+  static async importBook(sourcePath: string, options?: ImportOptions): Promise<BookData> {
+    return await handleTauriCall<BookData>('import_book', { sourcePath, options });
+  }
+
   /**
    * 列出所有书籍
    */
@@ -524,7 +830,7 @@ export class FileSystemService {
   }
 
   /**
-   * 删除书籍
+   * 删除书籍（桌面端移入回收站，可恢复）
    */
   static async deleteBook(bookId: string): Promise<void> {
     if (!isTauriEnvironment()) {
@@ -533,22 +839,136 @@ export class FileSystemService {
     return await handleTauriCall<void>('delete_book', { bookId });
   }
 
+  // ===== 回收站方法 =====
+
+  /**
+   * 列出回收站中的书籍和文档，最近删除的在前
+   */
+  static async listTrash(): Promise<TrashEntry[]> {
+    return await handleTauriCall<TrashEntry[]>('list_trash');
+  }
+
+  /**
+   * 从回收站恢复书籍或文档
+   */
+  static async restoreFromTrash(entryId: string): Promise<TrashEntry> {
+    return await handleTauriCall<TrashEntry>('restore_from_trash', { entryId });
+  }
+
+  /**
+   * 永久删除回收站中的所有内容，返回删除的数量
+   */
+  static async emptyTrash(): Promise<number> {
+    return await handleTauriCall<number>('empty_trash');
+  }
+
+  /**
+   * 获取回收站设置
+   */
+  static async getTrashSettings(): Promise<TrashSettings> {
+    return await handleTauriCall<TrashSettings>('get_trash_settings');
+  }
+
+  /**
+   * 保存回收站设置，并立即清除超过保留天数的内容
+   */
+  static async saveTrashSettings(settings: TrashSettings): Promise<TrashSettings> {
+    return await handleTauriCall<TrashSettings>('save_trash_settings', { settings });
+  }
+
+  // ===== 全文搜索方法 =====
+
+  /**
+   * 全文搜索：空格分隔的词需全部出现，引号内为短语，末尾加 * 按前缀匹配
+   */
+  static async search(query: string, options?: SearchOptions): Promise<SearchResults> {
+    return await handleTauriCall<SearchResults>('search', { query, options });
+  }
+
+  /**
+   * 在版本历史中查找内容出现和消失的提交，不指定文档时搜索整本书
+   */
+  static async searchHistory(bookId: string, query: string, documentId?: string): Promise<HistoryMatch[]> {
+    return await handleTauriCall<HistoryMatch[]>('search_history', { bookId, documentId, query });
+  }
+
+  // ===== 查找替换方法 =====
+
+  /**
+   * 在整本书中查找替换，dry_run 时只返回匹配预览
+   */
+  static async replaceInBook(bookId: string, options: ReplaceOptions): Promise<ReplaceResult> {
+    return await handleTauriCall<ReplaceResult>('replace_in_book', { bookId, options });
+  }
+
+  /**
+   * 撤销一组提交（如一次查找替换），保留之后的编辑
+   */
+  static async revertCommitGroup(bookId: string, groupId: string): Promise<RevertResult> {
+    return await handleTauriCall<RevertResult>('revert_commit_group', { bookId, groupId });
+  }
+
+  // ===== 书籍导出方法 =====
+
+  /**
+   * 导出书籍为 EPUB（按章节顺序，不含笔记）
+   */
+  static async exportBook(bookId: string, exportPath: string): Promise<void> {
+    return await handleTauriCall<void>('export_book', { bookId, exportPath });
+  }
+
+  /**
+   * 导出书籍为投稿格式的 DOCX
+   */
+  static async exportBookDocx(bookId: string, exportPath: string, options?: ManuscriptOptions): Promise<void> {
+    return await handleTauriCall<void>('export_book_docx', { bookId, exportPath, options });
+  }
+
+  /**
+   * 导出书籍为 PDF
+   */
+  static async exportBookPdf(bookId: string, exportPath: string, options?: PdfOptions): Promise<void> {
+    return await handleTauriCall<void>('export_book_pdf', { bookId, exportPath, options });
+  }
+
+  /**
+   * 导出单个文档为 PDF
+   */
+  static async exportDocumentPdf(
+    bookId: string,
+    documentId: string,
+    exportPath: string,
+    options?: PdfOptions
+  ): Promise<void> {
+    return await handleTauriCall<void>('export_document_pdf', { bookId, documentId, exportPath, options });
+  }
+
+  /**
+   * 导出书籍为静态网站
+   *
+   * 目录中的 .branchwrite-site.json 记录导出生成的文件，再次导出时只清理其中不再生成的文件
+   */
+  static async exportBookSite(bookId: string, exportDir: string, options?: SiteOptions): Promise<void> {
+    return await handleTauriCall<void>('export_book_site', { bookId, exportDir, options });
+  }
+
   // ===== 文档管理方法 =====
 
   /**
-   * 创建新文档
+   * 创建新文档，可指定上级文档（Web 环境下始终为顶层文档）
    */
   static async createDocument(
     bookId: string,
     title: string,
-    docType: string
+    docType: DocumentType,
+    parentId?: string
   ): Promise<DocumentConfig> {
     if (!isTauriEnvironment()) {
       console.log('🌐 FileSystemService: 使用 Web 环境（localStorage）')
       return await WebFileSystemAdapter.createDocument(bookId, title, docType);
     }
     console.log('🖥️  FileSystemService: 使用 Tauri 环境（文件系统）')
-    return await handleTauriCall<DocumentConfig>('create_document', { bookId, title, docType });
+    return await handleTauriCall<DocumentConfig>('create_document', { bookId, title, docType, parentId });
   }
 
   /**
@@ -586,7 +1006,7 @@ export class FileSystemService {
   }
 
   /**
-   * 删除文档
+   * 删除文档（桌面端移入回收站，可恢复到原来的位置）
    */
   static async deleteDocument(bookId: string, documentId: string): Promise<void> {
     if (!isTauriEnvironment()) {
@@ -603,6 +1023,313 @@ export class FileSystemService {
     }
     return await handleTauriCall<void>('delete_document', { bookId, documentId });
   }
+
+  /**
+   * 按当前设置重新统计书籍中所有文档
+   */
+  static async refreshBookStats(bookId: string): Promise<BookData> {
+    return await handleTauriCall<BookData>('refresh_book_stats', { bookId });
+  }
+
+  /**
+   * 统计文本（供编辑器实时显示）
+   */
+  static async getTextStats(content: string, countMarkdownSyntax: boolean = false): Promise<TextStats> {
+    return await handleTauriCall<TextStats>('get_text_stats', { content, countMarkdownSyntax });
+  }
+
+  // ===== 状态流程方法 =====
+
+  /**
+   * 获取书籍的状态流程，没有自定义时为默认的 draft → review → final
+   */
+  static async getStatusWorkflow(bookId: string): Promise<StatusWorkflow> {
+    return await handleTauriCall<StatusWorkflow>('get_status_workflow', { bookId });
+  }
+
+  /**
+   * 保存书籍的自定义状态流程，仍有文档使用的状态不能删除
+   */
+  static async saveStatusWorkflow(bookId: string, workflow: StatusWorkflow): Promise<StatusWorkflow> {
+    return await handleTauriCall<StatusWorkflow>('save_status_workflow', { bookId, workflow });
+  }
+
+  /**
+   * 按状态流程改变文档状态，变化会记入状态历史
+   */
+  static async setDocumentStatus(bookId: string, documentId: string, status: string): Promise<DocumentConfig> {
+    return await handleTauriCall<DocumentConfig>('set_document_status', { bookId, documentId, status });
+  }
+
+  /**
+   * 获取文档的状态变化历史（从早到晚）
+   */
+  static async listStatusHistory(bookId: string, documentId: string): Promise<StatusTransition[]> {
+    return await handleTauriCall<StatusTransition[]>('list_status_history', { bookId, documentId });
+  }
+
+  // ===== 目录结构方法 =====
+
+  /**
+   * 获取书籍的目录树（卷、章、场景），节点带下级文档的合计字数
+   */
+  static async getDocumentTree(bookId: string): Promise<DocumentNode[]> {
+    return await handleTauriCall<DocumentNode[]>('get_document_tree', { bookId });
+  }
+
+  /**
+   * 把文档连同下级文档移到另一个上级文档之下，parentId 为空时移到顶层
+   */
+  static async moveDocument(bookId: string, documentId: string, parentId?: string, index?: number): Promise<DocumentNode[]> {
+    return await handleTauriCall<DocumentNode[]>('move_document', { bookId, documentId, parentId, index });
+  }
+
+  /**
+   * 按给出的顺序重排同一上级文档下的全部子文档
+   */
+  static async reorderDocuments(bookId: string, documentIds: string[], parentId?: string): Promise<DocumentNode[]> {
+    return await handleTauriCall<DocumentNode[]>('reorder_documents', { bookId, parentId, documentIds });
+  }
+
+  /**
+   * 重新连续编号所有文档，并修复失效的上级引用
+   */
+  static async renumberDocuments(bookId: string): Promise<DocumentNode[]> {
+    return await handleTauriCall<DocumentNode[]>('renumber_documents', { bookId });
+  }
+
+  // ===== 编辑日志方法 =====
+
+  /**
+   * 把编辑器的最新内容写入编辑日志（Web 环境下不记录）
+   */
+  static async appendDocumentJournal(bookId: string, documentId: string, content: string): Promise<void> {
+    if (!isTauriEnvironment()) {
+      return;
+    }
+    return await handleTauriCall<void>('append_document_journal', { bookId, documentId, content });
+  }
+
+  /**
+   * 列出异常退出后有未保存修改的文档
+   */
+  static async listRecoverableDocuments(): Promise<RecoverableDocument[]> {
+    if (!isTauriEnvironment()) {
+      return [];
+    }
+    return await handleTauriCall<RecoverableDocument[]>('list_recoverable_documents');
+  }
+
+  /**
+   * 恢复文档的未保存修改，返回恢复后的内容
+   */
+  static async recoverDocumentJournal(bookId: string, documentId: string): Promise<string> {
+    return await handleTauriCall<string>('recover_document_journal', { bookId, documentId });
+  }
+
+  /**
+   * 放弃文档的未保存修改
+   */
+  static async discardDocumentJournal(bookId: string, documentId: string): Promise<void> {
+    return await handleTauriCall<void>('discard_document_journal', { bookId, documentId });
+  }
+
+  // ===== 文档版本管理方法 =====
+
+  /**
+   * 为文档当前内容创建提交
+   */
+  static async createDocumentCommit(
+    bookId: string,
+    documentId: string,
+    message: string,
+    isAutoCommit: boolean = false
+  ): Promise<CommitInfo> {
+    return await handleTauriCall<CommitInfo>('create_document_commit', {
+      bookId,
+      documentId,
+      message,
+      isAutoCommit,
+    });
+  }
+
+  /**
+   * 列出文档的所有提交
+   */
+  static async listDocumentCommits(bookId: string, documentId: string): Promise<CommitInfo[]> {
+    return await handleTauriCall<CommitInfo[]>('list_document_commits', { bookId, documentId });
+  }
+
+  /**
+   * 加载某次提交的文档内容
+   */
+  static async loadDocumentCommit(
+    bookId: string,
+    documentId: string,
+    commitId: string
+  ): Promise<string> {
+    return await handleTauriCall<string>('load_document_commit', { bookId, documentId, commitId });
+  }
+
+  /**
+   * 删除文档的某次提交
+   */
+  static async deleteDocumentCommit(
+    bookId: string,
+    documentId: string,
+    commitId: string
+  ): Promise<void> {
+    return await handleTauriCall<void>('delete_document_commit', { bookId, documentId, commitId });
+  }
+
+  /**
+   * 重建文档历史的增量链
+   */
+  static async repackDocument(bookId: string, documentId: string): Promise<RepackStats> {
+    return await handleTauriCall<RepackStats>('repack_document', { bookId, documentId });
+  }
+
+  // ===== 文档分支管理方法 =====
+
+  /**
+   * 列出文档的分支
+   */
+  static async listDocumentBranches(bookId: string, documentId: string): Promise<DocumentRefs> {
+    return await handleTauriCall<DocumentRefs>('list_document_branches', { bookId, documentId });
+  }
+
+  /**
+   * 创建文档分支，默认从当前分支的最新提交开始
+   */
+  static async createDocumentBranch(
+    bookId: string,
+    documentId: string,
+    name: string,
+    fromCommitId?: string
+  ): Promise<BranchInfo> {
+    return await handleTauriCall<BranchInfo>('create_document_branch', {
+      bookId,
+      documentId,
+      name,
+      fromCommitId,
+    });
+  }
+
+  /**
+   * 切换文档分支，返回该分支的最新内容
+   */
+  static async switchDocumentBranch(
+    bookId: string,
+    documentId: string,
+    branchId: string
+  ): Promise<string> {
+    return await handleTauriCall<string>('switch_document_branch', { bookId, documentId, branchId });
+  }
+
+  /**
+   * 重命名文档分支
+   */
+  static async renameDocumentBranch(
+    bookId: string,
+    documentId: string,
+    branchId: string,
+    newName: string
+  ): Promise<BranchInfo> {
+    return await handleTauriCall<BranchInfo>('rename_document_branch', {
+      bookId,
+      documentId,
+      branchId,
+      newName,
+    });
+  }
+
+  /**
+   * 删除文档分支
+   */
+  static async deleteDocumentBranch(
+    bookId: string,
+    documentId: string,
+    branchId: string
+  ): Promise<void> {
+    return await handleTauriCall<void>('delete_document_branch', { bookId, documentId, branchId });
+  }
+
+  // ===== 文档合并方法 =====
+
+  /**
+   * 三方合并文本
+   */
+  static async mergeText(base: string, ours: string, theirs: string): Promise<MergeResult> {
+    return await handleTauriCall<MergeResult>('merge_text', { base, ours, theirs });
+  }
+
+  /**
+   * 把另一个分支合并到当前分支，有冲突时不修改文档
+   */
+  static async mergeDocumentBranch(
+    bookId: string,
+    documentId: string,
+    branchId: string,
+    message: string
+  ): Promise<DocumentMergeOutcome> {
+    return await handleTauriCall<DocumentMergeOutcome>('merge_document_branch', {
+      bookId,
+      documentId,
+      branchId,
+      message,
+    });
+  }
+
+  /**
+   * 用解决冲突后的内容完成合并
+   */
+  static async resolveDocumentMerge(
+    bookId: string,
+    documentId: string,
+    theirsCommitId: string,
+    content: string,
+    message: string
+  ): Promise<CommitInfo> {
+    return await handleTauriCall<CommitInfo>('resolve_document_merge', {
+      bookId,
+      documentId,
+      theirsCommitId,
+      content,
+      message,
+    });
+  }
+
+  // ===== 差异比较方法 =====
+
+  /**
+   * 比较两段文本
+   */
+  static async diffText(
+    oldText: string,
+    newText: string,
+    granularity: DiffGranularity = 'line'
+  ): Promise<VersionDiffResult> {
+    return await handleTauriCall<VersionDiffResult>('diff_text', { oldText, newText, granularity });
+  }
+
+  /**
+   * 比较文档的两个版本，不指定提交时使用工作副本
+   */
+  static async diffVersions(
+    bookId: string,
+    documentId: string,
+    oldCommitId?: string,
+    newCommitId?: string,
+    granularity: DiffGranularity = 'line'
+  ): Promise<VersionDiffResult> {
+    return await handleTauriCall<VersionDiffResult>('diff_versions', {
+      bookId,
+      documentId,
+      oldCommitId,
+      newCommitId,
+      granularity,
+    });
+  }
 }
 
 /**
@@ -2,7 +2,7 @@
  * 存储适配器 - 自动选择 SQLite 或 localStorage
  */
 
-import type { BookConfig, DocumentConfig } from './fileSystemService'
+import type { BookConfig, DocumentConfig, DocumentType } from './fileSystemService'
 
 // 检测是否在 Tauri 环境
 const isTauriEnvironment = () => {
@@ -148,7 +148,7 @@ export async function deleteBook(bookId: string): Promise<void> {
 export async function createDocument(
   bookId: string,
   title: string,
-  type: string
+  type: DocumentType
 ): Promise<DocumentConfig> {
   if (useSQLite) {
     const { createDocument: createDocDB, getDocumentsByBook } = await import('./database')
@@ -170,7 +170,7 @@ export async function createDocument(
       book_id: doc.book_id,
       title: doc.title,
       order: doc.order_num,
-      type: doc.type,
+      doc_type: doc.type as DocumentType,
       created_at: doc.created_at,
       last_modified: doc.last_modified,
       word_count: doc.word_count,
@@ -193,7 +193,7 @@ export async function listDocuments(bookId: string): Promise<DocumentConfig[]> {
       book_id: doc.book_id,
       title: doc.title,
       order: doc.order_num,
-      type: doc.type,
+      doc_type: doc.type as DocumentType,
       created_at: doc.created_at,
       last_modified: doc.last_modified,
       word_count: doc.word_count,
@@ -2,7 +2,7 @@
  * Web 环境适配器 - 为纯 web 环境提供基本功能
  */
 
-import type { ProjectConfig, DocumentConfig } from './fileSystemService';
+import type { ProjectConfig, DocumentConfig, DocumentType } from './fileSystemService';
 
 // 模拟的项目数据存储
 const STORAGE_KEY = 'branchwrite_projects';
@@ -152,7 +152,7 @@ export class WebFileSystemAdapter {
   static async createDocument(
     projectId: string,
     title: string,
-    docType: string
+    docType: DocumentType
   ): Promise<DocumentConfig> {
     console.log('💾 WebAdapter: 开始创建文档', { projectId, title, docType })
     const documents = await this.listDocuments(projectId);
@@ -163,7 +163,7 @@ export class WebFileSystemAdapter {
       book_id: projectId,
       title,
       order: documents.length + 1,
-      type: docType,
+      doc_type: docType,
       created_at: new Date().toISOString(),
       last_modified: new Date().toISOString(),
       word_count: 0,
@@ -3,6 +3,7 @@ import { ref, computed } from 'vue'
 import type { AppState, CommitInfo, ProjectConfig, BookConfig, BookData, DocumentConfig } from '../types/index'
 import { DocumentManager } from '../models/DocumentManager'
 import { FileSystemService } from '../services/fileSystemService'
+import type { DocumentType } from '../services/fileSystemService'
 
 export const useAppStore = defineStore('app', () => {
   // 状态
@@ -214,7 +215,7 @@ export const useAppStore = defineStore('app', () => {
   }
 
   // 文档管理
-  const createDocument = async (bookId: string, title: string, docType: string) => {
+  const createDocument = async (bookId: string, title: string, docType: DocumentType) => {
     console.log('🏪 Store: 开始创建文档', { bookId, title, docType })
     try {
       const newDoc = await FileSystemService.createDocument(bookId, title, docType)
@@ -304,6 +305,32 @@ export const useAppStore = defineStore('app', () => {
     }
   }
 
+  const journalDocumentContent = async (bookId: string, documentId: string, content: string) => {
+    try {
+      await FileSystemService.appendDocumentJournal(bookId, documentId, content)
+    } catch (err) {
+      console.error('Failed to write document journal:', err)
+    }
+  }
+
+  // 异常退出后询问是否恢复未保存的修改
+  const recoverUnsavedChanges = async () => {
+    try {
+      const recoverable = await FileSystemService.listRecoverableDocuments()
+      for (const doc of recoverable) {
+        const editedAt = new Date(doc.journaled_at).toLocaleString()
+        const message = `《${doc.book_name}》中的「${doc.document_title}」有未保存的修改（${editedAt}），是否恢复？`
+        if (window.confirm(message)) {
+          await FileSystemService.recoverDocumentJournal(doc.book_id, doc.document_id)
+        } else {
+          await FileSystemService.discardDocumentJournal(doc.book_id, doc.document_id)
+        }
+      }
+    } catch (err) {
+      console.error('Failed to recover unsaved changes:', err)
+    }
+  }
+
   const selectDocument = (document: DocumentConfig) => {
     currentDocumentConfig.value = document
   }
@@ -364,6 +391,8 @@ export const useAppStore = defineStore('app', () => {
     loadDocuments,
     loadDocumentContent,
     saveDocumentContent,
+    journalDocumentContent,
+    recoverUnsavedChanges,
     selectDocument,
     initializeDocumentManager,
     updateSettings,
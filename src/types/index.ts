@@ -1,3 +1,5 @@
+import type { DocumentType } from '../services/fileSystemService';
+
 // 核心数据类型定义
 
 export interface Commit {
@@ -85,7 +87,7 @@ export interface DocumentConfig {
   book_id: string; // 关联的书籍ID
   title: string;
   order: number; // 在书籍中的顺序
-  type: string; // 文档类型: 'chapter' | 'section' | 'note'
+  doc_type: DocumentType;
   created_at: string;
   last_modified: string;
   word_count: number;
@@ -117,7 +119,7 @@ export interface TauriCommands {
   deleteBook: (bookId: string) => Promise<void>;
 
   // 文档管理命令
-  createDocument: (bookId: string, title: string, docType: string) => Promise<DocumentConfig>;
+  createDocument: (bookId: string, title: string, docType: DocumentType) => Promise<DocumentConfig>;
   listDocuments: (bookId: string) => Promise<DocumentConfig[]>;
   loadDocument: (bookId: string, documentId: string) => Promise<string>;
   saveDocument: (bookId: string, documentId: string, content: string) => Promise<void>;